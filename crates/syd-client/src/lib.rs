@@ -9,9 +9,40 @@ pub enum SydEvent {
     Brightness(u32),
     Media { status: String, title: String, artist: String },
     Battery(u32, String),
-    NetworkState(String),
+    NetworkState(NetworkStatus),
+    AccessPoints(Vec<WifiNet>),
+    SecretsRequested(SecretRequest),
+    SecretsCancelled(u32),
+    ConnectProgress { ssid: String, stage: ConnectStage },
+    VpnState(VpnProfile),
+    NetDevice(NetDevice),
+    NetDeviceRemoved(String),
+    Hotspot(HotspotStatus),
+    Traffic(Vec<TrafficRate>),
+    UsageWarning(DataUsage),
+    CaptivePortal(String),
+    Modem(Modem),
+    ModemRemoved(String),
+    Radio(RadioState),
+    AirplaneMode(bool),
     BtPower(bool),
+    BtAdapter(BtAdapter),
+    BtAdapterRemoved(String),
+    BtDeviceAdded(BtDevice),
+    BtDeviceRemoved(String),
+    BtDeviceChanged(BtDevice),
+    BtConnected(String),
+    BtDisconnected(String, DisconnectReason),
+    PairingRequested(PairingRequest),
+    PairingCancelled(u32),
+    TransferRequested(TransferRequest),
+    TransferRequestCancelled(u32),
+    Transfer(BtTransfer),
+    TransferFinished(BtTransfer),
     Notification(NotifData),
+    NotificationUpdated(NotifData),
+    NotificationExpired(u32),
+    NotificationClosed(u32, CloseReason),
     TrayItem(String),
 }
 
@@ -22,6 +53,8 @@ pub struct Syd {
     pub system: SystemProxy<'static>,
     pub network: NetworkProxy<'static>,
     pub bluetooth: BluetoothProxy<'static>,
+    pub bt_transfer: BluetoothTransferProxy<'static>,
+    pub rfkill: RfkillProxy<'static>,
     pub notif: NotificationsProxy<'static>,
     pub tray_watcher: StatusNotifierWatcherProxy<'static>,
 }
@@ -36,6 +69,8 @@ impl Syd {
             system: SystemProxy::new(&c).await?,
             network: NetworkProxy::new(&c).await?,
             bluetooth: BluetoothProxy::new(&c).await?,
+            bt_transfer: BluetoothTransferProxy::new(&c).await?,
+            rfkill: RfkillProxy::new(&c).await?,
             notif: NotificationsProxy::new(&c).await?,
             tray_watcher: StatusNotifierWatcherProxy::builder(&c).path("/StatusNotifierWatcher")?.build().await?,
         }))
@@ -50,6 +85,37 @@ impl Syd {
         let mut s6 = self.bluetooth.receive_power_changed().await.unwrap();
         let mut s7 = self.notif.receive_received().await.unwrap();
         let mut s8 = self.tray_watcher.receive_status_notifier_item_registered().await.unwrap();
+        let mut s9 = self.network.receive_access_points_changed().await.unwrap();
+        let mut s10 = self.network.receive_secrets_requested().await.unwrap();
+        let mut s11 = self.network.receive_secrets_cancelled().await.unwrap();
+        let mut s12 = self.network.receive_connect_progress().await.unwrap();
+        let mut s13 = self.network.receive_vpn_state_changed().await.unwrap();
+        let mut s14 = self.network.receive_device_changed().await.unwrap();
+        let mut s15 = self.network.receive_device_removed().await.unwrap();
+        let mut s16 = self.network.receive_hotspot_changed().await.unwrap();
+        let mut s17 = self.network.receive_traffic_changed().await.unwrap();
+        let mut s18 = self.network.receive_usage_warning().await.unwrap();
+        let mut s19 = self.network.receive_captive_portal().await.unwrap();
+        let mut s20 = self.rfkill.receive_radio_changed().await.unwrap();
+        let mut s21 = self.rfkill.receive_airplane_mode_changed().await.unwrap();
+        let mut s22 = self.network.receive_modem_changed().await.unwrap();
+        let mut s23 = self.network.receive_modem_removed().await.unwrap();
+        let mut s24 = self.bluetooth.receive_device_added().await.unwrap();
+        let mut s25 = self.bluetooth.receive_device_removed().await.unwrap();
+        let mut s26 = self.bluetooth.receive_device_changed().await.unwrap();
+        let mut s27 = self.bluetooth.receive_pairing_requested().await.unwrap();
+        let mut s28 = self.bluetooth.receive_pairing_cancelled().await.unwrap();
+        let mut s29 = self.bluetooth.receive_device_connected().await.unwrap();
+        let mut s30 = self.bluetooth.receive_device_disconnected().await.unwrap();
+        let mut s31 = self.bluetooth.receive_adapter_changed().await.unwrap();
+        let mut s32 = self.bluetooth.receive_adapter_removed().await.unwrap();
+        let mut s33 = self.bt_transfer.receive_transfer_requested().await.unwrap();
+        let mut s34 = self.bt_transfer.receive_transfer_request_cancelled().await.unwrap();
+        let mut s35 = self.bt_transfer.receive_transfer_changed().await.unwrap();
+        let mut s36 = self.bt_transfer.receive_transfer_finished().await.unwrap();
+        let mut s37 = self.notif.receive_updated().await.unwrap();
+        let mut s38 = self.notif.receive_closed().await.unwrap();
+        let mut s39 = self.notif.receive_expired().await.unwrap();
 
         async_stream::stream! {
             loop {
@@ -58,11 +124,42 @@ impl Syd {
                     Some(m) = s2.next() => if let Ok(a) = m.args() { yield SydEvent::Brightness(a.new_val); },
                     Some(m) = s3.next() => if let Ok(a) = m.args() { yield SydEvent::Media { status: a.status, title: a.title, artist: a.artist }; },
                     Some(m) = s4.next() => if let Ok(a) = m.args() { yield SydEvent::Battery(a.percent, a.state); },
-                    Some(m) = s5.next() => if let Ok(a) = m.args() { yield SydEvent::NetworkState(a.state); },
+                    Some(m) = s5.next() => if let Ok(a) = m.args() { yield SydEvent::NetworkState(a.status); },
                     Some(m) = s6.next() => if let Ok(a) = m.args() { yield SydEvent::BtPower(a.enabled); },
                     Some(m) = s7.next() => if let Ok(a) = m.args() { yield SydEvent::Notification(a.note); },
                     
                     Some(m) = s8.next() => if let Ok(a) = m.args() { yield SydEvent::TrayItem(a.service_name); },
+                    Some(m) = s9.next() => if let Ok(a) = m.args() { yield SydEvent::AccessPoints(a.networks); },
+                    Some(m) = s10.next() => if let Ok(a) = m.args() { yield SydEvent::SecretsRequested(a.request); },
+                    Some(m) = s11.next() => if let Ok(a) = m.args() { yield SydEvent::SecretsCancelled(a.id); },
+                    Some(m) = s12.next() => if let Ok(a) = m.args() { yield SydEvent::ConnectProgress { ssid: a.ssid, stage: a.stage }; },
+                    Some(m) = s13.next() => if let Ok(a) = m.args() { yield SydEvent::VpnState(a.profile); },
+                    Some(m) = s14.next() => if let Ok(a) = m.args() { yield SydEvent::NetDevice(a.device); },
+                    Some(m) = s15.next() => if let Ok(a) = m.args() { yield SydEvent::NetDeviceRemoved(a.interface); },
+                    Some(m) = s16.next() => if let Ok(a) = m.args() { yield SydEvent::Hotspot(a.status); },
+                    Some(m) = s17.next() => if let Ok(a) = m.args() { yield SydEvent::Traffic(a.rates); },
+                    Some(m) = s18.next() => if let Ok(a) = m.args() { yield SydEvent::UsageWarning(a.usage); },
+                    Some(m) = s19.next() => if let Ok(a) = m.args() { yield SydEvent::CaptivePortal(a.url); },
+                    Some(m) = s20.next() => if let Ok(a) = m.args() { yield SydEvent::Radio(a.state); },
+                    Some(m) = s21.next() => if let Ok(a) = m.args() { yield SydEvent::AirplaneMode(a.enabled); },
+                    Some(m) = s22.next() => if let Ok(a) = m.args() { yield SydEvent::Modem(a.modem); },
+                    Some(m) = s23.next() => if let Ok(a) = m.args() { yield SydEvent::ModemRemoved(a.path); },
+                    Some(m) = s24.next() => if let Ok(a) = m.args() { yield SydEvent::BtDeviceAdded(a.device); },
+                    Some(m) = s25.next() => if let Ok(a) = m.args() { yield SydEvent::BtDeviceRemoved(a.mac); },
+                    Some(m) = s26.next() => if let Ok(a) = m.args() { yield SydEvent::BtDeviceChanged(a.device); },
+                    Some(m) = s27.next() => if let Ok(a) = m.args() { yield SydEvent::PairingRequested(a.request); },
+                    Some(m) = s28.next() => if let Ok(a) = m.args() { yield SydEvent::PairingCancelled(a.id); },
+                    Some(m) = s29.next() => if let Ok(a) = m.args() { yield SydEvent::BtConnected(a.mac); },
+                    Some(m) = s30.next() => if let Ok(a) = m.args() { yield SydEvent::BtDisconnected(a.mac, a.reason); },
+                    Some(m) = s31.next() => if let Ok(a) = m.args() { yield SydEvent::BtAdapter(a.adapter); },
+                    Some(m) = s32.next() => if let Ok(a) = m.args() { yield SydEvent::BtAdapterRemoved(a.name); },
+                    Some(m) = s33.next() => if let Ok(a) = m.args() { yield SydEvent::TransferRequested(a.request); },
+                    Some(m) = s34.next() => if let Ok(a) = m.args() { yield SydEvent::TransferRequestCancelled(a.id); },
+                    Some(m) = s35.next() => if let Ok(a) = m.args() { yield SydEvent::Transfer(a.transfer); },
+                    Some(m) = s36.next() => if let Ok(a) = m.args() { yield SydEvent::TransferFinished(a.transfer); },
+                    Some(m) = s37.next() => if let Ok(a) = m.args() { yield SydEvent::NotificationUpdated(a.note); },
+                    Some(m) = s38.next() => if let Ok(a) = m.args() { yield SydEvent::NotificationClosed(a.id, a.reason); },
+                    Some(m) = s39.next() => if let Ok(a) = m.args() { yield SydEvent::NotificationExpired(a.id); },
                 }
             }
         }
@@ -1,14 +1,437 @@
 use zbus::{proxy, zvariant};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 
-#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default)]
+/// Bits for [`WifiNet::security_flags`].
+pub mod wifi_security {
+    pub const WEP: u32 = 1 << 0;
+    pub const WPA: u32 = 1 << 1;
+    pub const WPA2: u32 = 1 << 2;
+    pub const WPA3: u32 = 1 << 3;
+    pub const ENTERPRISE: u32 = 1 << 4;
+    pub const OWE: u32 = 1 << 5;
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum WifiBand {
+    #[default]
+    Unknown,
+    #[serde(rename = "2.4GHz")]
+    Ghz2_4,
+    #[serde(rename = "5GHz")]
+    Ghz5,
+    #[serde(rename = "6GHz")]
+    Ghz6,
+}
+
+impl WifiBand {
+    pub fn from_frequency(mhz: u32) -> Self {
+        match mhz {
+            2400..=2500 => Self::Ghz2_4,
+            5150..=5925 => Self::Ghz5,
+            5926..=7125 => Self::Ghz6,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
 pub struct WifiNet {
     pub ssid: String,
     pub strength: u8,
     pub security: String,
     pub known: bool,
     pub active: bool,
+    /// Strongest access point seen for this SSID.
+    pub bssid: String,
+    /// In MHz.
+    pub frequency: u32,
+    pub band: WifiBand,
+    pub channel: u32,
+    /// In kbit/s.
+    pub max_bitrate: u32,
+    /// See [`wifi_security`].
+    pub security_flags: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum ConnectionType {
+    #[default]
+    None,
+    Wifi,
+    Ethernet,
+    Vpn,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    Connecting,
+    /// Connected, but without full internet access.
+    Limited,
+    /// Connected behind a captive portal that needs a sign-in.
+    Portal,
+    Connected,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum Connectivity {
+    #[default]
+    Unknown,
+    None,
+    Portal,
+    Limited,
+    Full,
+}
+
+/// Snapshot of the primary network connection.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct NetworkStatus {
+    pub connection_type: ConnectionType,
+    pub state: ConnectionState,
+    pub connectivity: Connectivity,
+    /// Wi-Fi SSID, or the connection name for other types.
+    pub ssid: String,
+    /// Wi-Fi signal strength in percent, 0 for wired connections.
+    pub strength: u8,
+    pub interface: String,
+    /// Addresses in CIDR notation, e.g. `192.168.1.20/24`.
+    pub ipv4: Vec<String>,
+    pub ipv6: Vec<String>,
+    pub gateway: String,
+    pub dns: Vec<String>,
+    /// Name of the active VPN or WireGuard connection, empty if none.
+    pub vpn: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum DeviceType {
+    #[default]
+    Unknown,
+    Ethernet,
+    Wifi,
+    Bridge,
+    Tun,
+    Modem,
+    Wireguard,
+    Loopback,
+    Other,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum DeviceState {
+    #[default]
+    Unknown,
+    /// Not handled by the network manager, e.g. container bridges.
+    Unmanaged,
+    /// Present but unusable: radio off, cable unplugged or missing firmware.
+    Unavailable,
+    Disconnected,
+    Connecting,
+    Connected,
+    Disconnecting,
+    Failed,
+}
+
+/// A network interface and its current link.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct NetDevice {
+    pub interface: String,
+    pub device_type: DeviceType,
+    pub state: DeviceState,
+    pub mac: String,
+    /// Cable plugged in for ethernet, associated for Wi-Fi.
+    pub carrier: bool,
+    /// Link speed in Mb/s, 0 if unknown.
+    pub speed: u32,
+    pub ipv4: Vec<String>,
+    pub ipv6: Vec<String>,
+    /// Name of the connection active on the device, empty if none.
+    pub connection: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum KeyMgmt {
+    Open,
+    #[default]
+    Wpa2,
+    Wpa3Sae,
+    /// WPA-Enterprise (802.1X).
+    Enterprise,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum EapMethod {
+    #[default]
+    Peap,
+    Ttls,
+    Tls,
+}
+
+/// Everything `connect_advanced` needs to join a network. Empty strings mean "not set";
+/// missing secrets are asked for through `SecretsRequested`.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default)]
+pub struct WifiSettings {
+    pub ssid: String,
+    pub hidden: bool,
+    pub key_mgmt: KeyMgmt,
+    /// The PSK, the EAP password, or the private key password for EAP-TLS.
+    pub password: String,
+    pub eap: EapMethod,
+    pub identity: String,
+    pub anonymous_identity: String,
+    /// Path to the CA certificate.
+    pub ca_cert: String,
+    /// Inner authentication for PEAP and TTLS, e.g. `mschapv2`.
+    pub phase2_auth: String,
+    /// Client certificate and private key paths, EAP-TLS only.
+    pub client_cert: String,
+    pub private_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum ConnectStage {
+    #[default]
+    Preparing,
+    Associating,
+    Authenticating,
+    GettingIp,
+    /// Checking connectivity or bringing up dependent connections.
+    Verifying,
+    Connected,
+    Failed,
+}
+
+/// Errors returned by `org.syd.Network` methods, as `org.syd.Network.Error.*` on the bus.
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "org.syd.Network.Error")]
+pub enum NetworkError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    WrongPassword(String),
+    NotFound(String),
+    Timeout(String),
+    DhcpFailed(String),
+    RadioDisabled(String),
+    /// The user dismissed the secrets prompt.
+    Cancelled(String),
+    Failed(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum SecretKind {
+    #[default]
+    Wifi,
+    /// 802.1X credentials, for WPA-Enterprise or wired authentication.
+    Enterprise,
+    Vpn,
+}
+
+/// A request from the network backend for secrets the shell has to ask the user for.
+/// Answer it with `provide_secrets` or `cancel_secrets`.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default)]
+pub struct SecretRequest {
+    pub id: u32,
+    pub kind: SecretKind,
+    /// Name of the connection profile.
+    pub connection: String,
+    /// Empty for non-Wi-Fi connections.
+    pub ssid: String,
+    /// Setting keys to fill in, e.g. `psk` or `identity` and `password`.
+    pub fields: Vec<String>,
+    /// Set when previously stored secrets were rejected, e.g. after a wrong password.
+    pub retry: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum VpnState {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+    Disconnecting,
+    /// The last activation attempt failed.
+    Failed,
+}
+
+/// A saved VPN or WireGuard profile.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct VpnProfile {
+    /// Profile name, as shown by NetworkManager.
+    pub id: String,
+    pub uuid: String,
+    /// `wireguard`, or the VPN plugin name such as `openvpn` or `vpnc`.
+    pub kind: String,
+    pub state: VpnState,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum Metered {
+    /// Left to the backend, which may guess from the connection, e.g. a phone hotspot.
+    #[default]
+    Auto,
+    Yes,
+    No,
+}
+
+/// A saved Wi-Fi or wired connection profile.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct SavedNetwork {
+    /// Profile name. Equal to the SSID for profiles syd creates.
+    pub id: String,
+    /// Empty when the backend has no UUIDs (iwd, wpa_supplicant).
+    pub uuid: String,
+    pub kind: ConnectionType,
+    /// Empty for wired profiles.
+    pub ssid: String,
+    pub autoconnect: bool,
+    /// Among available autoconnect profiles, the highest goes first.
+    pub priority: i32,
+    pub metered: Metered,
+    /// Unix time of the last successful connection, 0 if never.
+    pub last_used: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum HotspotState {
+    #[default]
+    Off,
+    Starting,
+    Active,
+    Stopping,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct HotspotStatus {
+    pub state: HotspotState,
+    pub ssid: String,
+    /// `Unknown` when the band is left to the driver.
+    pub band: WifiBand,
+    pub interface: String,
+}
+
+/// Credentials for a network in the form phones scan from a QR code.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default)]
+pub struct SharedNetwork {
+    pub ssid: String,
+    /// e.g. `WIFI:T:WPA;S:Home;P:secret;;`
+    pub uri: String,
+    /// The QR code as a grayscale PNG.
+    pub qr_png: Vec<u8>,
+    /// The QR code drawn with half-block characters, two rows of modules per line,
+    /// dark modules as blocks.
+    pub qr_text: String,
+}
+
+/// Throughput of one interface over the last sampling interval.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct TrafficRate {
+    pub interface: String,
+    /// Bytes per second.
+    pub rx: u64,
+    pub tx: u64,
+}
+
+/// Bytes moved over physical interfaces. Day counters reset at local midnight,
+/// month counters on the first of the month.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct DataUsage {
+    pub day_rx: u64,
+    pub day_tx: u64,
+    pub month_rx: u64,
+    pub month_tx: u64,
+    /// Traffic in both directions this month over connections marked as metered.
+    pub metered_month: u64,
+    /// Metered bytes per month after which `UsageWarning` fires, 0 if disabled.
+    pub warning_threshold: u64,
+}
+
+/// The fastest radio technology a modem is using.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum AccessTech {
+    #[default]
+    Unknown,
+    /// GSM, GPRS or EDGE.
+    Gsm,
+    /// UMTS or HSPA.
+    Umts,
+    Lte,
+    Nr5g,
+    /// CDMA 1x or EV-DO.
+    Cdma,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum SimLock {
+    #[default]
+    Unknown,
+    /// Unlocked, or no lock set.
+    None,
+    Pin,
+    /// Too many wrong PINs; only the PUK from the carrier unlocks it.
+    Puk,
+    /// A PIN2, network or other lock the shell cannot answer.
+    Other,
+}
+
+/// A mobile broadband modem as ModemManager sees it.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct Modem {
+    /// ModemManager object path, e.g. `/org/freedesktop/ModemManager1/Modem/0`.
+    pub path: String,
+    pub manufacturer: String,
+    pub model: String,
+    /// Off in software; nothing else works until it is enabled.
+    pub enabled: bool,
+    /// A mobile data connection is up.
+    pub connected: bool,
+    /// 0-100.
+    pub signal: u8,
+    pub access_tech: AccessTech,
+    /// Empty until the modem registers with a network.
+    pub operator: String,
+    pub roaming: bool,
+    pub sim_lock: SimLock,
+    /// Attempts left for the lock in `sim_lock`, 0 if unknown.
+    pub unlock_retries: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum RadioType {
+    Wlan,
+    Bluetooth,
+    Wwan,
+    Nfc,
+    /// UWB, GPS, FM and other rarely seen switches.
+    #[default]
+    Other,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RadioState {
+    pub radio: RadioType,
+    /// Turned off in software; set only if every switch of this type is.
+    pub soft_blocked: bool,
+    /// Turned off by a hardware switch or the firmware, which software cannot undo.
+    pub hard_blocked: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default)]
@@ -17,26 +440,244 @@ pub struct AudioDevice {
     pub description: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct BtAdapter {
+    /// The kernel's name for it, e.g. `hci0`. Methods taking an adapter accept this or the address.
+    pub name: String,
+    pub address: String,
+    pub alias: String,
+    pub powered: bool,
+    /// Visible to devices that are searching, e.g. a phone about to pair.
+    pub discoverable: bool,
+    pub pairable: bool,
+    /// Seconds until `discoverable` turns itself off again; 0 keeps it on.
+    pub discoverable_timeout: u32,
+    /// The adapter that power, devices and discovery refer to when none is named.
+    pub default: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
 pub struct BtDevice {
     pub mac: String,
+    /// The name the device reports, empty if it has not said.
     pub name: String,
+    /// What to show: the name the user gave the device, else `name`, else the address.
+    pub alias: String,
     pub connected: bool,
+    pub paired: bool,
+    /// Allowed to connect without asking.
+    pub trusted: bool,
+    /// Signal strength in dBm while discovering, 0 if unknown.
+    pub rssi: i16,
+    /// Icon name from the device's appearance or class, e.g. `audio-headset`.
+    pub icon: String,
+    /// Charge in percent, from BlueZ or else UPower; -1 if the device does not report one.
+    pub battery: i16,
+}
+
+/// Errors returned by `org.syd.Bluetooth` methods, as `org.syd.Bluetooth.Error.*` on the bus.
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "org.syd.Bluetooth.Error")]
+pub enum BluetoothError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    /// The adapter is off, or the device is off or out of range.
+    NotAvailable(String),
+    AuthFailed(String),
+    /// The device has none of the profiles BlueZ can connect, or they are in use.
+    ProfileUnavailable(String),
+    Timeout(String),
+    NotFound(String),
+    Failed(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum DisconnectReason {
+    /// BlueZ did not say, which older versions never do.
+    #[default]
+    Unknown,
+    /// Asked for on this machine.
+    Local,
+    /// The device ended the connection, e.g. it was switched off.
+    Remote,
+    /// The link was lost, usually by going out of range.
+    Timeout,
+    Authentication,
+    /// The machine is going to sleep.
+    Suspend,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum PairingKind {
+    /// Answer with the PIN typed on, or printed on, the device.
+    #[default]
+    PinCode,
+    /// Answer with the six-digit passkey shown on the device.
+    Passkey,
+    /// Show `passkey` so the user can type it on the device. Needs no answer; declining
+    /// cancels pairing.
+    DisplayPasskey,
+    /// Show the PIN in `passkey` so the user can type it on the device.
+    DisplayPinCode,
+    /// Ask whether `passkey` matches the one on the device.
+    Confirmation,
+    /// Ask whether to pair with a device that has no way to show or enter a code.
+    Authorization,
+    /// Ask whether the device may use the profile in `service`.
+    AuthorizeService,
+}
+
+/// A question from BlueZ while pairing or connecting. Answer it with `agent_reply`.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default)]
+pub struct PairingRequest {
+    pub id: u32,
+    pub kind: PairingKind,
+    pub mac: String,
+    pub alias: String,
+    /// The passkey, zero-padded to six digits, or the PIN to show; empty otherwise.
+    pub passkey: String,
+    /// For `DisplayPasskey`, how many digits have been typed on the device so far.
+    pub entered: u16,
+    /// For `AuthorizeService`, the profile UUID.
+    pub service: String,
+}
+
+/// Errors returned by `org.syd.BluetoothTransfer` methods, as
+/// `org.syd.BluetoothTransfer.Error.*` on the bus.
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "org.syd.BluetoothTransfer.Error")]
+pub enum TransferError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    /// obexd is not running, or the device does not take files.
+    NotAvailable(String),
+    NotFound(String),
+    Failed(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum TransferStatus {
+    #[default]
+    Queued,
+    Active,
+    Suspended,
+    Complete,
+    Error,
+}
+
+/// A file going to or coming from a device over OBEX Object Push.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct BtTransfer {
+    /// Pass to `cancel`.
+    pub id: String,
+    pub mac: String,
+    pub incoming: bool,
+    /// The name the file is sent under.
+    pub name: String,
+    /// The local file: the one being sent, or where a received one is saved.
+    pub filename: String,
+    /// In bytes; 0 if the sender did not say.
+    pub size: u64,
+    pub transferred: u64,
+    pub status: TransferStatus,
+}
+
+/// A device wants to send a file. Answer it with `agent_reply`.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default)]
+pub struct TransferRequest {
+    pub id: u32,
+    pub mac: String,
+    pub alias: String,
+    pub name: String,
+    pub size: u64,
+    /// The MIME type, if the sender gave one.
+    pub mime: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[zvariant(signature = "s")]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    /// Stays up until the user dismisses it.
+    Critical,
+}
+
+/// A button the app put on a notification.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default, PartialEq)]
+pub struct NotifAction {
+    /// What `invoke_action` takes and the app gets back.
+    pub key: String,
+    pub label: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Default)]
 pub struct NotifData {
+    /// Assigned by the daemon, counting up from 1; what `Notify` returned to the app.
+    pub id: u32,
+    /// Not yet closed, expired or dismissed: its actions can still be invoked. Closed ones
+    /// stay in the history.
+    pub open: bool,
     pub summary: String,
     pub body: String,
     pub app_name: String,
+    /// In the order the app gave them, without the default action.
+    pub actions: Vec<NotifAction>,
+    /// Clicking the notification itself invokes the action `default`.
+    pub default_action: bool,
+    pub urgency: Urgency,
+    /// What it is about, e.g. `im.received` or `device.added`; empty if the app did not say.
+    pub category: String,
+    /// The sending app's desktop file, without `.desktop`.
+    pub desktop_entry: String,
+    /// The app's icon: a name from the icon theme or a file path.
+    pub app_icon: String,
+    /// A picture to show, such as a chat avatar: a file path or an icon name, empty if none.
+    /// Pixels sent inline are saved as a PNG in the daemon's cache.
+    pub image: String,
+    /// Show it, but leave it out of the history.
+    pub transient: bool,
+    /// Stays open after one of its actions is invoked.
+    pub resident: bool,
+    pub sound_file: String,
+    pub suppress_sound: bool,
+    /// Where on screen the app would like it, or -1 for no preference.
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Why a notification went away, as the spec's `NotificationClosed` reasons 1 to 4.
+#[derive(Debug, Serialize, Deserialize, zbus::zvariant::Type, Clone, Copy, Default, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum CloseReason {
+    Expired,
+    /// By the user, through `close` or `clear_history`.
+    Dismissed,
+    /// By the app that sent it, or by syd when it fell out of a full history.
+    Closed,
+    #[default]
+    Undefined,
 }
 
 #[proxy(interface = "org.syd.Notifications", default_service = "org.syd.Daemon", default_path = "/org/syd/Notifications")]
 pub trait Notifications {
     #[zbus(signal)] fn received(&self, note: NotifData) -> zbus::Result<()>;
+    #[zbus(signal)] fn updated(&self, note: NotifData) -> zbus::Result<()>;
+    /// The popup of `id` timed out and should go; it stays in the history, closed. `closed`
+    /// with reason `Expired` follows, so hide popups on `closed` only for other reasons.
+    #[zbus(signal)] fn expired(&self, id: u32) -> zbus::Result<()>;
+    #[zbus(signal)] fn closed(&self, id: u32, reason: CloseReason) -> zbus::Result<()>;
     fn get_history(&self) -> zbus::Result<Vec<NotifData>>;
     fn clear_history(&self) -> zbus::Result<()>;
     fn close(&self, id: u32) -> zbus::Result<()>;
+    fn remove(&self, id: u32) -> zbus::Result<()>;
+    fn invoke_action(&self, id: u32, key: String) -> zbus::Result<()>;
+    /// Like `invoke_action`, with an XDG activation token that lets the app raise its window.
+    fn invoke_action_with_token(&self, id: u32, key: String, activation_token: String) -> zbus::Result<()>;
 }
 
 #[proxy(interface = "org.kde.StatusNotifierWatcher", default_service = "org.syd.Daemon", default_path = "/StatusNotifierWatcher")]
@@ -101,21 +742,108 @@ pub trait Brightness {
     #[zbus(signal)] fn brightness_changed(&self, new_val: u32) -> zbus::Result<()>;
 }
 
-#[proxy(interface = "org.syd.Bluetooth", default_service = "org.syd.Daemon", default_path = "/org/syd/Bluetooth")]
-pub trait Bluetooth {
-    fn get_power(&self) -> zbus::Result<bool>;
-    fn set_power(&self, enabled: bool) -> zbus::Result<()>;
-    fn get_devices(&self) -> zbus::Result<Vec<BtDevice>>;
-    fn connect_device(&self, mac: String, connect: bool) -> zbus::Result<()>;
-    #[zbus(signal)] fn power_changed(&self, enabled: bool) -> zbus::Result<()>;
+/// In its own module because its device signals would generate the same types as
+/// org.syd.Network's. org.syd.BluetoothTransfer lives next to it.
+pub mod bluetooth {
+    use super::*;
+
+    #[proxy(interface = "org.syd.Bluetooth", default_service = "org.syd.Daemon", default_path = "/org/syd/Bluetooth")]
+    pub trait Bluetooth {
+        fn get_power(&self) -> zbus::Result<bool>;
+        fn set_power(&self, enabled: bool) -> zbus::Result<()>;
+        fn get_devices(&self) -> zbus::Result<Vec<BtDevice>>;
+        fn connect_device(&self, mac: String, connect: bool) -> Result<(), BluetoothError>;
+        fn start_discovery(&self) -> zbus::Result<()>;
+        fn stop_discovery(&self) -> zbus::Result<()>;
+        fn pair(&self, mac: String) -> Result<(), BluetoothError>;
+        fn trust(&self, mac: String, trusted: bool) -> Result<(), BluetoothError>;
+        fn remove(&self, mac: String) -> Result<(), BluetoothError>;
+        fn agent_reply(&self, id: u32, accept: bool, value: String) -> zbus::Result<()>;
+        fn get_adapters(&self) -> zbus::Result<Vec<BtAdapter>>;
+        fn set_default_adapter(&self, adapter: String) -> Result<(), BluetoothError>;
+        fn set_adapter_power(&self, adapter: String, enabled: bool) -> Result<(), BluetoothError>;
+        fn set_discoverable(&self, adapter: String, discoverable: bool) -> Result<(), BluetoothError>;
+        fn set_discoverable_timeout(&self, adapter: String, seconds: u32) -> Result<(), BluetoothError>;
+        fn set_pairable(&self, adapter: String, pairable: bool) -> Result<(), BluetoothError>;
+        fn set_adapter_alias(&self, adapter: String, alias: String) -> Result<(), BluetoothError>;
+        #[zbus(signal)] fn power_changed(&self, enabled: bool) -> zbus::Result<()>;
+        #[zbus(signal)] fn adapter_changed(&self, adapter: BtAdapter) -> zbus::Result<()>;
+        #[zbus(signal)] fn adapter_removed(&self, name: String) -> zbus::Result<()>;
+        #[zbus(signal)] fn device_added(&self, device: BtDevice) -> zbus::Result<()>;
+        #[zbus(signal)] fn device_removed(&self, mac: String) -> zbus::Result<()>;
+        #[zbus(signal)] fn device_changed(&self, device: BtDevice) -> zbus::Result<()>;
+        #[zbus(signal)] fn device_connected(&self, mac: String) -> zbus::Result<()>;
+        #[zbus(signal)] fn device_disconnected(&self, mac: String, reason: DisconnectReason) -> zbus::Result<()>;
+        #[zbus(signal)] fn pairing_requested(&self, request: PairingRequest) -> zbus::Result<()>;
+        #[zbus(signal)] fn pairing_cancelled(&self, id: u32) -> zbus::Result<()>;
+    }
+
+    #[proxy(interface = "org.syd.BluetoothTransfer", default_service = "org.syd.Daemon", default_path = "/org/syd/BluetoothTransfer")]
+    pub trait BluetoothTransfer {
+        fn send_files(&self, mac: String, files: Vec<String>) -> Result<Vec<String>, TransferError>;
+        fn get_transfers(&self) -> zbus::Result<Vec<BtTransfer>>;
+        fn cancel(&self, id: String) -> Result<(), TransferError>;
+        fn agent_reply(&self, id: u32, accept: bool) -> zbus::Result<()>;
+        #[zbus(signal)] fn transfer_requested(&self, request: TransferRequest) -> zbus::Result<()>;
+        #[zbus(signal)] fn transfer_request_cancelled(&self, id: u32) -> zbus::Result<()>;
+        #[zbus(signal)] fn transfer_changed(&self, transfer: BtTransfer) -> zbus::Result<()>;
+        #[zbus(signal)] fn transfer_finished(&self, transfer: BtTransfer) -> zbus::Result<()>;
+    }
+}
+pub use bluetooth::{BluetoothProxy, BluetoothProxyBlocking, BluetoothTransferProxy, BluetoothTransferProxyBlocking};
+
+#[proxy(interface = "org.syd.Rfkill", default_service = "org.syd.Daemon", default_path = "/org/syd/Rfkill")]
+pub trait Rfkill {
+    fn get_radios(&self) -> zbus::Result<Vec<RadioState>>;
+    fn get_airplane_mode(&self) -> zbus::Result<bool>;
+    fn set_airplane_mode(&self, enabled: bool) -> zbus::Result<()>;
+    fn set_radio_blocked(&self, radio: RadioType, blocked: bool) -> zbus::Result<()>;
+    #[zbus(signal)] fn radio_changed(&self, state: RadioState) -> zbus::Result<()>;
+    #[zbus(signal)] fn airplane_mode_changed(&self, enabled: bool) -> zbus::Result<()>;
 }
 
 #[proxy(interface = "org.syd.Network", default_service = "org.syd.Daemon", default_path = "/org/syd/Network")]
 pub trait Network {
-    fn get_state(&self) -> zbus::Result<String>;
+    fn get_state(&self) -> zbus::Result<NetworkStatus>;
     fn set_wifi(&self, enabled: bool) -> zbus::Result<()>;
     fn scan(&self) -> zbus::Result<Vec<WifiNet>>;
-    fn connect(&self, ssid: String, pass: String) -> zbus::Result<String>;
+    fn request_scan(&self) -> zbus::Result<()>;
+    fn connect(&self, ssid: String, pass: String) -> Result<(), NetworkError>;
+    fn connect_advanced(&self, settings: WifiSettings) -> Result<(), NetworkError>;
     fn forget(&self, ssid: String) -> zbus::Result<()>;
-    #[zbus(signal)] fn state_changed(&self, state: String) -> zbus::Result<()>;
+    fn provide_secrets(&self, id: u32, secrets: HashMap<String, String>) -> zbus::Result<()>;
+    fn cancel_secrets(&self, id: u32) -> zbus::Result<()>;
+    fn get_devices(&self) -> zbus::Result<Vec<NetDevice>>;
+    fn list_vpns(&self) -> zbus::Result<Vec<VpnProfile>>;
+    fn activate_vpn(&self, id: String) -> Result<(), NetworkError>;
+    fn deactivate_vpn(&self, id: String) -> Result<(), NetworkError>;
+    fn get_hotspot(&self) -> zbus::Result<HotspotStatus>;
+    fn start_hotspot(&self, ssid: String, password: String, band: WifiBand) -> Result<(), NetworkError>;
+    fn stop_hotspot(&self) -> Result<(), NetworkError>;
+    fn share_network(&self, ssid: String) -> Result<SharedNetwork, NetworkError>;
+    fn set_traffic_interval(&self, interval_ms: u32) -> zbus::Result<()>;
+    fn check_connectivity(&self) -> zbus::Result<Connectivity>;
+    fn get_data_usage(&self) -> zbus::Result<DataUsage>;
+    fn list_saved_networks(&self) -> zbus::Result<Vec<SavedNetwork>>;
+    fn set_autoconnect(&self, id: String, enabled: bool) -> Result<(), NetworkError>;
+    fn set_priority(&self, id: String, priority: i32) -> Result<(), NetworkError>;
+    fn set_metered(&self, id: String, metered: Metered) -> Result<(), NetworkError>;
+    fn rename_network(&self, id: String, name: String) -> Result<(), NetworkError>;
+    fn list_modems(&self) -> zbus::Result<Vec<Modem>>;
+    fn set_mobile_data(&self, modem: String, enabled: bool) -> Result<(), NetworkError>;
+    fn unlock_modem(&self, modem: String, pin: String) -> Result<(), NetworkError>;
+    #[zbus(signal)] fn state_changed(&self, status: NetworkStatus) -> zbus::Result<()>;
+    #[zbus(signal)] fn access_points_changed(&self, networks: Vec<WifiNet>) -> zbus::Result<()>;
+    #[zbus(signal)] fn secrets_requested(&self, request: SecretRequest) -> zbus::Result<()>;
+    #[zbus(signal)] fn secrets_cancelled(&self, id: u32) -> zbus::Result<()>;
+    #[zbus(signal)] fn connect_progress(&self, ssid: String, stage: ConnectStage) -> zbus::Result<()>;
+    #[zbus(signal)] fn vpn_state_changed(&self, profile: VpnProfile) -> zbus::Result<()>;
+    #[zbus(signal)] fn device_changed(&self, device: NetDevice) -> zbus::Result<()>;
+    #[zbus(signal)] fn device_removed(&self, interface: String) -> zbus::Result<()>;
+    #[zbus(signal)] fn hotspot_changed(&self, status: HotspotStatus) -> zbus::Result<()>;
+    #[zbus(signal)] fn traffic_changed(&self, rates: Vec<TrafficRate>) -> zbus::Result<()>;
+    #[zbus(signal)] fn usage_warning(&self, usage: DataUsage) -> zbus::Result<()>;
+    #[zbus(signal)] fn captive_portal(&self, url: String) -> zbus::Result<()>;
+    #[zbus(signal)] fn modem_changed(&self, modem: Modem) -> zbus::Result<()>;
+    #[zbus(signal)] fn modem_removed(&self, path: String) -> zbus::Result<()>;
 }
\ No newline at end of file
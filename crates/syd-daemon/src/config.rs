@@ -0,0 +1,74 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Settings read from `$XDG_CONFIG_HOME/syd/config.toml`. Every key is optional.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub network: NetworkConfig,
+    pub notifications: NotificationsConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub backend: NetworkBackend,
+    /// Warn once a month when traffic over metered connections passes this many MiB; 0 disables.
+    pub metered_warning_mb: u64,
+    /// Plain-HTTP URL probed to find a captive portal's sign-in page. Empty uses
+    /// NetworkManager's own connectivity check URL.
+    pub connectivity_check_uri: String,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// How long low-urgency notifications stay up when the app leaves it to syd; 0 keeps
+    /// them until dismissed. Critical ones always stay.
+    pub low_timeout_ms: u32,
+    /// The same for normal urgency.
+    pub normal_timeout_ms: u32,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self { Self { low_timeout_ms: 5000, normal_timeout_ms: 10000 } }
+}
+
+/// The service that does the actual networking. `auto` picks whichever is running.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkBackend {
+    #[default]
+    Auto,
+    NetworkManager,
+    Iwd,
+    /// systemd-networkd with wpa_supplicant for Wi-Fi.
+    Networkd,
+}
+
+impl Config {
+    /// Falls back to defaults if the file is missing or unreadable.
+    pub fn load() -> Self {
+        let path = config_dir().join("config.toml");
+        let Ok(text) = std::fs::read_to_string(&path) else { return Self::default() };
+        toml::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Ignoring {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+}
+
+pub fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    match std::env::var_os(var) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(fallback),
+    }
+}
+
+pub fn config_dir() -> PathBuf { xdg_dir("XDG_CONFIG_HOME", ".config").join("syd") }
+
+/// Where syd keeps files it can recreate, such as notification images.
+pub fn cache_dir() -> PathBuf { xdg_dir("XDG_CACHE_HOME", ".cache").join("syd") }
+
+/// Where syd keeps data that should survive restarts, such as usage counters.
+pub fn state_dir() -> PathBuf { xdg_dir("XDG_STATE_HOME", ".local/state").join("syd") }
@@ -1,55 +1,120 @@
+mod config;
 mod services;
-use zbus::ConnectionBuilder;
-use services::{audio, brightness, media, system, network, bluetooth, notifications, tray};
+use zbus::{Connection, ConnectionBuilder};
+use services::{audio, brightness, media, system, network, bluetooth, notifications, rfkill, tray};
 use std::error::Error;
 use tokio::sync::mpsc;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     println!("--- Syd Daemon Starting ---");
+    let config = config::Config::load();
 
+    // Networking and Bluetooth need the system bus; everything else carries on without it.
+    let sys_conn = Connection::system().await
+        .map_err(|e| eprintln!("No system bus, so no networking or Bluetooth: {}", e)).ok();
     
     let (audio_svc, audio_cache) = audio::AudioService::new();
     let (bright_svc, bright_cache) = brightness::BrightnessService::new();
-    let (bt_svc, bt_cache) = bluetooth::BluetoothService::new();
+    let (rfkill_svc, rfkill_switches) = rfkill::RfkillService::new();
     let media_svc = media::MediaService;
     let system_svc = system::SystemService;
-    let network_svc = network::NetworkService;
     
     
     let (notif_svc, notif_hist) = notifications::NotificationService::new();
-    let fdo_notif = notifications::FdoNotificationServer::new(notif_hist);
+    let fdo_notif = notifications::FdoNotificationServer::new(notif_hist, config.notifications);
     
     
     let (tray_tx, tray_rx) = mpsc::unbounded_channel();
     let tray_svc = tray::TrayService::new(tray_tx);
 
     
-    let conn = ConnectionBuilder::session()?
+    let mut builder = ConnectionBuilder::session()?
         .name("org.syd.Daemon")?
         
         .serve_at("/org/syd/Audio", audio_svc)?
         .serve_at("/org/syd/Brightness", bright_svc)?
         .serve_at("/org/syd/Media", media_svc)?
         .serve_at("/org/syd/System", system_svc)?
-        .serve_at("/org/syd/Network", network_svc)?
-        .serve_at("/org/syd/Bluetooth", bt_svc)?
+        .serve_at("/org/syd/Rfkill", rfkill_svc)?
         
         .serve_at("/org/syd/Notifications", notif_svc)?
         .serve_at("/org/freedesktop/Notifications", fdo_notif)?
         .name("org.freedesktop.Notifications")?
         
         .serve_at("/StatusNotifierWatcher", tray_svc)? 
-        .name("org.kde.StatusNotifierWatcher")?
-        .build().await?;
+        .name("org.kde.StatusNotifierWatcher")?;
+
+    let mut network_parts = None;
+    let mut bluetooth_parts = None;
+    if let Some(sys_conn) = &sys_conn {
+        let (secrets_tx, secrets_rx) = mpsc::unbounded_channel();
+        let (prompter, pending_secrets) = network::agent::Prompter::new(secrets_tx);
+        let backend = network::backend::select(sys_conn.clone(), config.network.backend, prompter.clone()).await;
+        let modems = network::modem::Modems::new(sys_conn.clone(), backend.clone());
+        let (network_svc, traffic) = network::NetworkService::new(backend.clone(), modems.clone(), pending_secrets, config.network.connectivity_check_uri.clone());
+        builder = builder.serve_at("/org/syd/Network", network_svc)?;
+        network_parts = Some((backend, modems, traffic, prompter, secrets_rx));
+
+        let bluez = bluetooth::bluez::Bluez::new(sys_conn.clone());
+        let (pairing_tx, pairing_rx) = mpsc::unbounded_channel();
+        let pairing = bluetooth::agent::Pairing::new(pairing_tx);
+        let bt_svc = bluetooth::BluetoothService::new(bluez.clone(), pairing.clone());
+        builder = builder.serve_at("/org/syd/Bluetooth", bt_svc)?;
+        // obexd lives on the session bus; its agent needs a connection of its own, like BlueZ's.
+        let transfers = match Connection::session().await {
+            Ok(c) => {
+                let obex = bluetooth::obex::Obex::new(c);
+                let (transfer_tx, transfer_rx) = mpsc::unbounded_channel();
+                let transfer_requests = bluetooth::transfer::Requests::new(transfer_tx);
+                let transfer_svc = bluetooth::transfer::BluetoothTransferService::new(obex.clone(), transfer_requests.clone());
+                builder = builder.serve_at("/org/syd/BluetoothTransfer", transfer_svc)?;
+                Some((obex, transfer_requests, transfer_rx))
+            }
+            Err(e) => { eprintln!("No Bluetooth file transfers: {}", e); None }
+        };
+        bluetooth_parts = Some((bluez, pairing, pairing_rx, transfers));
+    }
+
+    let conn = builder.build().await?;
 
     
     tokio::spawn(audio::monitor(conn.clone(), audio_cache));
     tokio::spawn(brightness::monitor(conn.clone(), bright_cache));
-    tokio::spawn(bluetooth::monitor(conn.clone(), bt_cache));
+    if let (Some(sys_conn), Some((bluez, pairing, pairing_rx, transfers))) = (&sys_conn, bluetooth_parts) {
+        tokio::spawn(bluetooth::monitor(conn.clone(), bluez.clone()));
+        tokio::spawn(bluetooth::agent::run(sys_conn.clone(), bluetooth::agent::BluetoothAgent::new(bluez.clone(), pairing)));
+        tokio::spawn(bluetooth::agent::monitor(conn.clone(), pairing_rx));
+        if let Some((obex, transfer_requests, transfer_rx)) = transfers {
+            tokio::spawn(bluetooth::transfer::run(obex.connection().clone(), bluetooth::transfer::ObexAgent::new(obex.clone(), bluez, transfer_requests)));
+            tokio::spawn(bluetooth::transfer::monitor(conn.clone(), obex));
+            tokio::spawn(bluetooth::transfer::monitor_requests(conn.clone(), transfer_rx));
+        }
+    }
+    tokio::spawn(rfkill::monitor(conn.clone(), rfkill_switches));
     tokio::spawn(media::monitor(conn.clone()));
     tokio::spawn(system::monitor(conn.clone()));
-    tokio::spawn(network::monitor(conn.clone()));
+    if let (Some(sys_conn), Some((backend, modems, traffic, prompter, secrets_rx))) = (&sys_conn, network_parts) {
+        match &backend {
+            network::backend::Backend::Nm(nm) => {
+                tokio::spawn(network::monitor(conn.clone(), backend.clone(), config.network.connectivity_check_uri.clone()));
+                tokio::spawn(network::monitor_access_points(conn.clone(), nm.clone()));
+                tokio::spawn(network::monitor_active(conn.clone(), nm.clone()));
+                tokio::spawn(network::monitor_devices(conn.clone(), nm.clone()));
+                tokio::spawn(network::agent::run(sys_conn.clone(), network::agent::SecretAgent::new(prompter)));
+            }
+            network::backend::Backend::Iwd(iwd) => {
+                tokio::spawn(network::backend::monitor(conn.clone(), backend.clone()));
+                tokio::spawn(network::iwd::run(sys_conn.clone(), iwd.agent(prompter)));
+            }
+            network::backend::Backend::Networkd(_) => {
+                tokio::spawn(network::backend::monitor(conn.clone(), backend.clone()));
+            }
+        }
+        tokio::spawn(network::traffic::monitor(conn.clone(), backend, traffic, config.network.metered_warning_mb.saturating_mul(1024 * 1024)));
+        tokio::spawn(network::agent::monitor(conn.clone(), secrets_rx));
+        tokio::spawn(network::modem::monitor(conn.clone(), modems));
+    }
     
     
     tokio::spawn(tray::monitor_tray(conn.clone(), tray_rx));
@@ -0,0 +1,181 @@
+use zbus::{interface, Connection, zvariant::{ObjectPath, OwnedObjectPath}};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, atomic::{AtomicU32, Ordering}};
+use tokio::sync::{mpsc, oneshot};
+use futures::StreamExt;
+use syd_core::{PairingKind, PairingRequest};
+use super::{bluez::{self, Bluez}, BluetoothService};
+
+const AGENT_PATH: &str = "/org/syd/BluetoothAgent";
+/// The shell can show codes and take typed ones, so BlueZ may use any pairing method.
+const CAPABILITY: &str = "KeyboardDisplay";
+
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "org.bluez.Error")]
+pub enum AgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    Rejected(String),
+    Canceled(String),
+}
+
+pub enum AgentEvent {
+    Requested(PairingRequest),
+    Cancelled(u32),
+}
+
+struct Pending {
+    device: OwnedObjectPath,
+    /// `None` for codes that are only shown.
+    reply: Option<oneshot::Sender<Option<String>>>,
+}
+
+/// Pairing questions waiting for the shell, shared by the agent and `BluetoothService`.
+#[derive(Clone)]
+pub struct Pairing {
+    pending: Arc<Mutex<HashMap<u32, Pending>>>,
+    next_id: Arc<AtomicU32>,
+    tx: mpsc::UnboundedSender<AgentEvent>,
+}
+
+impl Pairing {
+    pub fn new(tx: mpsc::UnboundedSender<AgentEvent>) -> Self {
+        Self { pending: Arc::default(), next_id: Arc::new(AtomicU32::new(1)), tx }
+    }
+
+    /// Sends `request` under a fresh id and waits for the answer; `None` means rejected.
+    async fn ask(&self, mut request: PairingRequest, device: OwnedObjectPath) -> Option<String> {
+        request.id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request.id, Pending { device, reply: Some(reply) });
+        let _ = self.tx.send(AgentEvent::Requested(request));
+        rx.await.ok().flatten()
+    }
+
+    /// Sends a code to show. BlueZ repeats `DisplayPasskey` as digits are typed, so a
+    /// device that is already showing one keeps its id.
+    fn show(&self, mut request: PairingRequest, device: OwnedObjectPath) {
+        let mut pending = self.pending.lock().unwrap();
+        let shown = pending.iter().find(|(_, p)| p.device == device && p.reply.is_none()).map(|(id, _)| *id);
+        request.id = shown.unwrap_or_else(|| self.next_id.fetch_add(1, Ordering::Relaxed));
+        pending.insert(request.id, Pending { device, reply: None });
+        let _ = self.tx.send(AgentEvent::Requested(request));
+    }
+
+    /// Answers request `id`. For a code being shown, returns its device if the user
+    /// declined, so pairing can be cancelled. `Err` if the request is gone.
+    pub fn respond(&self, id: u32, accept: bool, value: String) -> Result<Option<OwnedObjectPath>, ()> {
+        let p = self.pending.lock().unwrap().remove(&id).ok_or(())?;
+        match p.reply {
+            Some(reply) => { let _ = reply.send(accept.then_some(value)); Ok(None) }
+            None => Ok((!accept).then_some(p.device)),
+        }
+    }
+
+    /// Drops the requests for `device`, or every request when it is `None`, and tells the
+    /// shell to close their prompts.
+    pub fn withdraw(&self, device: Option<&OwnedObjectPath>) {
+        let mut pending = self.pending.lock().unwrap();
+        let ids: Vec<u32> = pending.iter()
+            .filter(|(_, p)| device.is_none_or(|d| p.device == *d))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            pending.remove(&id);
+            let _ = self.tx.send(AgentEvent::Cancelled(id));
+        }
+    }
+}
+
+pub struct BluetoothAgent { bluez: Bluez, pairing: Pairing }
+
+impl BluetoothAgent {
+    pub fn new(bluez: Bluez, pairing: Pairing) -> Self { Self { bluez, pairing } }
+
+    async fn request(&self, kind: PairingKind, device: &OwnedObjectPath) -> PairingRequest {
+        let dev = self.bluez.device(device).await.unwrap_or_default();
+        PairingRequest { kind, mac: dev.mac, alias: dev.alias, ..Default::default() }
+    }
+
+    async fn ask(&self, request: PairingRequest, device: OwnedObjectPath) -> Result<String, AgentError> {
+        self.pairing.ask(request, device).await.ok_or_else(|| AgentError::Rejected("Declined by the user".into()))
+    }
+}
+
+#[interface(name = "org.bluez.Agent1")]
+impl BluetoothAgent {
+    /// BlueZ has unregistered the agent, e.g. because it is shutting down.
+    async fn release(&self) { self.pairing.withdraw(None); }
+
+    async fn request_pin_code(&self, device: OwnedObjectPath) -> Result<String, AgentError> {
+        let req = self.request(PairingKind::PinCode, &device).await;
+        self.ask(req, device).await
+    }
+
+    async fn display_pin_code(&self, device: OwnedObjectPath, pincode: String) {
+        let req = PairingRequest { passkey: pincode, ..self.request(PairingKind::DisplayPinCode, &device).await };
+        self.pairing.show(req, device);
+    }
+
+    async fn request_passkey(&self, device: OwnedObjectPath) -> Result<u32, AgentError> {
+        let req = self.request(PairingKind::Passkey, &device).await;
+        let passkey = self.ask(req, device).await?;
+        passkey.trim().parse().map_err(|_| AgentError::Rejected(format!("{} is not a passkey", passkey)))
+    }
+
+    async fn display_passkey(&self, device: OwnedObjectPath, passkey: u32, entered: u16) {
+        let req = PairingRequest { passkey: format!("{:06}", passkey), entered, ..self.request(PairingKind::DisplayPasskey, &device).await };
+        self.pairing.show(req, device);
+    }
+
+    async fn request_confirmation(&self, device: OwnedObjectPath, passkey: u32) -> Result<(), AgentError> {
+        let req = PairingRequest { passkey: format!("{:06}", passkey), ..self.request(PairingKind::Confirmation, &device).await };
+        self.ask(req, device).await.map(|_| ())
+    }
+
+    async fn request_authorization(&self, device: OwnedObjectPath) -> Result<(), AgentError> {
+        let req = self.request(PairingKind::Authorization, &device).await;
+        self.ask(req, device).await.map(|_| ())
+    }
+
+    async fn authorize_service(&self, device: OwnedObjectPath, uuid: String) -> Result<(), AgentError> {
+        let req = PairingRequest { service: uuid, ..self.request(PairingKind::AuthorizeService, &device).await };
+        self.ask(req, device).await.map(|_| ())
+    }
+
+    /// BlueZ gave up on the request, e.g. because the device timed out.
+    async fn cancel(&self) { self.pairing.withdraw(None); }
+}
+
+async fn register(sys: &Connection) {
+    let path = ObjectPath::from_static_str_unchecked(AGENT_PATH);
+    let Ok(m) = bluez::AgentManagerProxy::new(sys).await else { return };
+    if let Err(e) = m.register_agent(&path, CAPABILITY).await {
+        eprintln!("Bluetooth agent registration failed: {}", e);
+        return;
+    }
+    // Without this BlueZ only asks us about pairings we start ourselves.
+    let _ = m.request_default_agent(&path).await;
+}
+
+pub async fn run(sys: Connection, agent: BluetoothAgent) {
+    if sys.object_server().at(AGENT_PATH, agent).await.is_err() { return; }
+    let Ok(dbus) = zbus::fdo::DBusProxy::new(&sys).await else { return };
+    let Ok(mut owners) = dbus.receive_name_owner_changed_with_args(&[(0, bluez::SERVICE)]).await else { return };
+
+    register(&sys).await;
+    // BlueZ forgets its agents when it restarts.
+    while let Some(sig) = owners.next().await {
+        if sig.args().map(|a| a.new_owner().is_some()).unwrap_or(false) { register(&sys).await; }
+    }
+}
+
+pub async fn monitor(conn: Connection, mut rx: mpsc::UnboundedReceiver<AgentEvent>) {
+    let iface = conn.object_server().interface::<_, BluetoothService>("/org/syd/Bluetooth").await.unwrap();
+    while let Some(ev) = rx.recv().await {
+        let _ = match ev {
+            AgentEvent::Requested(req) => BluetoothService::pairing_requested(&*iface.get().await, iface.signal_context(), req).await,
+            AgentEvent::Cancelled(id) => BluetoothService::pairing_cancelled(&*iface.get().await, iface.signal_context(), id).await,
+        };
+    }
+}
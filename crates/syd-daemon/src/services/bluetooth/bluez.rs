@@ -0,0 +1,471 @@
+use zbus::{proxy, Connection, Result, zvariant::{ObjectPath, OwnedObjectPath, OwnedValue}};
+use zbus::fdo::ObjectManagerProxy;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tokio::time::{timeout, Duration};
+use serde::{Deserialize, Serialize};
+use syd_core::{BluetoothError, BtAdapter, BtDevice, DisconnectReason};
+use crate::config;
+use crate::services::dbus::{at, prop};
+use super::upower;
+
+pub const SERVICE: &str = "org.bluez";
+pub const ADAPTER: &str = "org.bluez.Adapter1";
+pub const DEVICE: &str = "org.bluez.Device1";
+pub const BATTERY: &str = "org.bluez.Battery1";
+/// BlueZ's own page timeout is shorter, but profiles can take a while to come up after it.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[proxy(interface = "org.bluez.Adapter1", default_service = "org.bluez")]
+pub trait Adapter {
+    fn start_discovery(&self) -> Result<()>;
+    fn stop_discovery(&self) -> Result<()>;
+    fn remove_device(&self, device: &ObjectPath<'_>) -> Result<()>;
+    #[zbus(property)] fn set_powered(&self, powered: bool) -> Result<()>;
+    #[zbus(property)] fn set_discoverable(&self, discoverable: bool) -> Result<()>;
+    #[zbus(property)] fn set_discoverable_timeout(&self, seconds: u32) -> Result<()>;
+    #[zbus(property)] fn set_pairable(&self, pairable: bool) -> Result<()>;
+    #[zbus(property)] fn set_alias(&self, alias: &str) -> Result<()>;
+}
+
+#[proxy(interface = "org.bluez.Device1", default_service = "org.bluez")]
+pub trait Device {
+    fn connect(&self) -> Result<()>;
+    fn disconnect(&self) -> Result<()>;
+    fn pair(&self) -> Result<()>;
+    fn cancel_pairing(&self) -> Result<()>;
+    #[zbus(property)] fn set_trusted(&self, trusted: bool) -> Result<()>;
+}
+
+#[proxy(interface = "org.bluez.AgentManager1", default_service = "org.bluez", default_path = "/org/bluez")]
+pub trait AgentManager {
+    fn register_agent(&self, agent: &ObjectPath<'_>, capability: &str) -> Result<()>;
+    fn request_default_agent(&self, agent: &ObjectPath<'_>) -> Result<()>;
+}
+
+/// Every BlueZ object with the properties of each interface it implements.
+pub type Objects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+/// Objects implementing `iface`, with that interface's properties.
+pub fn having<'a>(objs: &'a Objects, iface: &'a str) -> impl Iterator<Item = (&'a OwnedObjectPath, &'a HashMap<String, OwnedValue>)> {
+    objs.iter().filter_map(move |(path, ifaces)| Some((path, ifaces.get(iface)?)))
+}
+
+/// Talks to BlueZ on whatever bus it is given, so a stand-in object tree works as well.
+#[derive(Clone)]
+pub struct Bluez {
+    conn: Connection,
+    /// Address of the adapter picked with `set_default`; the adapters' names follow the
+    /// order they were plugged in, so they do not identify a dongle across docking.
+    default: Arc<Mutex<Option<String>>>,
+    switched: Arc<Notify>,
+}
+
+/// The default adapter, as persisted between runs.
+#[derive(Serialize, Deserialize, Default)]
+struct Saved {
+    default_adapter: Option<String>,
+}
+
+fn state_file() -> PathBuf { config::state_dir().join("bluetooth.json") }
+
+impl Bluez {
+    pub fn new(conn: Connection) -> Self {
+        let saved: Saved = std::fs::read_to_string(state_file()).ok().and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default();
+        Self { conn, default: Arc::new(Mutex::new(saved.default_adapter)), switched: Arc::default() }
+    }
+
+    pub fn connection(&self) -> &Connection { &self.conn }
+
+    /// The whole object tree in one call. BlueZ keeps it small: adapters and the devices
+    /// they have seen.
+    pub async fn objects(&self) -> Result<Objects> {
+        let om = ObjectManagerProxy::builder(&self.conn).destination(SERVICE)?.path("/")?.build().await?;
+        Ok(om.get_managed_objects().await?.into_iter()
+            .map(|(path, ifaces)| (path, ifaces.into_iter().map(|(name, p)| (name.to_string(), p)).collect()))
+            .collect())
+    }
+
+    /// The chosen adapter while it is plugged in, otherwise the first one, which is what
+    /// `bluetoothctl` uses unless told otherwise.
+    fn adapter<'a>(&self, objs: &'a Objects) -> Option<(&'a OwnedObjectPath, &'a HashMap<String, OwnedValue>)> {
+        let default = self.default.lock().unwrap().clone();
+        having(objs, ADAPTER)
+            .find(|(_, p)| default.as_ref().is_some_and(|d| prop::<String>(p, "Address").is_some_and(|a| a.eq_ignore_ascii_case(d))))
+            .or_else(|| having(objs, ADAPTER).min_by(|a, b| a.0.cmp(b.0)))
+    }
+
+    /// The adapter called `name`, by kernel name or address; empty means the default one.
+    fn adapter_named<'a>(&self, objs: &'a Objects, name: &str) -> std::result::Result<&'a OwnedObjectPath, BluetoothError> {
+        let found = if name.is_empty() {
+            self.adapter(objs)
+        } else {
+            having(objs, ADAPTER).find(|(path, p)| {
+                adapter_name(path) == name || prop::<String>(p, "Address").is_some_and(|a| a.eq_ignore_ascii_case(name))
+            })
+        };
+        found.map(|(path, _)| path).ok_or_else(|| BluetoothError::NotFound(format!("No Bluetooth adapter {}", name)))
+    }
+
+    /// Every adapter, in the kernel's order.
+    pub async fn adapters(&self) -> Result<Vec<BtAdapter>> {
+        let objs = self.objects().await?;
+        let default = self.adapter(&objs).map(|(path, _)| path.clone());
+        let mut res: Vec<_> = having(&objs, ADAPTER)
+            .map(|(path, p)| BtAdapter { default: default.as_ref() == Some(path), ..adapter_from(path, p) })
+            .collect();
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(res)
+    }
+
+    /// Makes `name` the adapter everything else refers to, and remembers it.
+    pub async fn set_default(&self, name: &str) -> std::result::Result<(), BluetoothError> {
+        let objs = self.objects().await?;
+        let path = self.adapter_named(&objs, name)?;
+        let address: Option<String> = objs[path].get(ADAPTER).and_then(|p| prop(p, "Address"));
+        *self.default.lock().unwrap() = address.clone();
+        let path = state_file();
+        if let Some(dir) = path.parent() { let _ = std::fs::create_dir_all(dir); }
+        if let Ok(json) = serde_json::to_string(&Saved { default_adapter: address }) {
+            if let Err(e) = std::fs::write(&path, json) { eprintln!("Could not save {}: {}", path.display(), e); }
+        }
+        self.switched.notify_one();
+        Ok(())
+    }
+
+    /// Resolves once the default adapter has been changed, as BlueZ itself sends nothing.
+    pub async fn switched(&self) { self.switched.notified().await }
+
+    pub async fn powered(&self) -> bool {
+        let Ok(objs) = self.objects().await else { return false };
+        self.adapter(&objs).and_then(|(_, p)| prop(p, "Powered")).unwrap_or(false)
+    }
+
+    pub async fn set_powered(&self, on: bool) -> Result<()> {
+        self.adapter_proxy().await?.set_powered(on).await
+    }
+
+    /// Devices the default adapter knows about, paired or merely seen, by object path.
+    pub async fn device_map(&self) -> Result<HashMap<OwnedObjectPath, BtDevice>> {
+        let objs = self.objects().await?;
+        let Some((adapter, _)) = self.adapter(&objs) else { return Ok(HashMap::new()) };
+        let mut res: HashMap<_, _> = having(&objs, DEVICE)
+            .filter(|(_, p)| prop::<OwnedObjectPath>(p, "Adapter").as_ref() == Some(adapter))
+            .map(|(path, p)| {
+                let mut dev = device_from(p);
+                if let Some(pct) = objs[path].get(BATTERY).and_then(|b| prop::<u8>(b, "Percentage")) { dev.battery = pct.into(); }
+                (path.clone(), dev)
+            })
+            .collect();
+        if res.values().any(|d| d.connected && d.battery < 0) {
+            let levels = upower::batteries(&self.conn).await;
+            for dev in res.values_mut().filter(|d| d.connected && d.battery < 0) {
+                if let Some(pct) = levels.get(&dev.mac.to_ascii_uppercase()) { dev.battery = *pct; }
+            }
+        }
+        Ok(res)
+    }
+
+    pub async fn devices(&self) -> Result<Vec<BtDevice>> {
+        let mut res: Vec<BtDevice> = self.device_map().await?.into_values().collect();
+        res.sort_by(|a, b| a.alias.cmp(&b.alias));
+        Ok(res)
+    }
+
+    async fn adapter_proxy(&self) -> Result<AdapterProxy<'static>> {
+        let objs = self.objects().await?;
+        let Some((path, _)) = self.adapter(&objs) else { return Err(zbus::Error::Failure("No Bluetooth adapter".into())) };
+        zbus::proxy::Builder::new(&self.conn).path(path.clone())?.cache_properties(zbus::proxy::CacheProperties::No).build().await
+    }
+
+    /// The adapter called `name`, as for `adapter_named`.
+    pub async fn adapter_proxy_named(&self, name: &str) -> std::result::Result<AdapterProxy<'static>, BluetoothError> {
+        let objs = self.objects().await?;
+        let path = self.adapter_named(&objs, name)?;
+        Ok(zbus::proxy::Builder::new(&self.conn).path(path.clone())?.cache_properties(zbus::proxy::CacheProperties::No).build().await?)
+    }
+
+    /// Discovery belongs to this connection; BlueZ stops it if the daemon goes away.
+    pub async fn start_discovery(&self) -> Result<()> {
+        match self.adapter_proxy().await?.start_discovery().await {
+            Err(zbus::Error::MethodError(name, _, _)) if name.as_str() == "org.bluez.Error.InProgress" => Ok(()),
+            res => res,
+        }
+    }
+
+    pub async fn stop_discovery(&self) -> Result<()> {
+        self.adapter_proxy().await?.stop_discovery().await
+    }
+
+    pub async fn device_path(&self, mac: &str) -> std::result::Result<OwnedObjectPath, BluetoothError> {
+        having(&self.objects().await?, DEVICE)
+            .find(|(_, p)| prop::<String>(p, "Address").is_some_and(|a| a.eq_ignore_ascii_case(mac)))
+            .map(|(path, _)| path.clone())
+            .ok_or_else(|| BluetoothError::NotFound(format!("No device {}", mac)))
+    }
+
+    /// The device at `path`, which the agent is handed instead of an address.
+    pub async fn device(&self, path: &OwnedObjectPath) -> Result<BtDevice> {
+        let objs = self.objects().await?;
+        objs.get(path).and_then(|ifaces| ifaces.get(DEVICE)).map(device_from)
+            .ok_or_else(|| zbus::Error::Failure(format!("No device at {}", path)))
+    }
+
+    /// Returns once pairing has finished, which may take as long as the user needs to
+    /// answer the agent.
+    pub async fn pair(&self, path: &OwnedObjectPath) -> std::result::Result<(), BluetoothError> {
+        match at::<DeviceProxy>(&self.conn, path).await?.pair().await {
+            Err(zbus::Error::MethodError(name, _, _)) if name.as_str() == "org.bluez.Error.AlreadyExists" => Ok(()),
+            res => res.map_err(bluez_error),
+        }
+    }
+
+    pub async fn cancel_pairing(&self, path: &OwnedObjectPath) -> Result<()> {
+        at::<DeviceProxy>(&self.conn, path).await?.cancel_pairing().await
+    }
+
+    pub async fn set_trusted(&self, mac: &str, trusted: bool) -> std::result::Result<(), BluetoothError> {
+        let path = self.device_path(mac).await?;
+        Ok(at::<DeviceProxy>(&self.conn, &path).await?.set_trusted(trusted).await?)
+    }
+
+    /// Unpairs the device and forgets it, from the adapter that knows it.
+    pub async fn remove(&self, mac: &str) -> std::result::Result<(), BluetoothError> {
+        let objs = self.objects().await?;
+        let Some((path, props)) = having(&objs, DEVICE)
+            .find(|(_, p)| prop::<String>(p, "Address").is_some_and(|a| a.eq_ignore_ascii_case(mac))) else {
+            return Err(BluetoothError::NotFound(format!("No device {}", mac)));
+        };
+        let adapter: OwnedObjectPath = prop(props, "Adapter").ok_or_else(|| BluetoothError::NotFound(format!("No adapter for {}", mac)))?;
+        Ok(at::<AdapterProxy>(&self.conn, &adapter).await?.remove_device(&path.as_ref()).await?)
+    }
+
+    /// Connects every profile the device offers, giving up after `CONNECT_TIMEOUT`.
+    pub async fn connect(&self, mac: &str, on: bool) -> std::result::Result<(), BluetoothError> {
+        let path = self.device_path(mac).await?;
+        let dev: DeviceProxy = at(&self.conn, &path).await?;
+        if !on {
+            return match dev.disconnect().await {
+                Err(zbus::Error::MethodError(name, _, _)) if name.as_str() == "org.bluez.Error.NotConnected" => Ok(()),
+                res => res.map_err(bluez_error),
+            };
+        }
+        match timeout(CONNECT_TIMEOUT, dev.connect()).await {
+            Err(_) => Err(BluetoothError::Timeout(format!("{} did not answer", mac))),
+            Ok(Err(zbus::Error::MethodError(name, _, _))) if name.as_str() == "org.bluez.Error.AlreadyConnected" => Ok(()),
+            Ok(res) => res.map_err(bluez_error),
+        }
+    }
+}
+
+/// Sorts BlueZ's errors, whose names are coarse, into what the user can act on. The
+/// details are only in the message, e.g. `br-connection-page-timeout`.
+pub fn bluez_error(e: zbus::Error) -> BluetoothError {
+    let zbus::Error::MethodError(name, msg, _) = &e else { return e.into() };
+    let msg = msg.clone().unwrap_or_default();
+    match name.as_str() {
+        "org.bluez.Error.NotReady" => BluetoothError::NotAvailable("Bluetooth is off".into()),
+        "org.bluez.Error.DoesNotExist" => BluetoothError::NotFound(msg),
+        "org.bluez.Error.AuthenticationFailed" | "org.bluez.Error.AuthenticationRejected"
+        | "org.bluez.Error.AuthenticationCanceled" | "org.bluez.Error.AuthenticationTimeout" => BluetoothError::AuthFailed(msg),
+        "org.bluez.Error.ConnectionAttemptFailed" => BluetoothError::NotAvailable(msg),
+        "org.bluez.Error.NotAvailable" | "org.bluez.Error.NotSupported" => BluetoothError::ProfileUnavailable(msg),
+        _ if msg.contains("page-timeout") || msg.contains("timed out") || msg.contains("Timeout") => BluetoothError::Timeout(msg),
+        _ if msg.contains("profile-unavailable") || msg.contains("Protocol not available") => BluetoothError::ProfileUnavailable(msg),
+        _ if msg.contains("Host is down") || msg.contains("abort-by-local") => BluetoothError::NotAvailable(msg),
+        _ if msg.contains("auth") => BluetoothError::AuthFailed(msg),
+        _ => BluetoothError::Failed(msg),
+    }
+}
+
+/// The reason in a `Device1.Disconnected` signal, sent by BlueZ 5.72 and later.
+pub fn disconnect_reason(name: &str) -> DisconnectReason {
+    match name {
+        "org.bluez.Reason.Local" => DisconnectReason::Local,
+        "org.bluez.Reason.Remote" => DisconnectReason::Remote,
+        "org.bluez.Reason.Timeout" => DisconnectReason::Timeout,
+        "org.bluez.Reason.Authentication" => DisconnectReason::Authentication,
+        "org.bluez.Reason.Suspend" => DisconnectReason::Suspend,
+        _ => DisconnectReason::Unknown,
+    }
+}
+
+/// `hci0` for `/org/bluez/hci0`.
+fn adapter_name(path: &OwnedObjectPath) -> &str {
+    path.as_str().rsplit('/').next().unwrap_or_default()
+}
+
+fn adapter_from(path: &OwnedObjectPath, p: &HashMap<String, OwnedValue>) -> BtAdapter {
+    BtAdapter {
+        name: adapter_name(path).to_string(),
+        address: prop(p, "Address").unwrap_or_default(),
+        alias: prop(p, "Alias").unwrap_or_default(),
+        powered: prop(p, "Powered").unwrap_or(false),
+        discoverable: prop(p, "Discoverable").unwrap_or(false),
+        pairable: prop(p, "Pairable").unwrap_or(false),
+        discoverable_timeout: prop(p, "DiscoverableTimeout").unwrap_or(0),
+        default: false,
+    }
+}
+
+pub fn device_from(p: &HashMap<String, OwnedValue>) -> BtDevice {
+    let mac: String = prop(p, "Address").unwrap_or_default();
+    let name: String = prop(p, "Name").unwrap_or_default();
+    BtDevice {
+        // BlueZ fills Alias from the name, or the address when there is none.
+        alias: prop(p, "Alias").unwrap_or_else(|| if name.is_empty() { mac.clone() } else { name.clone() }),
+        connected: prop(p, "Connected").unwrap_or(false),
+        paired: prop(p, "Paired").unwrap_or(false),
+        trusted: prop(p, "Trusted").unwrap_or(false),
+        rssi: prop(p, "RSSI").unwrap_or(0),
+        battery: -1,
+        icon: prop(p, "Icon").unwrap_or_else(|| icon_from_class(prop(p, "Class").unwrap_or(0)).to_string()),
+        name,
+        mac,
+    }
+}
+
+/// For devices that send a class of device but no appearance, which BlueZ leaves without
+/// an icon.
+fn icon_from_class(class: u32) -> &'static str {
+    // Major device class, then the bits of the peripheral minor class that say keyboard or mouse.
+    match ((class >> 8) & 0x1f, (class >> 6) & 0x3) {
+        (1, _) => "computer",
+        (2, _) => "phone",
+        (3, _) => "network-wireless",
+        (4, _) => "audio-card",
+        (5, 1) => "input-keyboard",
+        (5, 2) => "input-mouse",
+        (5, _) => "input-gaming",
+        (6, _) => "camera-photo",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::Value;
+
+    fn props(entries: Vec<(&str, Value<'_>)>) -> HashMap<String, OwnedValue> {
+        entries.into_iter().map(|(k, v)| (k.to_string(), OwnedValue::try_from(v).unwrap())).collect()
+    }
+
+    fn path(p: &str) -> OwnedObjectPath { OwnedObjectPath::try_from(p).unwrap() }
+
+    /// Two adapters; a paired headset with a battery on the first, a phone on the second.
+    fn tree() -> Objects {
+        let adapter = |name: &str, address: &str| (path(&format!("/org/bluez/{}", name)), HashMap::from([(ADAPTER.to_string(), props(vec![
+            ("Address", Value::from(address)),
+            ("Alias", Value::from(format!("laptop-{}", name))),
+            ("Powered", Value::from(true)),
+            ("Discoverable", Value::from(false)),
+            ("Pairable", Value::from(true)),
+            ("DiscoverableTimeout", Value::from(180u32)),
+        ]))]));
+        let headset = (path("/org/bluez/hci0/dev_00_1A_7D_DA_71_13"), HashMap::from([
+            (DEVICE.to_string(), props(vec![
+                ("Address", Value::from("00:1A:7D:DA:71:13")),
+                ("Name", Value::from("WH-1000XM4")),
+                ("Alias", Value::from("Headphones")),
+                ("Adapter", Value::from(ObjectPath::from_static_str_unchecked("/org/bluez/hci0"))),
+                ("Connected", Value::from(true)),
+                ("Paired", Value::from(true)),
+                ("Trusted", Value::from(true)),
+                ("Icon", Value::from("audio-headset")),
+            ])),
+            (BATTERY.to_string(), props(vec![("Percentage", Value::from(80u8))])),
+        ]));
+        let phone = (path("/org/bluez/hci1/dev_3C_28_6D_11_22_33"), HashMap::from([(DEVICE.to_string(), props(vec![
+            ("Address", Value::from("3C:28:6D:11:22:33")),
+            ("Adapter", Value::from(ObjectPath::from_static_str_unchecked("/org/bluez/hci1"))),
+            ("RSSI", Value::from(-60i16)),
+            ("Class", Value::from(0x5a020cu32)),
+        ]))]));
+        HashMap::from([adapter("hci0", "00:00:00:00:00:01"), adapter("hci1", "00:00:00:00:00:02"), headset, phone])
+    }
+
+    struct Tree;
+
+    #[zbus::interface(name = "org.freedesktop.DBus.ObjectManager")]
+    impl Tree {
+        fn get_managed_objects(&self) -> Objects { tree() }
+    }
+
+    /// A `Bluez` talking to `Tree` over a private connection instead of the system bus, and
+    /// the serving end, which has to be kept.
+    async fn stand_in() -> (Bluez, Connection) {
+        let (ours, theirs) = tokio::net::UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let server = zbus::connection::Builder::unix_stream(theirs).server(guid).unwrap().p2p()
+            .serve_at("/", Tree).unwrap().build();
+        let client = zbus::connection::Builder::unix_stream(ours).p2p().build();
+        let (server, client) = tokio::join!(server, client);
+        (Bluez { conn: client.unwrap(), default: Arc::default(), switched: Arc::default() }, server.unwrap())
+    }
+
+    #[tokio::test]
+    async fn reads_the_object_tree() {
+        let (bluez, _server) = stand_in().await;
+
+        let adapters = bluez.adapters().await.unwrap();
+        assert_eq!(adapters.iter().map(|a| (a.name.as_str(), a.default)).collect::<Vec<_>>(), [("hci0", true), ("hci1", false)]);
+        assert_eq!((adapters[0].alias.as_str(), adapters[0].discoverable_timeout), ("laptop-hci0", 180));
+
+        // Only the default adapter's devices.
+        let devices = bluez.device_map().await.unwrap();
+        assert_eq!(devices.len(), 1);
+        let headset = &devices[&path("/org/bluez/hci0/dev_00_1A_7D_DA_71_13")];
+        assert_eq!((headset.alias.as_str(), headset.battery, headset.connected), ("Headphones", 80, true));
+        assert_eq!(bluez.devices().await.unwrap()[0].mac, "00:1A:7D:DA:71:13");
+
+        *bluez.default.lock().unwrap() = Some("00:00:00:00:00:02".into());
+        let phone = bluez.devices().await.unwrap().remove(0);
+        assert_eq!((phone.alias.as_str(), phone.rssi, phone.icon.as_str()), ("3C:28:6D:11:22:33", -60, "phone"));
+    }
+
+    #[test]
+    fn alias_falls_back_to_name_then_address() {
+        let named = device_from(&props(vec![("Address", Value::from("AA:BB:CC:DD:EE:FF")), ("Name", Value::from("Mouse"))]));
+        assert_eq!((named.alias.as_str(), named.battery), ("Mouse", -1));
+        let bare = device_from(&props(vec![("Address", Value::from("AA:BB:CC:DD:EE:FF"))]));
+        assert_eq!((bare.alias.as_str(), bare.icon.as_str()), ("AA:BB:CC:DD:EE:FF", ""));
+    }
+
+    #[test]
+    fn icons_from_class() {
+        assert_eq!(icon_from_class(0x5a020c), "phone");
+        assert_eq!(icon_from_class(0x240404), "audio-card");
+        assert_eq!(icon_from_class(0x002540), "input-keyboard");
+        assert_eq!(icon_from_class(0x002580), "input-mouse");
+        assert_eq!(icon_from_class(0x002508), "input-gaming");
+        assert_eq!(icon_from_class(0), "");
+    }
+
+    fn method_error(name: &str, msg: &str) -> zbus::Error {
+        let reply = zbus::Message::method("/org/bluez/hci0/dev_00_1A_7D_DA_71_13", "Connect").unwrap().build(&()).unwrap();
+        zbus::Error::MethodError(zbus::names::OwnedErrorName::try_from(name.to_string()).unwrap(), Some(msg.into()), reply)
+    }
+
+    #[test]
+    fn sorts_bluez_errors() {
+        assert!(matches!(bluez_error(method_error("org.bluez.Error.NotReady", "Resource Not Ready")), BluetoothError::NotAvailable(_)));
+        assert!(matches!(bluez_error(method_error("org.bluez.Error.AuthenticationRejected", "")), BluetoothError::AuthFailed(_)));
+        assert!(matches!(bluez_error(method_error("org.bluez.Error.NotAvailable", "")), BluetoothError::ProfileUnavailable(_)));
+        assert!(matches!(bluez_error(method_error("org.bluez.Error.Failed", "br-connection-page-timeout")), BluetoothError::Timeout(_)));
+        assert!(matches!(bluez_error(method_error("org.bluez.Error.Failed", "br-connection-profile-unavailable")), BluetoothError::ProfileUnavailable(_)));
+        assert!(matches!(bluez_error(method_error("org.bluez.Error.Failed", "Host is down")), BluetoothError::NotAvailable(_)));
+        assert!(matches!(bluez_error(method_error("org.bluez.Error.Failed", "Input/output error")), BluetoothError::Failed(m) if m == "Input/output error"));
+        assert!(matches!(bluez_error(zbus::Error::Failure("gone".into())), BluetoothError::ZBus(_)));
+    }
+
+    #[test]
+    fn disconnect_reasons() {
+        assert_eq!(disconnect_reason("org.bluez.Reason.Remote"), DisconnectReason::Remote);
+        assert_eq!(disconnect_reason("org.bluez.Reason.Authentication"), DisconnectReason::Authentication);
+        assert_eq!(disconnect_reason("org.bluez.Reason.Unknown"), DisconnectReason::Unknown);
+        assert_eq!(disconnect_reason(""), DisconnectReason::Unknown);
+    }
+}
@@ -0,0 +1,214 @@
+pub mod bluez;
+pub mod agent;
+pub mod upower;
+pub mod obex;
+pub mod transfer;
+
+use zbus::{interface, object_server::SignalContext, Connection, MatchRule, MessageStream};
+use std::collections::HashMap;
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use tokio::time::{sleep, timeout_at, Duration, Instant};
+use futures::{stream, StreamExt};
+use syd_core::{BluetoothError, BtAdapter, BtDevice, DisconnectReason, PairingRequest};
+use bluez::Bluez;
+
+/// Discovery drains the battery and slows down other radios, so it never runs unattended.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct BluetoothService { bluez: Bluez, pairing: agent::Pairing, discovery: Arc<AtomicU64> }
+impl BluetoothService {
+    pub fn new(bluez: Bluez, pairing: agent::Pairing) -> Self { Self { bluez, pairing, discovery: Arc::new(AtomicU64::new(0)) } }
+}
+#[interface(name = "org.syd.Bluetooth")]
+impl BluetoothService {
+    async fn get_power(&self) -> bool { self.bluez.powered().await }
+    async fn set_power(&self, e: bool) {
+        if let Err(err) = self.bluez.set_powered(e).await { eprintln!("Could not switch Bluetooth {}: {}", if e { "on" } else { "off" }, err); }
+    }
+
+    async fn get_adapters(&self) -> Vec<BtAdapter> { self.bluez.adapters().await.unwrap_or_default() }
+
+    /// Switches the adapter the other methods use, by name or address. It is remembered by
+    /// address, so the choice holds when the dongle comes back as another `hciN`.
+    async fn set_default_adapter(&self, adapter: String) -> Result<(), BluetoothError> {
+        // Discovery on the old adapter would go on unattended.
+        self.discovery.fetch_add(1, Ordering::SeqCst);
+        let _ = self.bluez.stop_discovery().await;
+        self.bluez.set_default(&adapter).await
+    }
+
+    /// An empty `adapter` means the default one, here and in the methods below.
+    async fn set_adapter_power(&self, adapter: String, enabled: bool) -> Result<(), BluetoothError> {
+        self.bluez.adapter_proxy_named(&adapter).await?.set_powered(enabled).await.map_err(bluez::bluez_error)
+    }
+
+    /// Lets devices that are searching find this machine, until `DiscoverableTimeout` runs out.
+    async fn set_discoverable(&self, adapter: String, discoverable: bool) -> Result<(), BluetoothError> {
+        self.bluez.adapter_proxy_named(&adapter).await?.set_discoverable(discoverable).await.map_err(bluez::bluez_error)
+    }
+
+    async fn set_discoverable_timeout(&self, adapter: String, seconds: u32) -> Result<(), BluetoothError> {
+        self.bluez.adapter_proxy_named(&adapter).await?.set_discoverable_timeout(seconds).await.map_err(bluez::bluez_error)
+    }
+
+    /// Whether devices may pair with this machine on their own initiative.
+    async fn set_pairable(&self, adapter: String, pairable: bool) -> Result<(), BluetoothError> {
+        self.bluez.adapter_proxy_named(&adapter).await?.set_pairable(pairable).await.map_err(bluez::bluez_error)
+    }
+
+    /// The name other devices see.
+    async fn set_adapter_alias(&self, adapter: String, alias: String) -> Result<(), BluetoothError> {
+        self.bluez.adapter_proxy_named(&adapter).await?.set_alias(&alias).await.map_err(bluez::bluez_error)
+    }
+
+    async fn get_devices(&self) -> Vec<BtDevice> { self.bluez.devices().await.unwrap_or_default() }
+
+    /// Returns once every profile is up, or down. `DeviceConnected` and `DeviceDisconnected`
+    /// follow for connections made or lost any other way too.
+    async fn connect_device(&self, mac: String, connect: bool) -> Result<(), BluetoothError> {
+        self.bluez.connect(&mac, connect).await
+    }
+
+    /// Looks for nearby devices for 30 seconds; calling again while it runs restarts the
+    /// countdown. Found devices arrive through `DeviceAdded`.
+    async fn start_discovery(&self) -> zbus::fdo::Result<()> {
+        self.bluez.start_discovery().await?;
+        let generation = self.discovery.fetch_add(1, Ordering::SeqCst) + 1;
+        let (bluez, discovery) = (self.bluez.clone(), self.discovery.clone());
+        tokio::spawn(async move {
+            sleep(DISCOVERY_TIMEOUT).await;
+            if discovery.load(Ordering::SeqCst) == generation { let _ = bluez.stop_discovery().await; }
+        });
+        Ok(())
+    }
+
+    async fn stop_discovery(&self) -> zbus::fdo::Result<()> {
+        self.discovery.fetch_add(1, Ordering::SeqCst);
+        Ok(self.bluez.stop_discovery().await?)
+    }
+
+    /// Returns once the device is paired. Codes to show or confirm arrive through
+    /// `PairingRequested` in the meantime.
+    async fn pair(&self, mac: String) -> Result<(), BluetoothError> {
+        let path = self.bluez.device_path(&mac).await?;
+        let res = self.bluez.pair(&path).await;
+        // A passkey on display is no use once pairing is over.
+        self.pairing.withdraw(Some(&path));
+        res
+    }
+
+    /// Trusted devices connect without an `AuthorizeService` request.
+    async fn trust(&self, mac: String, trusted: bool) -> Result<(), BluetoothError> { self.bluez.set_trusted(&mac, trusted).await }
+
+    /// Unpairs and forgets the device.
+    async fn remove(&self, mac: String) -> Result<(), BluetoothError> { self.bluez.remove(&mac).await }
+
+    /// Answers a `PairingRequested`. `value` is the PIN or passkey where one is asked for.
+    /// Declining a code on display cancels the pairing.
+    async fn agent_reply(&self, id: u32, accept: bool, value: String) -> zbus::fdo::Result<()> {
+        match self.pairing.respond(id, accept, value) {
+            Ok(Some(device)) => Ok(self.bluez.cancel_pairing(&device).await?),
+            Ok(None) => Ok(()),
+            Err(()) => Err(zbus::fdo::Error::InvalidArgs(format!("No pairing request {}", id))),
+        }
+    }
+
+    /// The default adapter was switched on or off.
+    #[zbus(signal)] async fn power_changed(&self, ctxt: &SignalContext<'_>, enabled: bool) -> zbus::Result<()>;
+    /// Sent for new adapters too, and for both adapters when the default one changes.
+    #[zbus(signal)] async fn adapter_changed(&self, ctxt: &SignalContext<'_>, adapter: BtAdapter) -> zbus::Result<()>;
+    #[zbus(signal)] async fn adapter_removed(&self, ctxt: &SignalContext<'_>, name: String) -> zbus::Result<()>;
+    #[zbus(signal)] async fn device_added(&self, ctxt: &SignalContext<'_>, device: BtDevice) -> zbus::Result<()>;
+    #[zbus(signal)] async fn device_removed(&self, ctxt: &SignalContext<'_>, mac: String) -> zbus::Result<()>;
+    /// Sent when anything in `BtDevice` changes, RSSI and battery level included.
+    #[zbus(signal)] async fn device_changed(&self, ctxt: &SignalContext<'_>, device: BtDevice) -> zbus::Result<()>;
+    #[zbus(signal)] async fn device_connected(&self, ctxt: &SignalContext<'_>, mac: String) -> zbus::Result<()>;
+    #[zbus(signal)] async fn device_disconnected(&self, ctxt: &SignalContext<'_>, mac: String, reason: DisconnectReason) -> zbus::Result<()>;
+    #[zbus(signal)] async fn pairing_requested(&self, ctxt: &SignalContext<'_>, request: PairingRequest) -> zbus::Result<()>;
+    /// The request was answered elsewhere or BlueZ gave up on it; close its prompt.
+    #[zbus(signal)] async fn pairing_cancelled(&self, ctxt: &SignalContext<'_>, id: u32) -> zbus::Result<()>;
+}
+
+/// Follows BlueZ's object tree: adapters and devices appearing, going away and changing.
+pub async fn monitor(conn: Connection, bluez: Bluez) {
+    let iface = conn.object_server().interface::<_, BluetoothService>("/org/syd/Bluetooth").await.unwrap();
+    // InterfacesAdded, InterfacesRemoved and PropertiesChanged from BlueZ, and UPower's
+    // device changes for the batteries of HID peripherals.
+    let rules = [
+        MatchRule::builder().msg_type(zbus::message::Type::Signal).sender(bluez::SERVICE).map(|b| b.build()),
+        MatchRule::builder().msg_type(zbus::message::Type::Signal).sender(upower::SERVICE)
+            .and_then(|b| b.path_namespace(upower::DEVICES_PATH)).map(|b| b.build()),
+    ];
+    let mut events = Vec::new();
+    for rule in rules.into_iter().flatten() {
+        if let Ok(s) = MessageStream::for_match_rule(rule, bluez.connection(), None).await { events.push(s); }
+    }
+    let mut events = stream::select_all(events);
+
+    let mut last = bluez.powered().await;
+    let mut adapters: Vec<BtAdapter> = bluez.adapters().await.unwrap_or_default();
+    let mut known: HashMap<_, BtDevice> = bluez.device_map().await.unwrap_or_default();
+    loop {
+        let mut batch = Vec::new();
+        tokio::select! {
+            msg = events.next() => match msg { Some(msg) => batch.push(msg), None => break },
+            _ = bluez.switched() => {}
+        }
+        // Discovery reports RSSI for every device in range, one signal each, and keeps at it,
+        // so the batch closes 300 ms after its first message however busy the bus is.
+        let deadline = Instant::now() + Duration::from_millis(300);
+        while let Ok(Some(msg)) = timeout_at(deadline, events.next()).await { batch.push(msg); }
+        let reasons = disconnect_reasons(&batch);
+
+        let curr = bluez.powered().await;
+        if curr != last {
+            let _ = BluetoothService::power_changed(&*iface.get().await, iface.signal_context(), curr).await;
+            last = curr;
+        }
+
+        let curr = bluez.adapters().await.unwrap_or_default();
+        for gone in adapters.iter().filter(|a| !curr.iter().any(|c| c.name == a.name)) {
+            let _ = BluetoothService::adapter_removed(&*iface.get().await, iface.signal_context(), gone.name.clone()).await;
+        }
+        for a in curr.iter().filter(|a| !adapters.contains(a)) {
+            let _ = BluetoothService::adapter_changed(&*iface.get().await, iface.signal_context(), a.clone()).await;
+        }
+        adapters = curr;
+
+        let Ok(devices) = bluez.device_map().await else { continue };
+        for (path, dev) in &known {
+            if devices.contains_key(path) { continue; }
+            let _ = BluetoothService::device_removed(&*iface.get().await, iface.signal_context(), dev.mac.clone()).await;
+        }
+        for (path, dev) in &devices {
+            match known.get(path) {
+                None => { let _ = BluetoothService::device_added(&*iface.get().await, iface.signal_context(), dev.clone()).await; }
+                Some(old) if old != dev => {
+                    let _ = BluetoothService::device_changed(&*iface.get().await, iface.signal_context(), dev.clone()).await;
+                    if old.connected == dev.connected { continue; }
+                    let _ = if dev.connected {
+                        BluetoothService::device_connected(&*iface.get().await, iface.signal_context(), dev.mac.clone()).await
+                    } else {
+                        let reason = reasons.get(path.as_str()).copied().unwrap_or_default();
+                        BluetoothService::device_disconnected(&*iface.get().await, iface.signal_context(), dev.mac.clone(), reason).await
+                    };
+                }
+                _ => {}
+            }
+        }
+        known = devices;
+    }
+}
+
+/// Reasons from the `Device1.Disconnected` signals in `batch`, by device path. Older BlueZ
+/// does not send them, which leaves the reason unknown.
+fn disconnect_reasons(batch: &[zbus::Result<zbus::Message>]) -> HashMap<String, DisconnectReason> {
+    batch.iter().flatten()
+        .filter_map(|msg| {
+            let h = msg.header();
+            if h.interface().map(|i| i.as_str()) != Some(bluez::DEVICE) || h.member().map(|m| m.as_str()) != Some("Disconnected") { return None; }
+            let (name, _message): (String, String) = msg.body().deserialize().ok()?;
+            Some((h.path()?.to_string(), bluez::disconnect_reason(&name)))
+        })
+        .collect()
+}
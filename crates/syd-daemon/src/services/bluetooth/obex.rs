@@ -0,0 +1,438 @@
+use zbus::{proxy, Connection, Result, zvariant::{OwnedObjectPath, OwnedValue, Value}};
+use zbus::names::InterfaceName;
+use zbus::proxy::CacheProperties;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use syd_core::{BtTransfer, TransferError, TransferStatus};
+use crate::config;
+use crate::services::dbus::{at, prop};
+
+pub const SERVICE: &str = "org.bluez.obex";
+pub const ROOT: &str = "/org/bluez/obex";
+pub const TRANSFER: &str = "org.bluez.obex.Transfer1";
+const SESSION: &str = "org.bluez.obex.Session1";
+
+#[proxy(interface = "org.bluez.obex.Client1", default_service = "org.bluez.obex", default_path = "/org/bluez/obex")]
+pub trait Client {
+    fn create_session(&self, destination: &str, args: HashMap<&str, Value<'_>>) -> Result<OwnedObjectPath>;
+    fn remove_session(&self, session: &OwnedObjectPath) -> Result<()>;
+}
+
+#[proxy(interface = "org.bluez.obex.ObjectPush1", default_service = "org.bluez.obex")]
+pub trait ObjectPush {
+    fn send_file(&self, sourcefile: &str) -> Result<(OwnedObjectPath, HashMap<String, OwnedValue>)>;
+}
+
+#[proxy(interface = "org.bluez.obex.Transfer1", default_service = "org.bluez.obex")]
+pub trait Transfer {
+    fn cancel(&self) -> Result<()>;
+}
+
+#[proxy(interface = "org.bluez.obex.AgentManager1", default_service = "org.bluez.obex", default_path = "/org/bluez/obex")]
+pub trait AgentManager {
+    fn register_agent(&self, agent: &zbus::zvariant::ObjectPath<'_>) -> Result<()>;
+}
+
+/// What is known about a transfer besides what obexd reports in its properties.
+struct Tracked {
+    transfer: BtTransfer,
+    /// Set for files we send; the session is ours to close once its last file is done.
+    session: Option<OwnedObjectPath>,
+}
+
+/// Talks to obexd on the session bus it runs on, so a stand-in object tree works as well.
+/// Transfers are tracked from the moment they start, because obexd drops their objects as
+/// soon as they finish.
+#[derive(Clone)]
+pub struct Obex {
+    conn: Connection,
+    transfers: Arc<Mutex<HashMap<OwnedObjectPath, Tracked>>>,
+    /// Held while a send queues its files. obexd may report progress before the reply to
+    /// `SendFile` is handled, so `update` waits for the transfer to be tracked.
+    sending: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Obex {
+    pub fn new(conn: Connection) -> Self { Self { conn, transfers: Arc::default(), sending: Arc::default() } }
+
+    pub fn connection(&self) -> &Connection { &self.conn }
+
+    /// Queues `files` for `mac` in one Object Push session and returns their transfer ids.
+    /// If one of them cannot be queued, those queued before it are cancelled.
+    pub async fn send(&self, mac: &str, files: &[String]) -> std::result::Result<Vec<String>, TransferError> {
+        // obexd opens the files itself, from wherever it was started.
+        if let Some(missing) = files.iter().find(|f| !Path::new(f).is_absolute() || !Path::new(f).is_file()) {
+            return Err(TransferError::NotFound(format!("No file {}", missing)));
+        }
+        let client = ClientProxy::new(&self.conn).await?;
+        // Fails when obexd is not running or the device has no Object Push server.
+        let session = client.create_session(mac, HashMap::from([("Target", Value::from("opp"))])).await.map_err(|e| match e {
+            zbus::Error::MethodError(_, msg, _) => TransferError::NotAvailable(msg.unwrap_or_default()),
+            e => e.into(),
+        })?;
+        let push: ObjectPushProxy = at(&self.conn, &session).await?;
+        let _sending = self.sending.lock().await;
+        let mut ids: Vec<String> = Vec::new();
+        for file in files {
+            let (path, props) = match push.send_file(file).await {
+                Ok(res) => res,
+                Err(e) => {
+                    // The send fails as a whole. Cancelled transfers close the session as they
+                    // end, unless they have all ended already.
+                    for id in &ids { let _ = self.cancel(id).await; }
+                    if !self.transfers.lock().unwrap().values().any(|t| t.session.as_ref() == Some(&session)) {
+                        let _ = client.remove_session(&session).await;
+                    }
+                    return Err(TransferError::Failed(format!("Could not send {}: {}", file, e)));
+                }
+            };
+            let transfer = BtTransfer { id: path.to_string(), mac: mac.to_string(), filename: file.clone(), ..transfer_from(&props) };
+            ids.push(transfer.id.clone());
+            self.transfers.lock().unwrap().insert(path, Tracked { transfer, session: Some(session.clone()) });
+        }
+        Ok(ids)
+    }
+
+    pub fn list(&self) -> Vec<BtTransfer> {
+        let mut res: Vec<_> = self.transfers.lock().unwrap().values().map(|t| t.transfer.clone()).collect();
+        res.sort_by(|a, b| a.id.cmp(&b.id));
+        res
+    }
+
+    pub async fn cancel(&self, id: &str) -> std::result::Result<(), TransferError> {
+        let path = OwnedObjectPath::try_from(id).map_err(zbus::Error::from)?;
+        if !self.transfers.lock().unwrap().contains_key(&path) {
+            return Err(TransferError::NotFound(format!("No transfer {}", id)));
+        }
+        Ok(at::<TransferProxy>(&self.conn, &path).await?.cancel().await?)
+    }
+
+    /// Reads a transfer obexd has just announced, with the address of the device at the
+    /// other end of its session, and its MIME type if the sender gave one.
+    pub async fn read(&self, path: &OwnedObjectPath) -> Result<(BtTransfer, String)> {
+        let props = self.get_all(path, TRANSFER).await?;
+        let mut transfer = BtTransfer { id: path.to_string(), incoming: true, ..transfer_from(&props) };
+        if let Some(session) = prop::<OwnedObjectPath>(&props, "Session") {
+            transfer.mac = self.get_all(&session, SESSION).await.ok().and_then(|p| prop(&p, "Destination")).unwrap_or_default();
+        }
+        Ok((transfer, prop(&props, "Type").unwrap_or_default()))
+    }
+
+    /// Starts tracking an incoming transfer the user has accepted.
+    pub fn track(&self, transfer: BtTransfer) {
+        let Ok(path) = OwnedObjectPath::try_from(transfer.id.clone()) else { return };
+        self.transfers.lock().unwrap().insert(path, Tracked { transfer, session: None });
+    }
+
+    /// Applies a `PropertiesChanged` from a transfer and returns its new state. Finished
+    /// transfers are forgotten, and the session of a send closed after its last file.
+    pub async fn update(&self, path: &OwnedObjectPath, changed: &HashMap<String, OwnedValue>) -> Option<BtTransfer> {
+        drop(self.sending.lock().await);
+        let (transfer, done) = {
+            let mut transfers = self.transfers.lock().unwrap();
+            let t = transfers.get_mut(path)?;
+            if let Some(status) = prop::<String>(changed, "Status") { t.transfer.status = status_from(&status); }
+            if let Some(n) = prop(changed, "Transferred") { t.transfer.transferred = n; }
+            if let Some(n) = prop(changed, "Size") { t.transfer.size = n; }
+            if let Some(f) = prop::<String>(changed, "Filename").filter(|f| !f.is_empty()) { t.transfer.filename = f; }
+            let transfer = t.transfer.clone();
+            if !matches!(transfer.status, TransferStatus::Complete | TransferStatus::Error) { return Some(transfer); }
+            let session = transfers.remove(path).and_then(|t| t.session);
+            let last = session.filter(|s| !transfers.values().any(|t| t.session.as_ref() == Some(s)));
+            (transfer, last)
+        };
+        if let Some(session) = done {
+            if let Ok(client) = ClientProxy::new(&self.conn).await { let _ = client.remove_session(&session).await; }
+        }
+        Some(transfer)
+    }
+
+    async fn get_all(&self, path: &OwnedObjectPath, iface: &'static str) -> Result<HashMap<String, OwnedValue>> {
+        let p = zbus::fdo::PropertiesProxy::builder(&self.conn).destination(SERVICE)?.path(path.clone())?
+            .cache_properties(CacheProperties::No).build().await?;
+        Ok(p.get_all(Some(InterfaceName::from_static_str_unchecked(iface)).into()).await?)
+    }
+}
+
+/// Where received files go: the download folder from `user-dirs.dirs`, or `~/Downloads`.
+pub fn download_dir() -> PathBuf {
+    let home = PathBuf::from(std::env::var_os("HOME").unwrap_or_default());
+    std::fs::read_to_string(config::xdg_dir("XDG_CONFIG_HOME", ".config").join("user-dirs.dirs")).ok()
+        .and_then(|text| user_dir(&text, "XDG_DOWNLOAD_DIR", &home))
+        .unwrap_or_else(|| home.join("Downloads"))
+}
+
+/// Reads `key` from xdg-user-dirs' file of `KEY="$HOME/dir"` or `KEY="/abs/dir"` lines.
+/// Like the shell that sources it, the last assignment wins.
+fn user_dir(text: &str, key: &str, home: &Path) -> Option<PathBuf> {
+    let (_, value) = text.lines().rev()
+        .filter_map(|line| line.trim().split_once('='))
+        .find(|(k, _)| *k == key)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    match value.strip_prefix("$HOME") {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => Some(home.join(rest.trim_start_matches('/'))),
+        Some(_) => None,
+        None => Some(PathBuf::from(value)).filter(|p| p.is_absolute()),
+    }
+}
+
+/// A path in `dir` for `name` that does not overwrite anything, e.g. `photo (1).jpg`. Only
+/// the last component of `name` is used; it comes from the sending device.
+pub fn save_path(dir: &Path, name: &str) -> PathBuf {
+    let name = Path::new(name).file_name().and_then(|n| n.to_str()).filter(|n| !n.is_empty()).unwrap_or("received");
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (name, String::new()),
+    };
+    (0..).map(|i| if i == 0 { dir.join(name) } else { dir.join(format!("{} ({}){}", stem, i, ext)) })
+        .find(|p| !p.exists())
+        .unwrap_or_else(|| dir.join(name))
+}
+
+fn status_from(s: &str) -> TransferStatus {
+    match s {
+        "active" => TransferStatus::Active,
+        "suspended" => TransferStatus::Suspended,
+        "complete" => TransferStatus::Complete,
+        "error" => TransferStatus::Error,
+        _ => TransferStatus::Queued,
+    }
+}
+
+fn transfer_from(props: &HashMap<String, OwnedValue>) -> BtTransfer {
+    BtTransfer {
+        name: prop(props, "Name").unwrap_or_default(),
+        filename: prop(props, "Filename").unwrap_or_default(),
+        size: prop(props, "Size").unwrap_or(0),
+        transferred: prop(props, "Transferred").unwrap_or(0),
+        status: status_from(&prop::<String>(props, "Status").unwrap_or_default()),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// An empty directory of its own for each test.
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("syd-obex-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn keeps_names_inside_the_directory() {
+        let d = dir("escape");
+        assert_eq!(save_path(&d, "../../etc/passwd"), d.join("passwd"));
+        assert_eq!(save_path(&d, "/etc/passwd"), d.join("passwd"));
+        assert_eq!(save_path(&d, ".."), d.join("received"));
+        assert_eq!(save_path(&d, ""), d.join("received"));
+    }
+
+    #[test]
+    fn numbers_collisions() {
+        let d = dir("collide");
+        assert_eq!(save_path(&d, "photo.jpg"), d.join("photo.jpg"));
+        std::fs::write(d.join("photo.jpg"), b"").unwrap();
+        assert_eq!(save_path(&d, "photo.jpg"), d.join("photo (1).jpg"));
+        std::fs::write(d.join("photo (1).jpg"), b"").unwrap();
+        assert_eq!(save_path(&d, "photo.jpg"), d.join("photo (2).jpg"));
+        std::fs::write(d.join("notes"), b"").unwrap();
+        assert_eq!(save_path(&d, "notes"), d.join("notes (1)"));
+        std::fs::write(d.join("archive.tar.gz"), b"").unwrap();
+        assert_eq!(save_path(&d, "archive.tar.gz"), d.join("archive.tar (1).gz"));
+    }
+
+    #[test]
+    fn dotfiles_have_no_extension() {
+        let d = dir("dotfile");
+        assert_eq!(save_path(&d, ".bashrc"), d.join(".bashrc"));
+        std::fs::write(d.join(".bashrc"), b"").unwrap();
+        assert_eq!(save_path(&d, ".bashrc"), d.join(".bashrc (1)"));
+    }
+
+    #[test]
+    fn reads_the_download_folder() {
+        let home = Path::new("/home/ana");
+        let text = "# written by xdg-user-dirs-update\nXDG_DESKTOP_DIR=\"$HOME/Desktop\"\nXDG_DOWNLOAD_DIR=\"$HOME/Hämtningar\"\n";
+        assert_eq!(user_dir(text, "XDG_DOWNLOAD_DIR", home), Some(PathBuf::from("/home/ana/Hämtningar")));
+        assert_eq!(user_dir("XDG_DOWNLOAD_DIR=\"/srv/incoming\"", "XDG_DOWNLOAD_DIR", home), Some(PathBuf::from("/srv/incoming")));
+        assert_eq!(user_dir("XDG_DOWNLOAD_DIR=\"$HOME\"", "XDG_DOWNLOAD_DIR", home), Some(PathBuf::from("/home/ana")));
+        assert_eq!(user_dir("XDG_DOWNLOAD_DIR=\"$HOME/a\"\nXDG_DOWNLOAD_DIR=\"$HOME/b\"", "XDG_DOWNLOAD_DIR", home), Some(PathBuf::from("/home/ana/b")));
+        assert_eq!(user_dir("#XDG_DOWNLOAD_DIR=\"$HOME/x\"", "XDG_DOWNLOAD_DIR", home), None);
+        assert_eq!(user_dir("XDG_DOWNLOAD_DIR=\"$HOMEDIR/x\"", "XDG_DOWNLOAD_DIR", home), None);
+        assert_eq!(user_dir("XDG_DOWNLOAD_DIR=\"Downloads\"", "XDG_DOWNLOAD_DIR", home), None);
+        assert_eq!(user_dir(text, "XDG_MUSIC_DIR", home), None);
+    }
+
+    const PHONE: &str = "3C:28:6D:11:22:33";
+    const SESSION_PATH: &str = "/org/bluez/obex/client/session0";
+
+    type Calls = Arc<Mutex<Vec<String>>>;
+
+    fn path(p: &str) -> OwnedObjectPath { OwnedObjectPath::try_from(p).unwrap() }
+
+    fn changed(entries: Vec<(&str, Value<'_>)>) -> HashMap<String, OwnedValue> {
+        entries.into_iter().map(|(k, v)| (k.to_string(), OwnedValue::try_from(v).unwrap())).collect()
+    }
+
+    /// obexd's client side: one session per device, which refuses files named `broken`.
+    struct Client { calls: Calls }
+
+    #[zbus::interface(name = "org.bluez.obex.Client1")]
+    impl Client {
+        fn create_session(&self, destination: &str, _args: HashMap<String, OwnedValue>) -> zbus::fdo::Result<OwnedObjectPath> {
+            self.calls.lock().unwrap().push(format!("CreateSession({})", destination));
+            if destination != PHONE { return Err(zbus::fdo::Error::Failed("Unable to find service record".into())); }
+            Ok(path(SESSION_PATH))
+        }
+
+        fn remove_session(&self, session: OwnedObjectPath) {
+            self.calls.lock().unwrap().push(format!("RemoveSession({})", session.as_str()));
+        }
+    }
+
+    struct Push { calls: Calls, next: Mutex<u32>, delay: Duration }
+
+    #[zbus::interface(name = "org.bluez.obex.ObjectPush1")]
+    impl Push {
+        async fn send_file(&self, sourcefile: String, #[zbus(object_server)] server: &zbus::ObjectServer) -> zbus::fdo::Result<(OwnedObjectPath, HashMap<String, OwnedValue>)> {
+            self.calls.lock().unwrap().push(format!("SendFile({})", Path::new(&sourcefile).file_name().unwrap().to_string_lossy()));
+            tokio::time::sleep(self.delay).await;
+            if sourcefile.ends_with("broken") { return Err(zbus::fdo::Error::Failed("Invalid file".into())); }
+            let transfer = {
+                let mut next = self.next.lock().unwrap();
+                *next += 1;
+                path(&format!("{}/transfer{}", SESSION_PATH, *next - 1))
+            };
+            server.at(&transfer, Transferring { calls: self.calls.clone() }).await?;
+            Ok((transfer, changed(vec![
+                ("Name", Value::from(Path::new(&sourcefile).file_name().unwrap().to_str().unwrap())),
+                ("Size", Value::from(std::fs::metadata(&sourcefile).unwrap().len())),
+                ("Status", Value::from("queued")),
+            ])))
+        }
+    }
+
+    struct Transferring { calls: Calls }
+
+    #[zbus::interface(name = "org.bluez.obex.Transfer1")]
+    impl Transferring {
+        fn cancel(&self, #[zbus(header)] header: zbus::message::Header<'_>) {
+            self.calls.lock().unwrap().push(format!("Cancel({})", header.path().unwrap().as_str()));
+        }
+    }
+
+    /// An `Obex` talking to the stand-ins over a private connection, the serving end, which
+    /// has to be kept, and the calls obexd received. `SendFile` answers after `delay`.
+    async fn stand_in(delay: Duration) -> (Obex, Connection, Calls) {
+        let calls = Calls::default();
+        let (ours, theirs) = tokio::net::UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let server = zbus::connection::Builder::unix_stream(theirs).server(guid).unwrap().p2p()
+            .serve_at(ROOT, Client { calls: calls.clone() }).unwrap()
+            .serve_at(SESSION_PATH, Push { calls: calls.clone(), next: Mutex::new(0), delay }).unwrap()
+            .build();
+        let client = zbus::connection::Builder::unix_stream(ours).p2p().build();
+        let (server, client) = tokio::join!(server, client);
+        (Obex::new(client.unwrap()), server.unwrap(), calls)
+    }
+
+    /// Files with these names in a directory of their own.
+    fn files(test: &str, names: &[&str]) -> Vec<String> {
+        let d = dir(test);
+        names.iter().map(|n| {
+            std::fs::write(d.join(n), n.as_bytes()).unwrap();
+            d.join(n).to_string_lossy().into_owned()
+        }).collect()
+    }
+
+    #[tokio::test]
+    async fn sends_and_closes_the_session_after_the_last_file() {
+        let (obex, _server, calls) = stand_in(Duration::ZERO).await;
+        let files = files("send", &["a.txt", "photo.jpg"]);
+
+        let ids = obex.send(PHONE, &files).await.unwrap();
+        assert_eq!(ids, [format!("{}/transfer0", SESSION_PATH), format!("{}/transfer1", SESSION_PATH)]);
+        let list = obex.list();
+        assert_eq!((list[1].name.as_str(), list[1].size, list[1].status), ("photo.jpg", 9, TransferStatus::Queued));
+        assert_eq!((list[1].mac.as_str(), list[1].filename.as_str(), list[1].incoming), (PHONE, files[1].as_str(), false));
+
+        let first = path(&ids[0]);
+        let t = obex.update(&first, &changed(vec![("Status", Value::from("active")), ("Transferred", Value::from(3u64))])).await.unwrap();
+        assert_eq!((t.status, t.transferred), (TransferStatus::Active, 3));
+        let t = obex.update(&first, &changed(vec![("Status", Value::from("complete"))])).await.unwrap();
+        assert_eq!(t.status, TransferStatus::Complete);
+        assert_eq!(obex.list().len(), 1);
+        // The second file still needs the session.
+        assert!(!calls.lock().unwrap().iter().any(|c| c.starts_with("RemoveSession")));
+
+        obex.update(&path(&ids[1]), &changed(vec![("Status", Value::from("complete"))])).await.unwrap();
+        assert!(obex.list().is_empty());
+        assert_eq!(*calls.lock().unwrap(), [
+            format!("CreateSession({})", PHONE), "SendFile(a.txt)".into(), "SendFile(photo.jpg)".into(),
+            format!("RemoveSession({})", SESSION_PATH),
+        ]);
+        // Forgotten once finished.
+        assert_eq!(obex.update(&first, &changed(vec![("Status", Value::from("active"))])).await, None);
+    }
+
+    #[tokio::test]
+    async fn progress_before_the_reply_is_kept() {
+        let (obex, _server, _calls) = stand_in(Duration::from_millis(100)).await;
+        let files = files("early", &["a.txt"]);
+
+        let sending = tokio::spawn({
+            let (obex, files) = (obex.clone(), files.clone());
+            async move { obex.send(PHONE, &files).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // As if obexd's first PropertiesChanged were handled while SendFile's reply is on its way.
+        let early = obex.update(&path(&format!("{}/transfer0", SESSION_PATH)), &changed(vec![("Status", Value::from("active"))])).await;
+        assert_eq!(early.map(|t| t.status), Some(TransferStatus::Active));
+        sending.await.unwrap().unwrap();
+        assert_eq!(obex.list()[0].status, TransferStatus::Active);
+    }
+
+    #[tokio::test]
+    async fn a_failed_file_cancels_the_queued_ones() {
+        let (obex, _server, calls) = stand_in(Duration::ZERO).await;
+        let files = files("partial", &["a.txt", "broken"]);
+
+        let err = obex.send(PHONE, &files).await.unwrap_err();
+        assert!(matches!(&err, TransferError::Failed(m) if m.starts_with(&format!("Could not send {}", files[1]))), "{:?}", err);
+        let queued = format!("{}/transfer0", SESSION_PATH);
+        assert_eq!(calls.lock().unwrap()[3..], [format!("Cancel({})", queued)]);
+
+        // obexd reports the cancelled transfer as failed, which closes the session.
+        let t = obex.update(&path(&queued), &changed(vec![("Status", Value::from("error"))])).await.unwrap();
+        assert_eq!(t.status, TransferStatus::Error);
+        assert_eq!(calls.lock().unwrap().last().unwrap(), &format!("RemoveSession({})", SESSION_PATH));
+    }
+
+    #[tokio::test]
+    async fn a_failed_first_file_closes_the_session() {
+        let (obex, _server, calls) = stand_in(Duration::ZERO).await;
+        let files = files("first", &["broken", "a.txt"]);
+
+        assert!(matches!(obex.send(PHONE, &files).await, Err(TransferError::Failed(_))));
+        assert_eq!(calls.lock().unwrap()[1..], ["SendFile(broken)".to_string(), format!("RemoveSession({})", SESSION_PATH)]);
+        assert!(obex.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_before_opening_a_session() {
+        let (obex, _server, calls) = stand_in(Duration::ZERO).await;
+        assert!(matches!(obex.send(PHONE, &["relative.txt".into()]).await, Err(TransferError::NotFound(_))));
+        assert!(matches!(obex.send(PHONE, &["/nonexistent/a.txt".into()]).await, Err(TransferError::NotFound(_))));
+        assert!(calls.lock().unwrap().is_empty());
+
+        let files = files("refused", &["a.txt"]);
+        let err = obex.send("00:11:22:33:44:55", &files).await;
+        assert!(matches!(err, Err(TransferError::NotAvailable(m)) if m == "Unable to find service record"));
+    }
+}
@@ -0,0 +1,174 @@
+use zbus::{interface, object_server::SignalContext, Connection, MatchRule, MessageStream, zvariant::{ObjectPath, OwnedObjectPath, OwnedValue}};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, atomic::{AtomicU32, Ordering}};
+use tokio::sync::{mpsc, oneshot};
+use futures::StreamExt;
+use syd_core::{BtTransfer, TransferError, TransferRequest, TransferStatus};
+use super::{bluez::Bluez, obex::{self, Obex}};
+
+const AGENT_PATH: &str = "/org/syd/ObexAgent";
+
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "org.bluez.obex.Error")]
+pub enum AgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    Rejected(String),
+}
+
+pub enum AgentEvent {
+    Requested(TransferRequest),
+    Cancelled(u32),
+}
+
+/// Incoming files waiting for the shell, shared by the agent and `BluetoothTransferService`.
+#[derive(Clone)]
+pub struct Requests {
+    pending: Arc<Mutex<HashMap<u32, oneshot::Sender<bool>>>>,
+    next_id: Arc<AtomicU32>,
+    tx: mpsc::UnboundedSender<AgentEvent>,
+}
+
+impl Requests {
+    pub fn new(tx: mpsc::UnboundedSender<AgentEvent>) -> Self {
+        Self { pending: Arc::default(), next_id: Arc::new(AtomicU32::new(1)), tx }
+    }
+
+    /// Sends `request` under a fresh id and waits for the answer.
+    async fn ask(&self, mut request: TransferRequest) -> bool {
+        request.id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request.id, reply);
+        let _ = self.tx.send(AgentEvent::Requested(request));
+        rx.await.unwrap_or(false)
+    }
+
+    /// `Err` if the request is gone.
+    pub fn respond(&self, id: u32, accept: bool) -> Result<(), ()> {
+        let reply = self.pending.lock().unwrap().remove(&id).ok_or(())?;
+        let _ = reply.send(accept);
+        Ok(())
+    }
+
+    /// Drops every request and tells the shell to close their prompts.
+    fn withdraw(&self) {
+        for (id, _) in self.pending.lock().unwrap().drain() {
+            let _ = self.tx.send(AgentEvent::Cancelled(id));
+        }
+    }
+}
+
+pub struct BluetoothTransferService { obex: Obex, requests: Requests }
+impl BluetoothTransferService {
+    pub fn new(obex: Obex, requests: Requests) -> Self { Self { obex, requests } }
+}
+#[interface(name = "org.syd.BluetoothTransfer")]
+impl BluetoothTransferService {
+    /// Sends `files`, given as absolute paths, to `mac` one after another. Returns the ids
+    /// their progress is reported under.
+    async fn send_files(&self, mac: String, files: Vec<String>) -> Result<Vec<String>, TransferError> {
+        self.obex.send(&mac, &files).await
+    }
+
+    /// Transfers that are queued or running, both ways.
+    async fn get_transfers(&self) -> Vec<BtTransfer> { self.obex.list() }
+
+    async fn cancel(&self, id: String) -> Result<(), TransferError> { self.obex.cancel(&id).await }
+
+    /// Answers a `TransferRequested`. Accepted files are saved in the download directory.
+    async fn agent_reply(&self, id: u32, accept: bool) -> zbus::fdo::Result<()> {
+        self.requests.respond(id, accept).map_err(|_| zbus::fdo::Error::InvalidArgs(format!("No transfer request {}", id)))
+    }
+
+    #[zbus(signal)] async fn transfer_requested(&self, ctxt: &SignalContext<'_>, request: TransferRequest) -> zbus::Result<()>;
+    /// The sender gave up, or obexd did; close the request's prompt.
+    #[zbus(signal)] async fn transfer_request_cancelled(&self, ctxt: &SignalContext<'_>, id: u32) -> zbus::Result<()>;
+    /// Progress, about once a second while a file is moving.
+    #[zbus(signal)] async fn transfer_changed(&self, ctxt: &SignalContext<'_>, transfer: BtTransfer) -> zbus::Result<()>;
+    /// The transfer is `Complete` or ended in `Error`, cancellation included.
+    #[zbus(signal)] async fn transfer_finished(&self, ctxt: &SignalContext<'_>, transfer: BtTransfer) -> zbus::Result<()>;
+}
+
+pub struct ObexAgent { obex: Obex, bluez: Bluez, requests: Requests }
+
+impl ObexAgent {
+    pub fn new(obex: Obex, bluez: Bluez, requests: Requests) -> Self { Self { obex, bluez, requests } }
+}
+
+#[interface(name = "org.bluez.obex.Agent1")]
+impl ObexAgent {
+    async fn release(&self) { self.requests.withdraw(); }
+
+    /// Returns where obexd should save the file, which never replaces an existing one.
+    async fn authorize_push(&self, transfer: OwnedObjectPath) -> Result<String, AgentError> {
+        let (t, mime) = self.obex.read(&transfer).await?;
+        let alias = self.bluez.devices().await.unwrap_or_default().into_iter()
+            .find(|d| d.mac.eq_ignore_ascii_case(&t.mac))
+            .map_or_else(|| t.mac.clone(), |d| d.alias);
+        let req = TransferRequest { mac: t.mac.clone(), alias, name: t.name.clone(), size: t.size, mime, ..Default::default() };
+        if !self.requests.ask(req).await { return Err(AgentError::Rejected("Declined by the user".into())); }
+
+        let dir = obex::download_dir();
+        if let Err(e) = std::fs::create_dir_all(&dir) { return Err(AgentError::Rejected(format!("Cannot save to {}: {}", dir.display(), e))); }
+        let path = obex::save_path(&dir, &t.name).to_string_lossy().into_owned();
+        self.obex.track(BtTransfer { filename: path.clone(), ..t });
+        Ok(path)
+    }
+
+    async fn cancel(&self) { self.requests.withdraw(); }
+}
+
+pub async fn run(conn: Connection, agent: ObexAgent) {
+    if conn.object_server().at(AGENT_PATH, agent).await.is_err() { return; }
+    let Ok(dbus) = zbus::fdo::DBusProxy::new(&conn).await else { return };
+    let Ok(mut owners) = dbus.receive_name_owner_changed_with_args(&[(0, obex::SERVICE)]).await else { return };
+
+    let register = || async {
+        let Ok(m) = obex::AgentManagerProxy::new(&conn).await else { return };
+        if let Err(e) = m.register_agent(&ObjectPath::from_static_str_unchecked(AGENT_PATH)).await {
+            eprintln!("OBEX agent registration failed: {}", e);
+        }
+    };
+    // Usually starts obexd, which is D-Bus activated.
+    register().await;
+    // obexd exits when idle and forgets its agent.
+    while let Some(sig) = owners.next().await {
+        if sig.args().map(|a| a.new_owner().is_some()).unwrap_or(false) { register().await; }
+    }
+}
+
+/// Sends `TransferChanged` and `TransferFinished` from the transfers' property changes.
+pub async fn monitor(conn: Connection, obex: Obex) {
+    let iface = conn.object_server().interface::<_, BluetoothTransferService>("/org/syd/BluetoothTransfer").await.unwrap();
+    let Ok(rule) = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .sender(obex::SERVICE)
+        .and_then(|b| b.interface("org.freedesktop.DBus.Properties"))
+        .and_then(|b| b.member("PropertiesChanged"))
+        .and_then(|b| b.path_namespace(obex::ROOT))
+        .map(|b| b.build()) else { return };
+    let Ok(mut events) = MessageStream::for_match_rule(rule, obex.connection(), None).await else { return };
+
+    while let Some(msg) = events.next().await {
+        let Ok(msg) = msg else { continue };
+        let Some(path) = msg.header().path().map(|p| OwnedObjectPath::from(p.to_owned())) else { continue };
+        let Ok((name, changed, _)) = msg.body().deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>() else { continue };
+        if name != obex::TRANSFER { continue; }
+        let Some(t) = obex.update(&path, &changed).await else { continue };
+        let _ = if matches!(t.status, TransferStatus::Complete | TransferStatus::Error) {
+            BluetoothTransferService::transfer_finished(&*iface.get().await, iface.signal_context(), t).await
+        } else {
+            BluetoothTransferService::transfer_changed(&*iface.get().await, iface.signal_context(), t).await
+        };
+    }
+}
+
+pub async fn monitor_requests(conn: Connection, mut rx: mpsc::UnboundedReceiver<AgentEvent>) {
+    let iface = conn.object_server().interface::<_, BluetoothTransferService>("/org/syd/BluetoothTransfer").await.unwrap();
+    while let Some(ev) = rx.recv().await {
+        let _ = match ev {
+            AgentEvent::Requested(req) => BluetoothTransferService::transfer_requested(&*iface.get().await, iface.signal_context(), req).await,
+            AgentEvent::Cancelled(id) => BluetoothTransferService::transfer_request_cancelled(&*iface.get().await, iface.signal_context(), id).await,
+        };
+    }
+}
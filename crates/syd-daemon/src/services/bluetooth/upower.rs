@@ -0,0 +1,44 @@
+use zbus::{proxy, Connection, Result, zvariant::OwnedObjectPath};
+use zbus::names::InterfaceName;
+use zbus::proxy::CacheProperties;
+use std::collections::HashMap;
+use crate::services::dbus::prop;
+
+pub const SERVICE: &str = "org.freedesktop.UPower";
+pub const DEVICES_PATH: &str = "/org/freedesktop/UPower/devices";
+
+#[proxy(interface = "org.freedesktop.UPower", default_service = "org.freedesktop.UPower", default_path = "/org/freedesktop/UPower")]
+trait UPower {
+    fn enumerate_devices(&self) -> Result<Vec<OwnedObjectPath>>;
+}
+
+/// Battery levels UPower knows for Bluetooth devices, by upper-case address. Mice and
+/// keyboards report theirs over HID, which BlueZ does not see.
+pub async fn batteries(conn: &Connection) -> HashMap<String, i16> {
+    let mut res = HashMap::new();
+    let Ok(upower) = UPowerProxy::new(conn).await else { return res };
+    for path in upower.enumerate_devices().await.unwrap_or_default() {
+        let Ok(p) = zbus::fdo::PropertiesProxy::builder(conn).destination(SERVICE).and_then(|b| b.path(path)) else { continue };
+        let Ok(p) = p.cache_properties(CacheProperties::No).build().await else { continue };
+        let Ok(props) = p.get_all(Some(InterfaceName::from_static_str_unchecked("org.freedesktop.UPower.Device")).into()).await else { continue };
+        if !prop::<bool>(&props, "IsPresent").unwrap_or(true) { continue; }
+        // HID batteries carry the address in the native path, e.g. `hid-aa:bb:..-battery`,
+        // BlueZ ones in the object path, e.g. `/org/bluez/hci0/dev_AA_BB_..`.
+        let native: String = prop(&props, "NativePath").unwrap_or_default();
+        let serial: String = prop(&props, "Serial").unwrap_or_default();
+        let Some(mac) = find_mac(&native).or_else(|| find_mac(&serial)) else { continue };
+        let pct: f64 = prop(&props, "Percentage").unwrap_or(0.0);
+        res.insert(mac, pct.round().clamp(0.0, 100.0) as i16);
+    }
+    res
+}
+
+/// The first Bluetooth address in `s`, written with colons or underscores.
+fn find_mac(s: &str) -> Option<String> {
+    let b = s.as_bytes();
+    (0..b.len().saturating_sub(16)).find_map(|i| {
+        let w = &b[i..i + 17];
+        let ok = w.iter().enumerate().all(|(j, c)| if j % 3 == 2 { *c == b':' || *c == b'_' } else { c.is_ascii_hexdigit() });
+        ok.then(|| String::from_utf8_lossy(w).replace('_', ":").to_ascii_uppercase())
+    })
+}
@@ -0,0 +1,19 @@
+use zbus::{Connection, Result, zvariant::{ObjectPath, OwnedObjectPath, OwnedValue}};
+use zbus::proxy::{CacheProperties, ProxyDefault};
+use std::collections::HashMap;
+
+/// Builds an uncached proxy for the object at `path`. Most lookups here are one-shot reads,
+/// so subscribing to property changes for every object would only add bus traffic.
+pub async fn at<'p, P>(conn: &Connection, path: &'p OwnedObjectPath) -> Result<P>
+where P: ProxyDefault + From<zbus::Proxy<'p>>
+{
+    zbus::proxy::Builder::<P>::new(conn)
+        .path(ObjectPath::from(path))?
+        .cache_properties(CacheProperties::No)
+        .build().await
+}
+
+/// Property `key` of a `GetAll` or `GetManagedObjects` result, if it has type `T`.
+pub fn prop<T: TryFrom<OwnedValue>>(props: &HashMap<String, OwnedValue>, key: &str) -> Option<T> {
+    T::try_from(props.get(key)?.try_clone().ok()?).ok()
+}
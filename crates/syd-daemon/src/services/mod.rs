@@ -1,8 +1,10 @@
+pub mod dbus;
 pub mod audio;
 pub mod brightness;
 pub mod media;
 pub mod system;
 pub mod network;
+pub mod rfkill;
 pub mod bluetooth;
 pub mod notifications;
 pub mod tray; 
@@ -0,0 +1,195 @@
+use zbus::{interface, Connection, zvariant::{OwnedObjectPath, Value}};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, atomic::{AtomicU32, Ordering}};
+use tokio::sync::{mpsc, oneshot};
+use futures::StreamExt;
+use syd_core::{SecretKind, SecretRequest};
+use super::{nm, NetworkService};
+use crate::services::dbus::prop;
+
+const AGENT_PATH: &str = "/org/freedesktop/NetworkManager/SecretAgent";
+const AGENT_ID: &str = "org.syd.Daemon";
+const CAP_VPN_HINTS: u32 = 0x1;
+
+// NM_SECRET_AGENT_GET_SECRETS_FLAGS
+const FLAG_ALLOW_INTERACTION: u32 = 0x1;
+const FLAG_REQUEST_NEW: u32 = 0x2;
+
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "org.freedesktop.NetworkManager.SecretAgent")]
+pub enum AgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NoSecrets(String),
+    UserCanceled(String),
+}
+
+pub enum AgentEvent {
+    Requested(SecretRequest),
+    Cancelled(u32),
+}
+
+struct Pending {
+    path: OwnedObjectPath,
+    setting: String,
+    reply: oneshot::Sender<Option<HashMap<String, String>>>,
+}
+
+/// Secret requests waiting for an answer from the shell, shared with `NetworkService`.
+#[derive(Clone, Default)]
+pub struct PendingSecrets(Arc<Mutex<HashMap<u32, Pending>>>);
+
+impl PendingSecrets {
+    /// Answers request `id`; `None` cancels it. Returns false if the request is gone.
+    pub fn respond(&self, id: u32, secrets: Option<HashMap<String, String>>) -> bool {
+        match self.0.lock().unwrap().remove(&id) {
+            Some(p) => p.reply.send(secrets).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Puts secret requests in front of the user through `SecretsRequested`. Shared by every
+/// backend's agent, so the shell sees the same prompts whichever one is in use.
+#[derive(Clone)]
+pub struct Prompter {
+    pending: PendingSecrets,
+    next_id: Arc<AtomicU32>,
+    tx: mpsc::UnboundedSender<AgentEvent>,
+}
+
+impl Prompter {
+    pub fn new(tx: mpsc::UnboundedSender<AgentEvent>) -> (Self, PendingSecrets) {
+        let p = PendingSecrets::default();
+        (Self { pending: p.clone(), next_id: Arc::new(AtomicU32::new(1)), tx }, p)
+    }
+
+    /// Sends `request` under a fresh id and waits for the answer. `path` and `setting`
+    /// identify the request for `withdraw`. `None` means the user or the backend gave up.
+    pub async fn ask(&self, mut request: SecretRequest, path: OwnedObjectPath, setting: &str) -> Option<HashMap<String, String>> {
+        request.id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply, rx) = oneshot::channel();
+        self.pending.0.lock().unwrap().insert(request.id, Pending { path, setting: setting.to_string(), reply });
+        let _ = self.tx.send(AgentEvent::Requested(request));
+        rx.await.ok().flatten()
+    }
+
+    /// Drops the requests matching `path` and `setting`, or every request when `path` is
+    /// `None`, and tells the shell to close their prompts.
+    pub fn withdraw(&self, path: Option<&OwnedObjectPath>, setting: &str) {
+        let mut pending = self.pending.0.lock().unwrap();
+        let ids: Vec<u32> = pending.iter()
+            .filter(|(_, p)| path.is_none_or(|path| p.path == *path && p.setting == setting))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            pending.remove(&id);
+            let _ = self.tx.send(AgentEvent::Cancelled(id));
+        }
+    }
+}
+
+pub struct SecretAgent { prompter: Prompter }
+
+impl SecretAgent {
+    pub fn new(prompter: Prompter) -> Self { Self { prompter } }
+}
+
+#[interface(name = "org.freedesktop.NetworkManager.SecretAgent")]
+impl SecretAgent {
+    async fn get_secrets(
+        &self,
+        connection: nm::Settings,
+        connection_path: OwnedObjectPath,
+        setting_name: String,
+        hints: Vec<String>,
+        flags: u32,
+    ) -> Result<HashMap<String, HashMap<String, Value<'static>>>, AgentError> {
+        // Syd keeps no secrets of its own, so without a user to ask there is nothing to offer.
+        if flags & FLAG_ALLOW_INTERACTION == 0 {
+            return Err(AgentError::NoSecrets("Interaction not allowed".into()));
+        }
+
+        let (kind, fields) = required_fields(&connection, &setting_name, &hints);
+        let request = SecretRequest {
+            id: 0,
+            kind,
+            connection: connection.get("connection").and_then(|c| prop(c, "id")).unwrap_or_default(),
+            ssid: connection.get("802-11-wireless")
+                .and_then(|w| prop::<Vec<u8>>(w, "ssid"))
+                .map(|s| String::from_utf8_lossy(&s).into_owned())
+                .unwrap_or_default(),
+            fields,
+            retry: flags & FLAG_REQUEST_NEW != 0,
+        };
+        let Some(secrets) = self.prompter.ask(request, connection_path, &setting_name).await else {
+            return Err(AgentError::UserCanceled("Request cancelled".into()));
+        };
+        let setting = if setting_name == "vpn" {
+            HashMap::from([("secrets".to_string(), Value::from(secrets))])
+        } else {
+            secrets.into_iter().map(|(k, v)| (k, Value::from(v))).collect()
+        };
+        Ok(HashMap::from([(setting_name, setting)]))
+    }
+
+    async fn cancel_get_secrets(&self, connection_path: OwnedObjectPath, setting_name: String) {
+        self.prompter.withdraw(Some(&connection_path), &setting_name);
+    }
+
+    // NetworkManager stores system-owned secrets itself; there is nothing for syd to persist.
+    async fn save_secrets(&self, _connection: nm::Settings, _connection_path: OwnedObjectPath) {}
+    async fn delete_secrets(&self, _connection: nm::Settings, _connection_path: OwnedObjectPath) {}
+}
+
+/// Works out which keys of `setting` the user has to fill in.
+fn required_fields(connection: &nm::Settings, setting: &str, hints: &[String]) -> (SecretKind, Vec<String>) {
+    let get = |key: &str| -> String { connection.get(setting).and_then(|s| prop(s, key)).unwrap_or_default() };
+    let hinted: Vec<String> = hints.iter().filter(|h| !h.starts_with("x-")).cloned().collect();
+    match setting {
+        "802-11-wireless-security" => match get("key-mgmt").as_str() {
+            "none" => (SecretKind::Wifi, vec!["wep-key0".into()]),
+            _ => (SecretKind::Wifi, vec!["psk".into()]),
+        },
+        "802-1x" => {
+            let eap: Vec<String> = connection.get(setting).and_then(|s| prop(s, "eap")).unwrap_or_default();
+            let mut fields = Vec::new();
+            if get("identity").is_empty() { fields.push("identity".into()); }
+            if eap.iter().any(|e| e == "tls") { fields.push("private-key-password".into()); } else { fields.push("password".into()); }
+            (SecretKind::Enterprise, fields)
+        }
+        "vpn" if hinted.is_empty() => (SecretKind::Vpn, vec!["password".into()]),
+        "vpn" => (SecretKind::Vpn, hinted),
+        _ => (SecretKind::Wifi, hinted),
+    }
+}
+
+async fn register(sys: &Connection) {
+    if let Ok(m) = nm::AgentManagerProxy::new(sys).await {
+        if let Err(e) = m.register_with_capabilities(AGENT_ID, CAP_VPN_HINTS).await {
+            eprintln!("Secret agent registration failed: {}", e);
+        }
+    }
+}
+
+pub async fn run(sys: Connection, agent: SecretAgent) {
+    if sys.object_server().at(AGENT_PATH, agent).await.is_err() { return; }
+    let Ok(dbus) = zbus::fdo::DBusProxy::new(&sys).await else { return };
+    let Ok(mut owners) = dbus.receive_name_owner_changed_with_args(&[(0, "org.freedesktop.NetworkManager")]).await else { return };
+
+    register(&sys).await;
+    // NetworkManager forgets its agents when it restarts.
+    while let Some(sig) = owners.next().await {
+        if sig.args().map(|a| a.new_owner().is_some()).unwrap_or(false) { register(&sys).await; }
+    }
+}
+
+pub async fn monitor(conn: Connection, mut rx: mpsc::UnboundedReceiver<AgentEvent>) {
+    let iface = conn.object_server().interface::<_, NetworkService>("/org/syd/Network").await.unwrap();
+    while let Some(ev) = rx.recv().await {
+        let _ = match ev {
+            AgentEvent::Requested(req) => NetworkService::secrets_requested(&*iface.get().await, iface.signal_context(), req).await,
+            AgentEvent::Cancelled(id) => NetworkService::secrets_cancelled(&*iface.get().await, iface.signal_context(), id).await,
+        };
+    }
+}
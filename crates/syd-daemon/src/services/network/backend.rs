@@ -0,0 +1,260 @@
+use zbus::{Connection, MatchRule, MessageStream};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio::time::{interval, timeout, Duration};
+use std::collections::{HashMap, HashSet};
+use futures::{stream, StreamExt};
+use syd_core::{ConnectStage, Connectivity, Metered, NetDevice, SavedNetwork, NetworkError, NetworkStatus, WifiNet, WifiSettings};
+use super::{agent::Prompter, iwd, networkd, nm, NetworkService};
+use crate::config::NetworkBackend;
+
+/// The service `org.syd.Network` is backed by. NetworkManager supports everything; iwd and
+/// networkd cover Wi-Fi, status and devices, and the NM-only methods fail with `Failed`.
+#[derive(Clone)]
+pub enum Backend {
+    Nm(nm::Nm),
+    Iwd(iwd::Iwd),
+    Networkd(networkd::Networkd),
+}
+
+/// Picks the configured backend, or the first one running: NetworkManager, which may itself
+/// drive iwd, then iwd, then networkd. Falls back to NetworkManager.
+pub async fn select(sys: Connection, choice: NetworkBackend, prompter: Prompter) -> Backend {
+    let running = |name: &'static str| {
+        let sys = sys.clone();
+        async move {
+            let Ok(dbus) = zbus::fdo::DBusProxy::new(&sys).await else { return false };
+            match name.try_into() {
+                Ok(name) => dbus.name_has_owner(name).await.unwrap_or(false),
+                Err(_) => false,
+            }
+        }
+    };
+    let choice = match choice {
+        NetworkBackend::Auto if running("org.freedesktop.NetworkManager").await => NetworkBackend::NetworkManager,
+        NetworkBackend::Auto if running(iwd::SERVICE).await => NetworkBackend::Iwd,
+        NetworkBackend::Auto if running(networkd::NETWORKD).await => NetworkBackend::Networkd,
+        NetworkBackend::Auto => NetworkBackend::NetworkManager,
+        c => c,
+    };
+    let backend = match choice {
+        NetworkBackend::Iwd => Backend::Iwd(iwd::Iwd::new(sys)),
+        NetworkBackend::Networkd => Backend::Networkd(networkd::Networkd::new(sys, prompter)),
+        _ => Backend::Nm(nm::Nm::new(sys)),
+    };
+    println!("Network backend: {}", backend.name());
+    backend
+}
+
+/// Converts a signal strength in dBm to percent, the way NetworkManager does.
+pub fn strength_from_dbm(dbm: i32) -> u8 {
+    ((dbm + 100) * 2).clamp(0, 100) as u8
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Nm(_) => "NetworkManager",
+            Backend::Iwd(_) => "iwd",
+            Backend::Networkd(_) => "systemd-networkd",
+        }
+    }
+
+    /// For methods only NetworkManager can serve.
+    pub fn nm(&self) -> Result<&nm::Nm, NetworkError> {
+        match self {
+            Backend::Nm(nm) => Ok(nm),
+            _ => Err(self.unsupported()),
+        }
+    }
+
+    fn unsupported(&self) -> NetworkError {
+        NetworkError::Failed(format!("Not supported with {}", self.name()))
+    }
+
+    pub async fn status(&self) -> zbus::Result<NetworkStatus> {
+        match self {
+            Backend::Nm(nm) => nm.status().await,
+            Backend::Iwd(iwd) => iwd.status().await,
+            Backend::Networkd(nd) => nd.status().await,
+        }
+    }
+
+    pub async fn access_points(&self) -> zbus::Result<Vec<WifiNet>> {
+        match self {
+            Backend::Nm(nm) => nm.access_points().await,
+            Backend::Iwd(iwd) => iwd.access_points().await,
+            Backend::Networkd(nd) => nd.access_points().await,
+        }
+    }
+
+    pub async fn request_scan(&self) -> zbus::Result<()> {
+        match self {
+            Backend::Nm(nm) => nm.request_scan().await,
+            Backend::Iwd(iwd) => iwd.request_scan().await,
+            Backend::Networkd(nd) => nd.request_scan().await,
+        }
+    }
+
+    pub async fn set_wifi(&self, on: bool) {
+        let res = match self {
+            Backend::Nm(_) => {
+                let _ = Command::new("nmcli").args(["radio", "wifi", if on{"on"}else{"off"}]).output().await;
+                Ok(())
+            }
+            Backend::Iwd(iwd) => iwd.set_wifi(on).await.map_err(zbus::fdo::Error::from),
+            Backend::Networkd(nd) => nd.set_wifi(on).await,
+        };
+        if let Err(e) = res { eprintln!("Could not switch Wi-Fi {}: {}", if on { "on" } else { "off" }, e); }
+    }
+
+    pub async fn connect(&self, ssid: &str, pass: &str, progress: mpsc::UnboundedSender<ConnectStage>) -> Result<(), NetworkError> {
+        match self {
+            Backend::Nm(nm) => nm.connect(ssid, pass, progress).await,
+            Backend::Iwd(iwd) => iwd.connect(ssid, pass, progress).await,
+            Backend::Networkd(nd) => nd.connect(ssid, pass, progress).await,
+        }
+    }
+
+    pub async fn connect_advanced(&self, w: &WifiSettings, progress: mpsc::UnboundedSender<ConnectStage>) -> Result<(), NetworkError> {
+        match self {
+            Backend::Nm(nm) => nm.connect_advanced(w, progress).await,
+            Backend::Iwd(iwd) => iwd.connect_advanced(w, progress).await,
+            Backend::Networkd(nd) => nd.connect_advanced(w, progress).await,
+        }
+    }
+
+    pub async fn forget(&self, ssid: &str) {
+        let res = match self {
+            Backend::Nm(nm) => nm.forget(ssid).await,
+            Backend::Iwd(iwd) => iwd.forget(ssid).await,
+            Backend::Networkd(nd) => nd.forget(ssid).await,
+        };
+        if let Err(e) = res { eprintln!("Could not forget {}: {}", ssid, e); }
+    }
+
+    pub async fn saved_networks(&self) -> zbus::Result<Vec<SavedNetwork>> {
+        match self {
+            Backend::Nm(nm) => nm.saved_networks().await,
+            Backend::Iwd(iwd) => iwd.saved_networks().await,
+            Backend::Networkd(nd) => nd.saved_networks().await,
+        }
+    }
+
+    /// `id` is a profile name or UUID; the other backends name networks by SSID.
+    pub async fn set_autoconnect(&self, id: &str, enabled: bool) -> Result<(), NetworkError> {
+        match self {
+            Backend::Nm(nm) => nm.edit_profile(id, "autoconnect", enabled.into()).await,
+            Backend::Iwd(iwd) => iwd.set_autoconnect(id, enabled).await,
+            Backend::Networkd(nd) => nd.set_autoconnect(id, enabled).await,
+        }
+    }
+
+    pub async fn set_priority(&self, id: &str, priority: i32) -> Result<(), NetworkError> {
+        match self {
+            Backend::Nm(nm) => nm.edit_profile(id, "autoconnect-priority", priority.into()).await,
+            Backend::Networkd(nd) => nd.set_priority(id, priority).await,
+            Backend::Iwd(_) => Err(self.unsupported()),
+        }
+    }
+
+    pub async fn set_metered(&self, id: &str, metered: Metered) -> Result<(), NetworkError> {
+        self.nm()?.set_metered(id, metered).await
+    }
+
+    /// iwd and wpa_supplicant know networks by SSID only, so there is nothing to rename.
+    pub async fn rename_network(&self, id: &str, name: &str) -> Result<(), NetworkError> {
+        if name.is_empty() { return Err(NetworkError::Failed("The name cannot be empty".into())); }
+        self.nm()?.edit_profile(id, "id", name.into()).await
+    }
+
+    pub async fn devices(&self) -> zbus::Result<Vec<NetDevice>> {
+        match self {
+            Backend::Nm(nm) => Ok(nm.devices().await?.into_iter().map(|(_, d)| d).collect()),
+            Backend::Iwd(iwd) => iwd.devices().await,
+            Backend::Networkd(nd) => nd.devices().await,
+        }
+    }
+
+    /// Only NetworkManager knows which connections are metered.
+    pub async fn metered_interfaces(&self) -> zbus::Result<HashSet<String>> {
+        match self {
+            Backend::Nm(nm) => nm.metered_interfaces().await,
+            _ => Ok(HashSet::new()),
+        }
+    }
+
+    /// Re-runs NetworkManager's check; the other backends report what they last saw.
+    pub async fn check_connectivity(&self) -> zbus::Result<Connectivity> {
+        match self {
+            Backend::Nm(nm) => nm.check_connectivity().await,
+            _ => Ok(self.status().await?.connectivity),
+        }
+    }
+
+    /// The URL the backend probes for connectivity, empty if it has none.
+    pub async fn check_uri(&self) -> String {
+        match self {
+            Backend::Nm(nm) => nm.check_uri().await.unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Keeps the shell up to date with iwd or networkd. Neither reports addresses, and their
+/// objects are spread over several services, so any signal from them (or a periodic tick)
+/// triggers a fresh look at everything, and only the differences are sent.
+pub async fn monitor(conn: Connection, backend: Backend) {
+    let iface = conn.object_server().interface::<_, NetworkService>("/org/syd/Network").await.unwrap();
+    let (sys, senders) = match &backend {
+        Backend::Nm(_) => return,
+        Backend::Iwd(iwd) => (iwd.connection().clone(), vec![iwd::SERVICE]),
+        Backend::Networkd(nd) => (nd.connection().clone(), vec![networkd::NETWORKD, networkd::SUPPLICANT]),
+    };
+    let mut events = Vec::new();
+    for sender in senders {
+        let Ok(rule) = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .sender(sender)
+            .map(|b| b.build()) else { continue };
+        if let Ok(s) = MessageStream::for_match_rule(rule, &sys, None).await { events.push(s); }
+    }
+    let mut events = stream::select_all(events);
+    // Addresses change without any signal from the backend, so poll as well.
+    let mut tick = interval(Duration::from_secs(10));
+
+    let mut status = None;
+    let mut nets = Vec::new();
+    let mut known: HashMap<String, NetDevice> = HashMap::new();
+    loop {
+        tokio::select! {
+            Some(_) = events.next() => {
+                // Scans and reconnects come as bursts of property changes.
+                while let Ok(Some(_)) = timeout(Duration::from_millis(300), events.next()).await {}
+            }
+            _ = tick.tick() => {},
+        }
+
+        let curr = backend.status().await.unwrap_or_default();
+        if status.as_ref() != Some(&curr) {
+            let _ = NetworkService::state_changed(&*iface.get().await, iface.signal_context(), curr.clone()).await;
+            status = Some(curr);
+        }
+
+        let curr = backend.access_points().await.unwrap_or_default();
+        if curr != nets {
+            let _ = NetworkService::access_points_changed(&*iface.get().await, iface.signal_context(), curr.clone()).await;
+            nets = curr;
+        }
+
+        let devices: HashMap<String, NetDevice> = backend.devices().await.unwrap_or_default().into_iter().map(|d| (d.interface.clone(), d)).collect();
+        for gone in known.keys().filter(|k| !devices.contains_key(*k)) {
+            let _ = NetworkService::device_removed(&*iface.get().await, iface.signal_context(), gone.clone()).await;
+        }
+        for (name, dev) in &devices {
+            if known.get(name) == Some(dev) { continue; }
+            let _ = NetworkService::device_changed(&*iface.get().await, iface.signal_context(), dev.clone()).await;
+        }
+        known = devices;
+    }
+}
@@ -0,0 +1,453 @@
+use zbus::{interface, proxy, Connection, Result, zvariant::{ObjectPath, OwnedObjectPath, OwnedValue}};
+use zbus::fdo::ObjectManagerProxy;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use futures::StreamExt;
+use syd_core::{wifi_security, ConnectStage, Metered, SavedNetwork, ConnectionState, ConnectionType, Connectivity, DeviceState, DeviceType, KeyMgmt, NetDevice, NetworkError, NetworkStatus, SecretKind, SecretRequest, WifiNet, WifiSettings};
+use super::{agent::Prompter, backend, links};
+use crate::services::dbus::{at, prop};
+
+pub const SERVICE: &str = "net.connman.iwd";
+const AGENT_PATH: &str = "/org/syd/IwdAgent";
+/// iwd only replies to Connect once the network is up, and that includes asking the agent
+/// for a passphrase, so leave time for the user to type it.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(180);
+
+#[proxy(interface = "net.connman.iwd.AgentManager", default_service = "net.connman.iwd", default_path = "/net/connman/iwd")]
+pub trait AgentManager {
+    fn register_agent(&self, path: &ObjectPath<'_>) -> Result<()>;
+}
+
+#[proxy(interface = "net.connman.iwd.Device", default_service = "net.connman.iwd")]
+pub trait Device {
+    #[zbus(property)] fn set_powered(&self, powered: bool) -> Result<()>;
+}
+
+#[proxy(interface = "net.connman.iwd.Station", default_service = "net.connman.iwd")]
+pub trait Station {
+    fn scan(&self) -> Result<()>;
+    /// Networks in range, strongest first, with their signal in 100 * dBm.
+    fn get_ordered_networks(&self) -> Result<Vec<(OwnedObjectPath, i16)>>;
+    fn connect_hidden_network(&self, name: &str) -> Result<()>;
+}
+
+#[proxy(interface = "net.connman.iwd.Network", default_service = "net.connman.iwd")]
+pub trait Network {
+    fn connect(&self) -> Result<()>;
+    #[zbus(property)] fn name(&self) -> Result<String>;
+}
+
+#[proxy(interface = "net.connman.iwd.KnownNetwork", default_service = "net.connman.iwd")]
+pub trait KnownNetwork {
+    fn forget(&self) -> Result<()>;
+    #[zbus(property)] fn set_auto_connect(&self, enabled: bool) -> Result<()>;
+}
+
+/// Every iwd object with the properties of each interface it implements.
+type Objects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+/// Objects implementing `iface`, with that interface's properties.
+fn having<'a>(objs: &'a Objects, iface: &'a str) -> impl Iterator<Item = (&'a OwnedObjectPath, &'a HashMap<String, OwnedValue>)> {
+    objs.iter().filter_map(move |(path, ifaces)| Some((path, ifaces.get(iface)?)))
+}
+
+fn props<'a>(objs: &'a Objects, path: &OwnedObjectPath, iface: &str) -> Option<&'a HashMap<String, OwnedValue>> {
+    objs.get(path)?.get(iface)
+}
+
+/// Secrets handed to `connect` up front, by SSID, for the agent to answer with instead of
+/// prompting. Keyed by name because a hidden network has no object until iwd finds it.
+type Presets = Arc<Mutex<HashMap<String, HashMap<String, String>>>>;
+
+#[derive(Clone)]
+pub struct Iwd { conn: Connection, presets: Presets }
+
+impl Iwd {
+    pub fn new(conn: Connection) -> Self { Self { conn, presets: Presets::default() } }
+
+    pub fn connection(&self) -> &Connection { &self.conn }
+
+    /// The agent that answers iwd's passphrase requests, sharing this backend's presets.
+    pub fn agent(&self, prompter: Prompter) -> IwdAgent {
+        IwdAgent { conn: self.conn.clone(), presets: self.presets.clone(), prompter }
+    }
+
+    async fn objects(&self) -> Result<Objects> {
+        let om = ObjectManagerProxy::builder(&self.conn).destination(SERVICE)?.path("/")?.build().await?;
+        Ok(om.get_managed_objects().await?.into_iter()
+            .map(|(path, ifaces)| (path, ifaces.into_iter().map(|(name, p)| (name.to_string(), p)).collect()))
+            .collect())
+    }
+
+    /// Signal strength per network in range, in percent.
+    async fn strengths(&self, objs: &Objects) -> HashMap<OwnedObjectPath, u8> {
+        let mut res = HashMap::new();
+        for (path, _) in having(objs, "net.connman.iwd.Station") {
+            let Ok(station) = at::<StationProxy>(&self.conn, path).await else { continue };
+            for (net, signal) in station.get_ordered_networks().await.unwrap_or_default() {
+                res.insert(net, backend::strength_from_dbm(signal as i32 / 100));
+            }
+        }
+        res
+    }
+
+    pub async fn request_scan(&self) -> Result<()> {
+        for (path, _) in having(&self.objects().await?, "net.connman.iwd.Station") {
+            let station: StationProxy = at(&self.conn, path).await?;
+            // iwd refuses while a scan is running; the result will arrive either way.
+            let _ = station.scan().await;
+        }
+        Ok(())
+    }
+
+    pub async fn set_wifi(&self, on: bool) -> Result<()> {
+        for (path, _) in having(&self.objects().await?, "net.connman.iwd.Device") {
+            let dev: DeviceProxy = at(&self.conn, path).await?;
+            dev.set_powered(on).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn access_points(&self) -> Result<Vec<WifiNet>> {
+        let objs = self.objects().await?;
+        let strengths = self.strengths(&objs).await;
+        let mut by_ssid: HashMap<String, WifiNet> = HashMap::new();
+        for (path, strength) in strengths {
+            let Some(p) = props(&objs, &path, "net.connman.iwd.Network") else { continue };
+            let (security_flags, security) = security_from(&prop::<String>(p, "Type").unwrap_or_default());
+            // iwd groups access points into networks and keeps BSS details to itself.
+            let net = WifiNet {
+                ssid: prop(p, "Name").unwrap_or_default(),
+                known: prop::<OwnedObjectPath>(p, "KnownNetwork").is_some(),
+                active: prop(p, "Connected").unwrap_or(false),
+                strength,
+                security,
+                security_flags,
+                ..Default::default()
+            };
+            if by_ssid.get(&net.ssid).is_some_and(|cur| cur.strength >= net.strength) { continue; }
+            by_ssid.insert(net.ssid.clone(), net);
+        }
+        let mut res: Vec<WifiNet> = by_ssid.into_values().collect();
+        res.sort_by(|a, b| {
+            if a.active != b.active { return b.active.cmp(&a.active); }
+            b.strength.cmp(&a.strength)
+        });
+        Ok(res)
+    }
+
+    /// Finds a network in range by name.
+    async fn find_network(&self, ssid: &str) -> Result<Option<(OwnedObjectPath, String)>> {
+        let objs = self.objects().await?;
+        let found = having(&objs, "net.connman.iwd.Network")
+            .find(|(_, p)| prop::<String>(p, "Name").as_deref() == Some(ssid))
+            .map(|(path, p)| (path.clone(), prop(p, "Type").unwrap_or_default()));
+        Ok(found)
+    }
+
+    async fn ensure_radio(&self) -> std::result::Result<(), NetworkError> {
+        let objs = self.objects().await?;
+        let mut devices = having(&objs, "net.connman.iwd.Device").peekable();
+        if devices.peek().is_none() { return Err(NetworkError::RadioDisabled("No Wi-Fi device".into())); }
+        if !devices.any(|(_, p)| prop(p, "Powered").unwrap_or(false)) {
+            return Err(NetworkError::RadioDisabled("Wi-Fi is turned off".into()));
+        }
+        Ok(())
+    }
+
+    /// Connects to `ssid`. Without `pass`, iwd uses the stored passphrase or asks the agent.
+    /// A new `pass` for a known network is ignored; forget it first to change it.
+    pub async fn connect(&self, ssid: &str, pass: &str, progress: mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+        self.ensure_radio().await?;
+        let Some((path, kind)) = self.find_network(ssid).await? else {
+            return Err(NetworkError::NotFound(format!("{} is not in range", ssid)));
+        };
+        let preset = (!pass.is_empty()).then(|| HashMap::from([("psk".to_string(), pass.to_string())]));
+        self.activate(ssid, &path, &kind, preset, progress).await
+    }
+
+    /// iwd takes EAP settings only from provisioning files in `/var/lib/iwd`, so for
+    /// enterprise networks only the identity and password are passed on, to networks that
+    /// have one.
+    pub async fn connect_advanced(&self, w: &WifiSettings, progress: mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+        let eap_settings = [&w.anonymous_identity, &w.ca_cert, &w.phase2_auth, &w.client_cert, &w.private_key];
+        if w.key_mgmt == KeyMgmt::Enterprise && eap_settings.iter().any(|s| !s.is_empty()) {
+            return Err(NetworkError::Failed("802.1X is not supported with iwd; provision the network in /var/lib/iwd".into()));
+        }
+        self.ensure_radio().await?;
+        let mut preset = HashMap::new();
+        match w.key_mgmt {
+            KeyMgmt::Open => {}
+            KeyMgmt::Wpa2 | KeyMgmt::Wpa3Sae => { preset.insert("psk".to_string(), w.password.clone()); }
+            KeyMgmt::Enterprise => {
+                preset.insert("identity".to_string(), w.identity.clone());
+                preset.insert("password".to_string(), w.password.clone());
+                preset.insert("private-key-password".to_string(), w.password.clone());
+            }
+        }
+        preset.retain(|_, v| !v.is_empty());
+        let preset = (!preset.is_empty()).then_some(preset);
+
+        match self.find_network(&w.ssid).await? {
+            Some((path, kind)) => self.activate(&w.ssid, &path, &kind, preset, progress).await,
+            None if w.hidden => {
+                let objs = self.objects().await?;
+                let Some((station, _)) = having(&objs, "net.connman.iwd.Station").next() else {
+                    return Err(NetworkError::RadioDisabled("No Wi-Fi device".into()));
+                };
+                let station: StationProxy = at(&self.conn, station).await?;
+                if let Some(p) = preset { self.presets.lock().unwrap().insert(w.ssid.clone(), p); }
+                let _ = progress.send(ConnectStage::Preparing);
+                let _ = progress.send(ConnectStage::Associating);
+                let res = timeout(CONNECT_TIMEOUT, station.connect_hidden_network(&w.ssid)).await;
+                self.presets.lock().unwrap().remove(&w.ssid);
+                let kind = if w.key_mgmt == KeyMgmt::Open { "open" } else { "psk" };
+                finish(&w.ssid, kind, res, &progress)
+            }
+            None => Err(NetworkError::NotFound(format!("{} is not in range", w.ssid))),
+        }
+    }
+
+    async fn activate(&self, ssid: &str, path: &OwnedObjectPath, kind: &str, preset: Option<HashMap<String, String>>, progress: mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+        let network: NetworkProxy = at(&self.conn, path).await?;
+        if let Some(p) = preset { self.presets.lock().unwrap().insert(ssid.to_string(), p); }
+        let _ = progress.send(ConnectStage::Preparing);
+        let _ = progress.send(ConnectStage::Associating);
+        let res = timeout(CONNECT_TIMEOUT, network.connect()).await;
+        self.presets.lock().unwrap().remove(ssid);
+        finish(ssid, kind, res, &progress)
+    }
+
+    pub async fn forget(&self, ssid: &str) -> Result<()> {
+        let objs = self.objects().await?;
+        for (path, p) in having(&objs, "net.connman.iwd.KnownNetwork") {
+            if prop::<String>(p, "Name").as_deref() != Some(ssid) { continue; }
+            let known: KnownNetworkProxy = at(&self.conn, path).await?;
+            known.forget().await?;
+        }
+        Ok(())
+    }
+
+    /// Known networks, most recently used first. iwd has no priorities or metered flags.
+    pub async fn saved_networks(&self) -> Result<Vec<SavedNetwork>> {
+        let objs = self.objects().await?;
+        let mut res: Vec<SavedNetwork> = having(&objs, "net.connman.iwd.KnownNetwork")
+            .map(|(_, p)| {
+                let name: String = prop(p, "Name").unwrap_or_default();
+                SavedNetwork {
+                    id: name.clone(),
+                    kind: ConnectionType::Wifi,
+                    ssid: name,
+                    autoconnect: prop(p, "AutoConnect").unwrap_or(true),
+                    metered: Metered::Auto,
+                    last_used: prop::<String>(p, "LastConnectedTime")
+                        .and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok())
+                        .map_or(0, |t| t.timestamp().max(0) as u64),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        res.sort_by_key(|n| std::cmp::Reverse(n.last_used));
+        Ok(res)
+    }
+
+    pub async fn set_autoconnect(&self, ssid: &str, enabled: bool) -> std::result::Result<(), NetworkError> {
+        let objs = self.objects().await?;
+        let Some((path, _)) = having(&objs, "net.connman.iwd.KnownNetwork").find(|(_, p)| prop::<String>(p, "Name").as_deref() == Some(ssid)) else {
+            return Err(NetworkError::NotFound(format!("No saved network named {}", ssid)));
+        };
+        let known: KnownNetworkProxy = at(&self.conn, path).await?;
+        known.set_auto_connect(enabled).await?;
+        Ok(())
+    }
+
+    pub async fn status(&self) -> Result<NetworkStatus> {
+        let objs = self.objects().await?;
+        let links = links::links().await;
+        let route = links::default_route().await;
+        let mut st = NetworkStatus::default();
+
+        // A station's object path is its device's, where the interface name lives.
+        for (path, p) in having(&objs, "net.connman.iwd.Station") {
+            let state: String = prop(p, "State").unwrap_or_default();
+            let Some(net) = prop::<OwnedObjectPath>(p, "ConnectedNetwork") else {
+                if state == "connecting" { st.state = ConnectionState::Connecting; }
+                continue;
+            };
+            st.connection_type = ConnectionType::Wifi;
+            st.interface = props(&objs, path, "net.connman.iwd.Device").and_then(|d| prop(d, "Name")).unwrap_or_default();
+            st.ssid = props(&objs, &net, "net.connman.iwd.Network").and_then(|n| prop(n, "Name")).unwrap_or_default();
+            st.strength = self.strengths(&objs).await.get(&net).copied().unwrap_or(0);
+            st.state = if state == "connecting" { ConnectionState::Connecting } else { ConnectionState::Limited };
+            break;
+        }
+        // iwd only does Wi-Fi; a routed wired link still counts as being online.
+        if st.connection_type == ConnectionType::None {
+            if let Some(l) = links.iter().find(|l| l.device_type == DeviceType::Ethernet && route.as_ref().is_some_and(|(_, dev)| *dev == l.name)) {
+                st.connection_type = ConnectionType::Ethernet;
+                st.interface = l.name.clone();
+                st.ssid = l.name.clone();
+                st.state = ConnectionState::Limited;
+            }
+        }
+        if st.connection_type == ConnectionType::None { return Ok(st); }
+
+        let routed = route.as_ref().is_some_and(|(_, dev)| *dev == st.interface);
+        if routed && st.state == ConnectionState::Limited {
+            st.state = ConnectionState::Connected;
+        }
+        st.connectivity = if routed { Connectivity::Full } else { Connectivity::None };
+        links::describe(&mut st, &links, route);
+        Ok(st)
+    }
+
+    pub async fn devices(&self) -> Result<Vec<NetDevice>> {
+        let objs = self.objects().await?;
+        let mut wifi: HashMap<String, (DeviceState, String)> = HashMap::new();
+        for (path, d) in having(&objs, "net.connman.iwd.Device") {
+            let name: String = prop(d, "Name").unwrap_or_default();
+            let station = props(&objs, path, "net.connman.iwd.Station");
+            let state = match station.and_then(|s| prop::<String>(s, "State")).as_deref() {
+                _ if !prop(d, "Powered").unwrap_or(false) => DeviceState::Unavailable,
+                Some("connected" | "roaming") => DeviceState::Connected,
+                Some("connecting") => DeviceState::Connecting,
+                Some("disconnecting") => DeviceState::Disconnecting,
+                Some(_) => DeviceState::Disconnected,
+                // In AP or ad-hoc mode, which syd does not manage through iwd.
+                None => DeviceState::Unmanaged,
+            };
+            let connection = station
+                .and_then(|s| prop::<OwnedObjectPath>(s, "ConnectedNetwork"))
+                .and_then(|n| props(&objs, &n, "net.connman.iwd.Network").and_then(|n| prop(n, "Name")))
+                .unwrap_or_default();
+            wifi.insert(name, (state, connection));
+        }
+        Ok(links::links().await.iter()
+            .map(|l| {
+                let mut dev = l.device();
+                if let Some((state, connection)) = wifi.get(&l.name) {
+                    dev.state = *state;
+                    dev.connection = connection.clone();
+                }
+                dev
+            })
+            .collect())
+    }
+}
+
+/// Maps iwd's network types to syd's security bits and label.
+fn security_from(kind: &str) -> (u32, String) {
+    match kind {
+        "wep" => (wifi_security::WEP, "WEP".into()),
+        "psk" => (wifi_security::WPA2, "WPA2".into()),
+        "8021x" => (wifi_security::WPA2 | wifi_security::ENTERPRISE, "WPA2 802.1X".into()),
+        _ => (0, String::new()),
+    }
+}
+
+fn finish(ssid: &str, kind: &str, res: std::result::Result<Result<()>, tokio::time::error::Elapsed>, progress: &mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+    let res = match res {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(connect_error(e, ssid, kind)),
+        Err(_) => Err(NetworkError::Timeout(format!("{} did not respond", ssid))),
+    };
+    let _ = progress.send(if res.is_ok() { ConnectStage::Connected } else { ConnectStage::Failed });
+    res
+}
+
+fn connect_error(e: zbus::Error, ssid: &str, kind: &str) -> NetworkError {
+    let zbus::Error::MethodError(name, _, _) = &e else { return e.into() };
+    match name.as_str() {
+        "net.connman.iwd.Aborted" => NetworkError::Cancelled(format!("No password was given for {}", ssid)),
+        "net.connman.iwd.InvalidFormat" => NetworkError::WrongPassword(format!("That is not a valid password for {}", ssid)),
+        // iwd does not tell a rejected handshake apart from other failures.
+        "net.connman.iwd.Failed" if kind != "open" => NetworkError::WrongPassword(format!("Authentication with {} failed", ssid)),
+        "net.connman.iwd.NotFound" => NetworkError::NotFound(format!("{} is not in range", ssid)),
+        "net.connman.iwd.NotConfigured" => NetworkError::Failed(format!("{} needs a provisioning file in /var/lib/iwd", ssid)),
+        "net.connman.iwd.NoAgent" => NetworkError::Failed("No agent registered with iwd".into()),
+        _ => NetworkError::Failed(format!("Connection to {} failed: {}", ssid, e)),
+    }
+}
+
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "net.connman.iwd.Agent.Error")]
+pub enum IwdAgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    Canceled(String),
+}
+
+/// iwd's counterpart to NetworkManager's secret agent. Requests go to the shell as
+/// `SecretsRequested`, unless `connect` already brought the answer.
+pub struct IwdAgent { conn: Connection, presets: Presets, prompter: Prompter }
+
+impl IwdAgent {
+    async fn ask(&self, network: OwnedObjectPath, kind: SecretKind, fields: &[&str]) -> std::result::Result<HashMap<String, String>, IwdAgentError> {
+        let name = match at::<NetworkProxy>(&self.conn, &network).await {
+            Ok(n) => n.name().await.unwrap_or_default(),
+            Err(_) => String::new(),
+        };
+        let preset = self.presets.lock().unwrap().get(&name).cloned();
+        if let Some(p) = preset.filter(|p| fields.iter().all(|f| p.contains_key(*f))) {
+            return Ok(p);
+        }
+
+        let request = SecretRequest {
+            id: 0,
+            kind,
+            connection: name.clone(),
+            ssid: name,
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+            retry: false,
+        };
+        let setting = if kind == SecretKind::Enterprise { "802-1x" } else { "802-11-wireless-security" };
+        self.prompter.ask(request, network, setting).await.ok_or_else(|| IwdAgentError::Canceled("Request cancelled".into()))
+    }
+}
+
+#[interface(name = "net.connman.iwd.Agent")]
+impl IwdAgent {
+    async fn release(&self) {}
+
+    async fn request_passphrase(&self, network: OwnedObjectPath) -> std::result::Result<String, IwdAgentError> {
+        Ok(self.ask(network, SecretKind::Wifi, &["psk"]).await?.remove("psk").unwrap_or_default())
+    }
+
+    async fn request_private_key_passphrase(&self, network: OwnedObjectPath) -> std::result::Result<String, IwdAgentError> {
+        Ok(self.ask(network, SecretKind::Enterprise, &["private-key-password"]).await?.remove("private-key-password").unwrap_or_default())
+    }
+
+    async fn request_user_name_and_password(&self, network: OwnedObjectPath) -> std::result::Result<(String, String), IwdAgentError> {
+        let mut s = self.ask(network, SecretKind::Enterprise, &["identity", "password"]).await?;
+        Ok((s.remove("identity").unwrap_or_default(), s.remove("password").unwrap_or_default()))
+    }
+
+    async fn request_user_password(&self, network: OwnedObjectPath, _user: String) -> std::result::Result<String, IwdAgentError> {
+        Ok(self.ask(network, SecretKind::Enterprise, &["password"]).await?.remove("password").unwrap_or_default())
+    }
+
+    /// iwd has at most one request out at a time, so this withdraws whatever is pending.
+    async fn cancel(&self, _reason: String) { self.prompter.withdraw(None, ""); }
+}
+
+async fn register(sys: &Connection) {
+    if let Ok(m) = AgentManagerProxy::new(sys).await {
+        if let Err(e) = m.register_agent(&ObjectPath::from_static_str_unchecked(AGENT_PATH)).await {
+            eprintln!("iwd agent registration failed: {}", e);
+        }
+    }
+}
+
+pub async fn run(sys: Connection, agent: IwdAgent) {
+    if sys.object_server().at(AGENT_PATH, agent).await.is_err() { return; }
+    let Ok(dbus) = zbus::fdo::DBusProxy::new(&sys).await else { return };
+    let Ok(mut owners) = dbus.receive_name_owner_changed_with_args(&[(0, SERVICE)]).await else { return };
+
+    register(&sys).await;
+    // iwd forgets its agent when it restarts.
+    while let Some(sig) = owners.next().await {
+        if sig.args().map(|a| a.new_owner().is_some()).unwrap_or(false) { register(&sys).await; }
+    }
+}
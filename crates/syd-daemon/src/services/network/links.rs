@@ -0,0 +1,123 @@
+use std::path::Path;
+use serde_json::Value;
+use tokio::process::Command;
+use syd_core::{DeviceState, DeviceType, NetDevice, NetworkStatus};
+
+/// One interface as `ip` sees it.
+pub struct Link {
+    pub name: String,
+    pub device_type: DeviceType,
+    pub mac: String,
+    /// `operstate`, e.g. `UP`, `DOWN` or `DORMANT`.
+    pub operstate: String,
+    pub carrier: bool,
+    pub ipv4: Vec<String>,
+    pub ipv6: Vec<String>,
+}
+
+impl Link {
+    /// A `NetDevice` with the state guessed from the kernel's view alone. Backends
+    /// overwrite `state` and `connection` with what they know.
+    pub fn device(&self) -> NetDevice {
+        NetDevice {
+            interface: self.name.clone(),
+            device_type: self.device_type,
+            state: match self.operstate.as_str() {
+                _ if self.device_type == DeviceType::Loopback => DeviceState::Unmanaged,
+                "UP" | "UNKNOWN" if self.carrier => DeviceState::Connected,
+                "DORMANT" => DeviceState::Disconnected,
+                _ => DeviceState::Unavailable,
+            },
+            mac: self.mac.clone(),
+            carrier: self.carrier,
+            speed: speed(&self.name),
+            ipv4: self.ipv4.clone(),
+            ipv6: self.ipv6.clone(),
+            connection: String::new(),
+        }
+    }
+}
+
+async fn ip_json(args: &[&str]) -> Vec<Value> {
+    let Ok(out) = Command::new("ip").arg("-j").args(args).output().await else { return Vec::new() };
+    serde_json::from_slice(&out.stdout).unwrap_or_default()
+}
+
+/// Every interface with its addresses. NetworkManager reports these itself; the other
+/// backends leave them to the kernel, so ask `ip`.
+pub async fn links() -> Vec<Link> {
+    ip_json(&["-d", "addr", "show"]).await.iter().filter_map(link_from).collect()
+}
+
+fn link_from(v: &Value) -> Option<Link> {
+    let name = v["ifname"].as_str()?.to_string();
+    let flags: Vec<&str> = v["flags"].as_array().map(|f| f.iter().filter_map(Value::as_str).collect()).unwrap_or_default();
+    let addrs = |family: &str| -> Vec<String> {
+        v["addr_info"].as_array().into_iter().flatten()
+            .filter(|a| a["family"] == family && a["scope"] != "host")
+            .filter_map(|a| Some(format!("{}/{}", a["local"].as_str()?, a["prefixlen"].as_u64()?)))
+            .collect()
+    };
+    Some(Link {
+        device_type: device_type(&name, v["link_type"].as_str().unwrap_or(""), v["linkinfo"]["info_kind"].as_str().unwrap_or("")),
+        mac: v["address"].as_str().unwrap_or("").to_string(),
+        operstate: v["operstate"].as_str().unwrap_or("").to_string(),
+        carrier: flags.contains(&"LOWER_UP"),
+        ipv4: addrs("inet"),
+        ipv6: addrs("inet6"),
+        name,
+    })
+}
+
+fn device_type(name: &str, link_type: &str, kind: &str) -> DeviceType {
+    let sys = Path::new("/sys/class/net").join(name);
+    match (link_type, kind) {
+        ("loopback", _) => DeviceType::Loopback,
+        _ if sys.join("wireless").exists() || sys.join("phy80211").exists() => DeviceType::Wifi,
+        (_, "bridge") => DeviceType::Bridge,
+        (_, "tun") => DeviceType::Tun,
+        (_, "wireguard") => DeviceType::Wireguard,
+        ("ether", "") if sys.join("device").exists() => DeviceType::Ethernet,
+        _ => DeviceType::Other,
+    }
+}
+
+/// Link speed in Mb/s from sysfs, 0 when the driver does not say.
+fn speed(iface: &str) -> u32 {
+    std::fs::read_to_string(Path::new("/sys/class/net").join(iface).join("speed"))
+        .ok()
+        .and_then(|s| s.trim().parse::<i64>().ok())
+        .filter(|s| *s > 0)
+        .map_or(0, |s| s as u32)
+}
+
+/// The default IPv4 route, falling back to IPv6: (gateway, interface).
+pub async fn default_route() -> Option<(String, String)> {
+    for args in [&["route", "show", "default"][..], &["-6", "route", "show", "default"][..]] {
+        if let Some(r) = ip_json(args).await.first() {
+            return Some((r["gateway"].as_str().unwrap_or("").to_string(), r["dev"].as_str()?.to_string()));
+        }
+    }
+    None
+}
+
+/// Nameservers from resolv.conf. With systemd-resolved this is its stub, which is still
+/// the address programs actually ask.
+fn nameservers() -> Vec<String> {
+    std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default().lines()
+        .filter_map(|l| l.strip_prefix("nameserver"))
+        .map(|ns| ns.trim().to_string())
+        .collect()
+}
+
+/// Fills in the addresses, gateway and DNS servers of `st.interface`.
+pub fn describe(st: &mut NetworkStatus, links: &[Link], route: Option<(String, String)>) {
+    if let Some(l) = links.iter().find(|l| l.name == st.interface) {
+        st.ipv4 = l.ipv4.clone();
+        st.ipv6 = l.ipv6.clone();
+    }
+    if let Some((gateway, dev)) = route {
+        if dev == st.interface { st.gateway = gateway; }
+    }
+    st.dns = nameservers();
+}
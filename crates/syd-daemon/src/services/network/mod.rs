@@ -0,0 +1,304 @@
+pub mod nm;
+pub mod iwd;
+pub mod networkd;
+pub mod backend;
+pub mod links;
+pub mod agent;
+pub mod portal;
+pub mod share;
+pub mod traffic;
+pub mod modem;
+
+use zbus::{interface, object_server::SignalContext, Connection, MatchRule, MessageStream};
+use tokio::time::{interval, timeout, Duration};
+use syd_core::{ConnectStage, Connectivity, DataUsage, HotspotStatus, Metered, Modem, NetDevice, NetworkError, NetworkStatus, SavedNetwork, SecretRequest, SharedNetwork, TrafficRate, VpnProfile, VpnState, WifiBand, WifiNet, WifiSettings};
+use std::future::Future;
+use tokio::sync::mpsc;
+use std::collections::HashMap;
+use futures::{stream, Stream, StreamExt};
+use std::pin::Pin;
+
+pub struct NetworkService { backend: backend::Backend, modems: modem::Modems, secrets: agent::PendingSecrets, traffic: traffic::Traffic, check_uri: String }
+impl NetworkService {
+    /// `check_uri` overrides the URL probed for captive portals; empty uses the backend's.
+    pub fn new(backend: backend::Backend, modems: modem::Modems, secrets: agent::PendingSecrets, check_uri: String) -> (Self, traffic::Traffic) {
+        let traffic = traffic::Traffic::default();
+        (Self { backend, modems, secrets, traffic: traffic.clone(), check_uri }, traffic)
+    }
+
+    /// Runs a connection attempt, relaying its stages as `ConnectProgress` until it finishes.
+    async fn relay_progress(
+        &self,
+        ctxt: &SignalContext<'_>,
+        ssid: &str,
+        attempt: impl Future<Output = Result<(), NetworkError>>,
+        mut rx: mpsc::UnboundedReceiver<ConnectStage>,
+    ) -> Result<(), NetworkError> {
+        let forward = async {
+            while let Some(stage) = rx.recv().await {
+                let _ = self.connect_progress(ctxt, ssid.to_string(), stage).await;
+            }
+        };
+        let (res, _) = tokio::join!(attempt, forward);
+        res
+    }
+}
+
+#[interface(name = "org.syd.Network")]
+impl NetworkService {
+    async fn get_state(&self) -> NetworkStatus { self.backend.status().await.unwrap_or_default() }
+    
+    async fn set_wifi(&self, e: bool) { self.backend.set_wifi(e).await; }
+    
+    async fn scan(&self) -> Vec<WifiNet> { self.backend.access_points().await.unwrap_or_default() }
+
+    /// Starts a rescan in the background; results arrive through `AccessPointsChanged`.
+    async fn request_scan(&self) { let _ = self.backend.request_scan().await; }
+
+    /// Returns once the connection is up, or with the reason it failed. `ConnectProgress`
+    /// reports the stages in between.
+    async fn connect(&self, ssid: String, pass: String, #[zbus(signal_context)] ctxt: SignalContext<'_>) -> Result<(), NetworkError> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.relay_progress(&ctxt, &ssid, self.backend.connect(&ssid, &pass, tx), rx).await
+    }
+
+    async fn connect_advanced(&self, settings: WifiSettings, #[zbus(signal_context)] ctxt: SignalContext<'_>) -> Result<(), NetworkError> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.relay_progress(&ctxt, &settings.ssid, self.backend.connect_advanced(&settings, tx), rx).await
+    }
+
+    async fn forget(&self, ssid: String) { self.backend.forget(&ssid).await; }
+
+    /// Saved Wi-Fi and wired profiles, most recently used first.
+    async fn list_saved_networks(&self) -> Vec<SavedNetwork> { self.backend.saved_networks().await.unwrap_or_default() }
+
+    /// `id` is a profile name or UUID, for this and the methods below.
+    async fn set_autoconnect(&self, id: String, enabled: bool) -> Result<(), NetworkError> { self.backend.set_autoconnect(&id, enabled).await }
+
+    async fn set_priority(&self, id: String, priority: i32) -> Result<(), NetworkError> { self.backend.set_priority(&id, priority).await }
+
+    async fn set_metered(&self, id: String, metered: Metered) -> Result<(), NetworkError> { self.backend.set_metered(&id, metered).await }
+
+    async fn rename_network(&self, id: String, name: String) -> Result<(), NetworkError> { self.backend.rename_network(&id, &name).await }
+
+    async fn provide_secrets(&self, id: u32, secrets: HashMap<String, String>) -> zbus::fdo::Result<()> {
+        if self.secrets.respond(id, Some(secrets)) { Ok(()) } else { Err(zbus::fdo::Error::InvalidArgs(format!("No secret request {}", id))) }
+    }
+
+    async fn cancel_secrets(&self, id: u32) -> zbus::fdo::Result<()> {
+        if self.secrets.respond(id, None) { Ok(()) } else { Err(zbus::fdo::Error::InvalidArgs(format!("No secret request {}", id))) }
+    }
+
+    /// Every network interface, managed or not.
+    async fn get_devices(&self) -> Vec<NetDevice> { self.backend.devices().await.unwrap_or_default() }
+
+    async fn list_vpns(&self) -> Vec<VpnProfile> {
+        match self.backend.nm() {
+            Ok(nm) => nm.vpns().await.unwrap_or_default(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Takes a profile name or UUID. Returns once NetworkManager has accepted the request;
+    /// `VpnStateChanged` follows the activation from there.
+    async fn activate_vpn(&self, id: String) -> Result<(), NetworkError> { self.backend.nm()?.activate_vpn(&id).await }
+
+    async fn deactivate_vpn(&self, id: String) -> Result<(), NetworkError> { self.backend.nm()?.deactivate_vpn(&id).await }
+
+    async fn get_hotspot(&self) -> HotspotStatus {
+        match self.backend.nm() {
+            Ok(nm) => nm.hotspot().await.unwrap_or_default(),
+            Err(_) => HotspotStatus::default(),
+        }
+    }
+
+    /// `band` may be `Unknown` to let the driver choose.
+    async fn start_hotspot(&self, ssid: String, password: String, band: WifiBand) -> Result<(), NetworkError> {
+        self.backend.nm()?.start_hotspot(&ssid, &password, band).await
+    }
+
+    async fn stop_hotspot(&self) -> Result<(), NetworkError> { Ok(self.backend.nm()?.stop_hotspot().await?) }
+
+    /// Returns the `WIFI:` URI and QR code for a saved network. An empty `ssid` shares the
+    /// running hotspot, or else the network currently in use.
+    async fn share_network(&self, ssid: String) -> Result<SharedNetwork, NetworkError> {
+        share::share(&self.backend.nm()?.credentials(&ssid).await?)
+    }
+
+    /// Sends `TrafficChanged` every `interval_ms` milliseconds; 0 stops it. The interval is
+    /// shared by all clients.
+    async fn set_traffic_interval(&self, interval_ms: u32) { self.traffic.set_interval(interval_ms); }
+
+    async fn get_data_usage(&self) -> DataUsage { self.traffic.usage() }
+
+    /// Mobile broadband modems known to ModemManager.
+    async fn list_modems(&self) -> Vec<Modem> { self.modems.list().await }
+
+    /// `modem` is the path from `list_modems`.
+    async fn set_mobile_data(&self, modem: String, enabled: bool) -> Result<(), NetworkError> { self.modems.set_data(&modem, enabled).await }
+
+    async fn unlock_modem(&self, modem: String, pin: String) -> Result<(), NetworkError> { self.modems.unlock(&modem, &pin).await }
+
+    /// Re-runs the connectivity check. A portal is announced through `CaptivePortal` again,
+    /// even if it was already known.
+    async fn check_connectivity(&self, #[zbus(signal_context)] ctxt: SignalContext<'_>) -> zbus::fdo::Result<Connectivity> {
+        let c = self.backend.check_connectivity().await?;
+        if c == Connectivity::Portal {
+            let _ = self.captive_portal(&ctxt, portal_url(&self.backend, &self.check_uri).await).await;
+        }
+        Ok(c)
+    }
+
+    #[zbus(signal)] async fn state_changed(&self, ctxt: &SignalContext<'_>, status: NetworkStatus) -> zbus::Result<()>;
+    #[zbus(signal)] async fn access_points_changed(&self, ctxt: &SignalContext<'_>, networks: Vec<WifiNet>) -> zbus::Result<()>;
+    #[zbus(signal)] async fn secrets_requested(&self, ctxt: &SignalContext<'_>, request: SecretRequest) -> zbus::Result<()>;
+    #[zbus(signal)] async fn secrets_cancelled(&self, ctxt: &SignalContext<'_>, id: u32) -> zbus::Result<()>;
+    #[zbus(signal)] async fn connect_progress(&self, ctxt: &SignalContext<'_>, ssid: String, stage: ConnectStage) -> zbus::Result<()>;
+    #[zbus(signal)] async fn vpn_state_changed(&self, ctxt: &SignalContext<'_>, profile: VpnProfile) -> zbus::Result<()>;
+    /// Sent for new devices as well as for changes to known ones.
+    #[zbus(signal)] async fn device_changed(&self, ctxt: &SignalContext<'_>, device: NetDevice) -> zbus::Result<()>;
+    #[zbus(signal)] async fn device_removed(&self, ctxt: &SignalContext<'_>, interface: String) -> zbus::Result<()>;
+    #[zbus(signal)] async fn hotspot_changed(&self, ctxt: &SignalContext<'_>, status: HotspotStatus) -> zbus::Result<()>;
+    #[zbus(signal)] async fn traffic_changed(&self, ctxt: &SignalContext<'_>, rates: Vec<TrafficRate>) -> zbus::Result<()>;
+    /// Sent once a month, when metered traffic passes the configured threshold.
+    #[zbus(signal)] async fn usage_warning(&self, ctxt: &SignalContext<'_>, usage: DataUsage) -> zbus::Result<()>;
+    /// Sent when a captive portal is detected, with the page to sign in on.
+    #[zbus(signal)] async fn captive_portal(&self, ctxt: &SignalContext<'_>, url: String) -> zbus::Result<()>;
+    /// Sent for new modems as well as for changes to known ones.
+    #[zbus(signal)] async fn modem_changed(&self, ctxt: &SignalContext<'_>, modem: Modem) -> zbus::Result<()>;
+    #[zbus(signal)] async fn modem_removed(&self, ctxt: &SignalContext<'_>, path: String) -> zbus::Result<()>;
+}
+
+/// Works out a captive portal's sign-in page by probing `check_uri`, or the backend's check URL.
+async fn portal_url(backend: &backend::Backend, check_uri: &str) -> String {
+    let uri = match check_uri {
+        "" => backend.check_uri().await,
+        uri => uri.to_string(),
+    };
+    portal::sign_in_url(if uri.is_empty() { portal::DEFAULT_CHECK_URI } else { &uri }).await
+}
+
+pub async fn monitor(conn: Connection, backend: backend::Backend, check_uri: String) {
+    let Ok(nm) = backend.nm() else { return };
+    let iface = conn.object_server().interface::<_, NetworkService>("/org/syd/Network").await.unwrap();
+    let Ok(manager) = nm.manager().await else { return };
+    let mut state = manager.receive_state_changed().await;
+    let mut connectivity = manager.receive_connectivity_changed().await;
+    let mut primary = manager.receive_primary_connection_changed().await;
+    // Signal strength and addresses change without touching the manager object, so poll those.
+    let mut tick = interval(Duration::from_secs(10));
+    let mut last = None;
+    loop {
+        tokio::select! {
+            Some(_) = state.next() => {},
+            Some(_) = connectivity.next() => {},
+            Some(_) = primary.next() => {},
+            _ = tick.tick() => {},
+        }
+        let curr = nm.status().await.unwrap_or_default();
+        if last.as_ref() == Some(&curr) { continue; }
+        let _ = NetworkService::state_changed(&*iface.get().await, iface.signal_context(), curr.clone()).await;
+        let was_portal = last.is_some_and(|l| l.connectivity == Connectivity::Portal);
+        if curr.connectivity == Connectivity::Portal && !was_portal {
+            let url = portal_url(&backend, &check_uri).await;
+            let _ = NetworkService::captive_portal(&*iface.get().await, iface.signal_context(), url).await;
+        }
+        last = Some(curr);
+    }
+}
+
+pub async fn monitor_access_points(conn: Connection, nm: nm::Nm) {
+    let iface = conn.object_server().interface::<_, NetworkService>("/org/syd/Network").await.unwrap();
+    let mut events: Vec<Pin<Box<dyn Stream<Item = ()> + Send>>> = Vec::new();
+    for path in nm.wifi_devices().await.unwrap_or_default() {
+        let Ok(b) = nm::WirelessProxy::builder(nm.connection()).path(path) else { continue };
+        let Ok(wifi) = b.build().await else { continue };
+        if let Ok(s) = wifi.receive_access_point_added().await { events.push(Box::pin(s.map(|_| ()))); }
+        if let Ok(s) = wifi.receive_access_point_removed().await { events.push(Box::pin(s.map(|_| ()))); }
+        events.push(Box::pin(wifi.receive_last_scan_changed().await.map(|_| ())));
+    }
+    let mut events = stream::select_all(events);
+
+    while events.next().await.is_some() {
+        // A scan adds and removes access points one by one, so wait for the burst to settle.
+        while let Ok(Some(_)) = timeout(Duration::from_millis(500), events.next()).await {}
+        let nets = nm.access_points().await.unwrap_or_default();
+        let _ = NetworkService::access_points_changed(&*iface.get().await, iface.signal_context(), nets).await;
+    }
+}
+
+/// Follows active connections to report VPN and hotspot state.
+pub async fn monitor_active(conn: Connection, nm: nm::Nm) {
+    let iface = conn.object_server().interface::<_, NetworkService>("/org/syd/Network").await.unwrap();
+    // Active connection objects come and go, so match StateChanged on all of them at once.
+    let Ok(rule) = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .sender("org.freedesktop.NetworkManager")
+        .and_then(|b| b.interface("org.freedesktop.NetworkManager.Connection.Active"))
+        .and_then(|b| b.member("StateChanged"))
+        .map(|b| b.build()) else { return };
+    let Ok(mut changes) = MessageStream::for_match_rule(rule, nm.connection(), None).await else { return };
+
+    let mut last: HashMap<String, VpnState> = nm.vpns().await.unwrap_or_default().into_iter().map(|p| (p.uuid, p.state)).collect();
+    let mut active = nm.active_vpns().await.unwrap_or_default();
+    let mut hotspot = nm.hotspot().await.unwrap_or_default();
+    while let Some(Ok(msg)) = changes.next().await {
+        // A failed connection is already gone by the time we list profiles again,
+        // so work out which one it was from the previous snapshot.
+        let failed = match (msg.header().path(), msg.body().deserialize::<(u32, u32)>()) {
+            (Some(path), Ok((nm::ACTIVE_STATE_DEACTIVATED, reason))) if nm::vpn_failed(reason) => {
+                active.iter().find(|(_, (p, _))| p.as_str() == path.as_str()).map(|(uuid, _)| uuid.clone())
+            }
+            _ => None,
+        };
+
+        for mut profile in nm.vpns().await.unwrap_or_default() {
+            if failed.as_ref() == Some(&profile.uuid) { profile.state = VpnState::Failed; }
+            if last.get(&profile.uuid) == Some(&profile.state) { continue; }
+            last.insert(profile.uuid.clone(), profile.state);
+            let _ = NetworkService::vpn_state_changed(&*iface.get().await, iface.signal_context(), profile).await;
+        }
+        active = nm.active_vpns().await.unwrap_or_default();
+
+        let curr = nm.hotspot().await.unwrap_or_default();
+        if curr != hotspot {
+            hotspot = curr.clone();
+            let _ = NetworkService::hotspot_changed(&*iface.get().await, iface.signal_context(), curr).await;
+        }
+    }
+}
+
+pub async fn monitor_devices(conn: Connection, nm: nm::Nm) {
+    let iface = conn.object_server().interface::<_, NetworkService>("/org/syd/Network").await.unwrap();
+    let Ok(manager) = nm.manager().await else { return };
+    let (Ok(mut added), Ok(mut removed)) = (manager.receive_device_added().await, manager.receive_device_removed().await) else { return };
+    // Watch property changes on every device interface (Device, Wired, Wireless, ...) in one match.
+    let Ok(rule) = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .sender("org.freedesktop.NetworkManager")
+        .and_then(|b| b.interface("org.freedesktop.DBus.Properties"))
+        .and_then(|b| b.member("PropertiesChanged"))
+        .and_then(|b| b.path_namespace("/org/freedesktop/NetworkManager/Devices"))
+        .map(|b| b.build()) else { return };
+    let Ok(mut changes) = MessageStream::for_match_rule(rule, nm.connection(), None).await else { return };
+
+    let mut known: HashMap<_, NetDevice> = nm.devices().await.unwrap_or_default().into_iter().collect();
+    loop {
+        let path = tokio::select! {
+            Some(sig) = added.next() => match sig.args() { Ok(a) => a.device_path, Err(_) => continue },
+            Some(sig) = removed.next() => {
+                let Ok(a) = sig.args() else { continue };
+                if let Some(dev) = known.remove(&a.device_path) {
+                    let _ = NetworkService::device_removed(&*iface.get().await, iface.signal_context(), dev.interface).await;
+                }
+                continue;
+            }
+            Some(Ok(msg)) = changes.next() => match msg.header().path() { Some(p) => p.to_owned().into(), None => continue },
+            else => break,
+        };
+        let Ok(dev) = nm.device(&path).await else { continue };
+        if known.get(&path) == Some(&dev) { continue; }
+        known.insert(path, dev.clone());
+        let _ = NetworkService::device_changed(&*iface.get().await, iface.signal_context(), dev).await;
+    }
+}
@@ -0,0 +1,435 @@
+use zbus::{proxy, Connection, MatchRule, MessageStream, Result, zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value}};
+use zbus::fdo::ObjectManagerProxy;
+use std::collections::HashMap;
+use tokio::time::{timeout, Duration};
+use futures::StreamExt;
+use syd_core::{AccessTech, NetworkError, SimLock};
+use super::{backend::Backend, NetworkService};
+use crate::services::dbus::{at, prop};
+
+pub const SERVICE: &str = "org.freedesktop.ModemManager1";
+const MODEM: &str = "org.freedesktop.ModemManager1.Modem";
+const MODEM_3GPP: &str = "org.freedesktop.ModemManager1.Modem.Modem3gpp";
+
+// MMModemState
+const STATE_ENABLED: i32 = 6;
+const STATE_CONNECTED: i32 = 11;
+
+// MMModemLock
+const LOCK_UNKNOWN: u32 = 0;
+const LOCK_NONE: u32 = 1;
+const LOCK_SIM_PIN: u32 = 2;
+const LOCK_SIM_PUK: u32 = 4;
+
+// MMModemAccessTechnology bits, grouped by generation.
+const ACCESS_GSM: u32 = 0x1e;
+const ACCESS_UMTS: u32 = 0x3e0;
+const ACCESS_CDMA: u32 = 0x3c00;
+const ACCESS_LTE: u32 = 0x4000 | 0x10000 | 0x20000;
+const ACCESS_5GNR: u32 = 0x8000;
+
+// MMModem3gppRegistrationState values that mean a visited network.
+const REG_ROAMING: u32 = 5;
+const REG_ROAMING_SMS_ONLY: u32 = 7;
+const REG_ROAMING_CSFB_NOT_PREFERRED: u32 = 10;
+
+#[proxy(interface = "org.freedesktop.ModemManager1.Modem", default_service = "org.freedesktop.ModemManager1")]
+pub trait Modem {
+    fn enable(&self, enable: bool) -> Result<()>;
+    #[zbus(property)] fn sim(&self) -> Result<OwnedObjectPath>;
+}
+
+#[proxy(interface = "org.freedesktop.ModemManager1.Modem.Simple", default_service = "org.freedesktop.ModemManager1")]
+pub trait Simple {
+    fn connect(&self, properties: HashMap<&str, Value<'_>>) -> Result<OwnedObjectPath>;
+    fn disconnect(&self, bearer: &ObjectPath<'_>) -> Result<()>;
+}
+
+#[proxy(interface = "org.freedesktop.ModemManager1.Sim", default_service = "org.freedesktop.ModemManager1")]
+pub trait Sim {
+    fn send_pin(&self, pin: &str) -> Result<()>;
+}
+
+/// Every ModemManager object with the properties of each interface it implements.
+type Objects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+/// Mobile broadband modems. ModemManager runs next to whichever network backend is in use;
+/// under NetworkManager, data connections go through NetworkManager so it keeps track of them.
+#[derive(Clone)]
+pub struct Modems { conn: Connection, backend: Backend }
+
+impl Modems {
+    pub fn new(conn: Connection, backend: Backend) -> Self { Self { conn, backend } }
+
+    async fn objects(&self) -> Result<Objects> {
+        let om = ObjectManagerProxy::builder(&self.conn).destination(SERVICE)?.path("/org/freedesktop/ModemManager1")?.build().await?;
+        Ok(om.get_managed_objects().await?.into_iter()
+            .map(|(path, ifaces)| (path, ifaces.into_iter().map(|(name, p)| (name.to_string(), p)).collect()))
+            .collect())
+    }
+
+    /// Empty, rather than an error, when ModemManager is not running.
+    pub async fn list(&self) -> Vec<syd_core::Modem> {
+        let mut res: Vec<_> = self.objects().await.unwrap_or_default().iter()
+            .filter_map(|(path, ifaces)| modem_from(path, ifaces))
+            .collect();
+        res.sort_by(|a, b| a.path.cmp(&b.path));
+        res
+    }
+
+    async fn find(&self, path: &str) -> std::result::Result<syd_core::Modem, NetworkError> {
+        self.list().await.into_iter().find(|m| m.path == path)
+            .ok_or_else(|| NetworkError::NotFound(format!("No modem at {}", path)))
+    }
+
+    /// Turning data on enables the modem first. Without NetworkManager the modem's default
+    /// bearer settings are used, which is enough for LTE carriers that hand out the APN.
+    pub async fn set_data(&self, path: &str, on: bool) -> std::result::Result<(), NetworkError> {
+        let m = self.find(path).await?;
+        if m.connected == on { return Ok(()); }
+        let obj = OwnedObjectPath::try_from(path).map_err(zbus::Error::from)?;
+        if on {
+            if matches!(m.sim_lock, SimLock::Pin | SimLock::Puk | SimLock::Other) {
+                return Err(NetworkError::Failed(format!("The SIM in {} is locked", m.model)));
+            }
+            if !m.enabled {
+                at::<ModemProxy>(&self.conn, &obj).await?.enable(true).await?;
+            }
+        }
+        match &self.backend {
+            Backend::Nm(nm) => nm.set_modem_data(path, on).await,
+            _ => {
+                let simple: SimpleProxy = at(&self.conn, &obj).await?;
+                if on {
+                    simple.connect(HashMap::new()).await?;
+                } else {
+                    // The null path disconnects every bearer.
+                    simple.disconnect(&ObjectPath::from_static_str_unchecked("/")).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn unlock(&self, path: &str, pin: &str) -> std::result::Result<(), NetworkError> {
+        let m = self.find(path).await?;
+        match m.sim_lock {
+            SimLock::Pin => {}
+            SimLock::None => return Ok(()),
+            SimLock::Puk => return Err(NetworkError::Failed("The SIM needs its PUK from the carrier".into())),
+            _ => return Err(NetworkError::Failed("The SIM is not waiting for a PIN".into())),
+        }
+        let obj = OwnedObjectPath::try_from(path).map_err(zbus::Error::from)?;
+        let sim = at::<ModemProxy>(&self.conn, &obj).await?.sim().await?;
+        let sim: SimProxy = at(&self.conn, &sim).await?;
+        sim.send_pin(pin).await.map_err(|e| unlock_error(e, m.unlock_retries))
+    }
+}
+
+fn unlock_error(e: zbus::Error, retries: u32) -> NetworkError {
+    let zbus::Error::MethodError(name, _, _) = &e else { return e.into() };
+    match name.as_str() {
+        "org.freedesktop.ModemManager1.Error.MobileEquipment.IncorrectPassword" => {
+            NetworkError::WrongPassword(format!("Wrong PIN, {} attempts left", retries.saturating_sub(1)))
+        }
+        "org.freedesktop.ModemManager1.Error.MobileEquipment.SimPuk" => NetworkError::Failed("The SIM needs its PUK from the carrier".into()),
+        _ => NetworkError::Failed(format!("Unlocking the SIM failed: {}", e)),
+    }
+}
+
+fn modem_from(path: &OwnedObjectPath, ifaces: &HashMap<String, HashMap<String, OwnedValue>>) -> Option<syd_core::Modem> {
+    let m = ifaces.get(MODEM)?;
+    let gpp = ifaces.get(MODEM_3GPP);
+    let state: i32 = prop(m, "State").unwrap_or(0);
+    let lock: u32 = prop(m, "UnlockRequired").unwrap_or(LOCK_UNKNOWN);
+    let retries: HashMap<u32, u32> = prop(m, "UnlockRetries").unwrap_or_default();
+    let registration: u32 = gpp.and_then(|g| prop(g, "RegistrationState")).unwrap_or(0);
+    Some(syd_core::Modem {
+        path: path.to_string(),
+        manufacturer: prop(m, "Manufacturer").unwrap_or_default(),
+        model: prop(m, "Model").unwrap_or_default(),
+        enabled: state >= STATE_ENABLED,
+        connected: state == STATE_CONNECTED,
+        signal: prop::<(u32, bool)>(m, "SignalQuality").map_or(0, |(q, _)| q.min(100) as u8),
+        access_tech: access_tech_from(prop(m, "AccessTechnologies").unwrap_or(0)),
+        operator: gpp.and_then(|g| prop(g, "OperatorName")).unwrap_or_default(),
+        roaming: matches!(registration, REG_ROAMING | REG_ROAMING_SMS_ONLY | REG_ROAMING_CSFB_NOT_PREFERRED),
+        sim_lock: sim_lock_from(lock),
+        unlock_retries: retries.get(&lock).copied().unwrap_or(0),
+    })
+}
+
+fn access_tech_from(bits: u32) -> AccessTech {
+    match bits {
+        b if b & ACCESS_5GNR != 0 => AccessTech::Nr5g,
+        b if b & ACCESS_LTE != 0 => AccessTech::Lte,
+        b if b & ACCESS_UMTS != 0 => AccessTech::Umts,
+        b if b & ACCESS_CDMA != 0 => AccessTech::Cdma,
+        b if b & ACCESS_GSM != 0 => AccessTech::Gsm,
+        _ => AccessTech::Unknown,
+    }
+}
+
+fn sim_lock_from(lock: u32) -> SimLock {
+    match lock {
+        LOCK_UNKNOWN => SimLock::Unknown,
+        LOCK_NONE => SimLock::None,
+        LOCK_SIM_PIN => SimLock::Pin,
+        LOCK_SIM_PUK => SimLock::Puk,
+        _ => SimLock::Other,
+    }
+}
+
+/// Sends `ModemChanged` and `ModemRemoved` as ModemManager's objects change. Signal quality
+/// updates are frequent, so only modems whose reported state differs are sent.
+pub async fn monitor(conn: Connection, modems: Modems) {
+    let iface = conn.object_server().interface::<_, NetworkService>("/org/syd/Network").await.unwrap();
+    let Ok(rule) = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .sender(SERVICE)
+        .map(|b| b.build()) else { return };
+    let Ok(mut events) = MessageStream::for_match_rule(rule, &modems.conn, None).await else { return };
+
+    let mut known: HashMap<String, syd_core::Modem> = modems.list().await.into_iter().map(|m| (m.path.clone(), m)).collect();
+    while events.next().await.is_some() {
+        // Registration changes several properties in a row.
+        while let Ok(Some(_)) = timeout(Duration::from_millis(300), events.next()).await {}
+        let curr: HashMap<String, syd_core::Modem> = modems.list().await.into_iter().map(|m| (m.path.clone(), m)).collect();
+        for gone in known.keys().filter(|k| !curr.contains_key(*k)) {
+            let _ = NetworkService::modem_removed(&*iface.get().await, iface.signal_context(), gone.clone()).await;
+        }
+        for (path, m) in &curr {
+            if known.get(path) == Some(m) { continue; }
+            let _ = NetworkService::modem_changed(&*iface.get().await, iface.signal_context(), m.clone()).await;
+        }
+        known = curr;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use super::super::iwd::Iwd;
+
+    fn props(entries: Vec<(&str, Value<'_>)>) -> HashMap<String, OwnedValue> {
+        entries.into_iter().map(|(k, v)| (k.to_string(), OwnedValue::try_from(v).unwrap())).collect()
+    }
+
+    fn path(p: &str) -> OwnedObjectPath { OwnedObjectPath::try_from(p).unwrap() }
+
+    const LTE_MODEM: &str = "/org/freedesktop/ModemManager1/Modem/0";
+    const NR_MODEM: &str = "/org/freedesktop/ModemManager1/Modem/1";
+    const NR_SIM: &str = "/org/freedesktop/ModemManager1/SIM/1";
+
+    // MMModemState
+    const STATE_LOCKED: i32 = 2;
+    const STATE_DISABLED: i32 = 3;
+    const STATE_REGISTERED: i32 = 8;
+
+    /// What ModemManager reports for one modem, and the calls made on it.
+    struct Fake {
+        state: i32,
+        lock: u32,
+        retries: u32,
+        calls: Vec<String>,
+    }
+
+    type Shared = Arc<Mutex<Fake>>;
+
+    fn fake(state: i32, lock: u32, retries: u32) -> Shared {
+        Arc::new(Mutex::new(Fake { state, lock, retries, calls: Vec::new() }))
+    }
+
+    /// A disabled LTE modem at home, and a PIN-locked 5G one roaming abroad.
+    struct Tree { lte: Shared, nr: Shared }
+
+    #[zbus::interface(name = "org.freedesktop.DBus.ObjectManager")]
+    impl Tree {
+        fn get_managed_objects(&self) -> Objects {
+            let modem = |f: &Shared, model: &str, tech: u32, operator: &str, registration: u32| {
+                let f = f.lock().unwrap();
+                HashMap::from([
+                    (MODEM.to_string(), props(vec![
+                        ("Manufacturer", Value::from("Quectel")),
+                        ("Model", Value::from(model)),
+                        ("State", Value::from(f.state)),
+                        ("UnlockRequired", Value::from(f.lock)),
+                        ("UnlockRetries", Value::from(HashMap::from([(LOCK_SIM_PIN, f.retries), (LOCK_SIM_PUK, 10)]))),
+                        ("SignalQuality", Value::from((70u32, true))),
+                        ("AccessTechnologies", Value::from(tech)),
+                    ])),
+                    (MODEM_3GPP.to_string(), props(vec![
+                        ("OperatorName", Value::from(operator)),
+                        ("RegistrationState", Value::from(registration)),
+                    ])),
+                ])
+            };
+            HashMap::from([
+                (path(LTE_MODEM), modem(&self.lte, "EM05-G", 0x4000, "Telia", 1)),
+                (path(NR_MODEM), modem(&self.nr, "RM520N-GL", 0x8000 | 0x4000, "Vodafone", REG_ROAMING)),
+                (path(NR_SIM), HashMap::from([("org.freedesktop.ModemManager1.Sim".to_string(), props(vec![]))])),
+            ])
+        }
+    }
+
+    struct Device { fake: Shared, sim: OwnedObjectPath }
+
+    #[zbus::interface(name = "org.freedesktop.ModemManager1.Modem")]
+    impl Device {
+        fn enable(&self, enable: bool) {
+            let mut f = self.fake.lock().unwrap();
+            f.calls.push(format!("Enable({})", enable));
+            f.state = if enable { STATE_REGISTERED } else { STATE_DISABLED };
+        }
+
+        #[zbus(property)]
+        fn sim(&self) -> OwnedObjectPath { self.sim.clone() }
+    }
+
+    struct Simple { fake: Shared }
+
+    #[zbus::interface(name = "org.freedesktop.ModemManager1.Modem.Simple")]
+    impl Simple {
+        fn connect(&self, _properties: HashMap<String, OwnedValue>) -> OwnedObjectPath {
+            let mut f = self.fake.lock().unwrap();
+            f.calls.push("Connect".into());
+            f.state = STATE_CONNECTED;
+            path("/org/freedesktop/ModemManager1/Bearer/0")
+        }
+
+        fn disconnect(&self, bearer: ObjectPath<'_>) {
+            let mut f = self.fake.lock().unwrap();
+            f.calls.push(format!("Disconnect({})", bearer));
+            f.state = STATE_REGISTERED;
+        }
+    }
+
+    #[derive(zbus::DBusError, Debug)]
+    #[zbus(prefix = "org.freedesktop.ModemManager1.Error.MobileEquipment")]
+    enum MobileEquipment {
+        #[zbus(error)]
+        ZBus(zbus::Error),
+        IncorrectPassword(String),
+    }
+
+    struct Card { fake: Shared }
+
+    #[zbus::interface(name = "org.freedesktop.ModemManager1.Sim")]
+    impl Card {
+        fn send_pin(&self, pin: &str) -> std::result::Result<(), MobileEquipment> {
+            let mut f = self.fake.lock().unwrap();
+            if pin != "1234" {
+                f.retries -= 1;
+                return Err(MobileEquipment::IncorrectPassword("Incorrect password".into()));
+            }
+            f.lock = LOCK_NONE;
+            f.state = STATE_DISABLED;
+            Ok(())
+        }
+    }
+
+    /// `Modems` talking to the stand-ins over a private connection, the serving end, which
+    /// has to be kept, and the two modems' state.
+    async fn stand_in() -> (Modems, Connection, Shared, Shared) {
+        let (lte, nr) = (fake(STATE_DISABLED, LOCK_NONE, 3), fake(STATE_LOCKED, LOCK_SIM_PIN, 3));
+        let (ours, theirs) = tokio::net::UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let server = zbus::connection::Builder::unix_stream(theirs).server(guid).unwrap().p2p()
+            .serve_at("/org/freedesktop/ModemManager1", Tree { lte: lte.clone(), nr: nr.clone() }).unwrap()
+            .serve_at(LTE_MODEM, Device { fake: lte.clone(), sim: path("/") }).unwrap()
+            .serve_at(LTE_MODEM, Simple { fake: lte.clone() }).unwrap()
+            .serve_at(NR_MODEM, Device { fake: nr.clone(), sim: path(NR_SIM) }).unwrap()
+            .serve_at(NR_SIM, Card { fake: nr.clone() }).unwrap()
+            .build();
+        let client = zbus::connection::Builder::unix_stream(ours).p2p().build();
+        let (server, client) = tokio::join!(server, client);
+        let client = client.unwrap();
+        let modems = Modems::new(client.clone(), Backend::Iwd(Iwd::new(client)));
+        (modems, server.unwrap(), lte, nr)
+    }
+
+    #[tokio::test]
+    async fn lists_modems() {
+        let (modems, _server, _, _) = stand_in().await;
+        let list = modems.list().await;
+        assert_eq!(list.iter().map(|m| m.path.as_str()).collect::<Vec<_>>(), [LTE_MODEM, NR_MODEM]);
+
+        let lte = &list[0];
+        assert_eq!((lte.model.as_str(), lte.access_tech, lte.signal), ("EM05-G", AccessTech::Lte, 70));
+        assert_eq!((lte.enabled, lte.connected, lte.roaming), (false, false, false));
+        assert_eq!((lte.operator.as_str(), lte.sim_lock, lte.unlock_retries), ("Telia", SimLock::None, 0));
+
+        let nr = &list[1];
+        assert_eq!((nr.access_tech, nr.roaming, nr.operator.as_str()), (AccessTech::Nr5g, true, "Vodafone"));
+        assert_eq!((nr.sim_lock, nr.unlock_retries), (SimLock::Pin, 3));
+    }
+
+    #[tokio::test]
+    async fn turns_data_on_and_off() {
+        let (modems, _server, lte, _) = stand_in().await;
+
+        modems.set_data(LTE_MODEM, true).await.unwrap();
+        assert_eq!(lte.lock().unwrap().calls, ["Enable(true)", "Connect"]);
+        assert!(modems.list().await[0].connected);
+        // Already on.
+        modems.set_data(LTE_MODEM, true).await.unwrap();
+
+        modems.set_data(LTE_MODEM, false).await.unwrap();
+        assert_eq!(lte.lock().unwrap().calls, ["Enable(true)", "Connect", "Disconnect(/)"]);
+        assert!(!modems.list().await[0].connected);
+
+        assert!(matches!(modems.set_data(NR_MODEM, true).await, Err(NetworkError::Failed(m)) if m == "The SIM in RM520N-GL is locked"));
+        assert!(matches!(modems.set_data("/org/freedesktop/ModemManager1/Modem/9", true).await, Err(NetworkError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn unlocks_the_sim() {
+        let (modems, _server, _, nr) = stand_in().await;
+
+        let wrong = modems.unlock(NR_MODEM, "0000").await;
+        assert!(matches!(wrong, Err(NetworkError::WrongPassword(m)) if m == "Wrong PIN, 2 attempts left"));
+        assert_eq!(modems.list().await[1].unlock_retries, 2);
+
+        modems.unlock(NR_MODEM, "1234").await.unwrap();
+        assert_eq!(nr.lock().unwrap().lock, LOCK_NONE);
+        assert_eq!(modems.list().await[1].sim_lock, SimLock::None);
+        // Nothing to unlock.
+        modems.unlock(LTE_MODEM, "0000").await.unwrap();
+    }
+
+    #[test]
+    fn modem_needs_the_modem_interface() {
+        let bare = HashMap::from([(MODEM.to_string(), props(vec![]))]);
+        let m = modem_from(&path(LTE_MODEM), &bare).unwrap();
+        assert_eq!((m.enabled, m.access_tech, m.sim_lock, m.operator.as_str()), (false, AccessTech::Unknown, SimLock::Unknown, ""));
+
+        let sim_only = HashMap::from([("org.freedesktop.ModemManager1.Sim".to_string(), props(vec![]))]);
+        assert_eq!(modem_from(&path(NR_SIM), &sim_only), None);
+
+        let connected = HashMap::from([(MODEM.to_string(), props(vec![("State", Value::from(STATE_CONNECTED)), ("SignalQuality", Value::from((130u32, false)))]))]);
+        let m = modem_from(&path(LTE_MODEM), &connected).unwrap();
+        assert_eq!((m.enabled, m.connected, m.signal), (true, true, 100));
+    }
+
+    #[test]
+    fn fastest_access_tech_wins() {
+        assert_eq!(access_tech_from(0), AccessTech::Unknown);
+        assert_eq!(access_tech_from(0x2), AccessTech::Gsm);
+        assert_eq!(access_tech_from(0x20 | 0x2), AccessTech::Umts);
+        assert_eq!(access_tech_from(0x400), AccessTech::Cdma);
+        assert_eq!(access_tech_from(0x4000 | 0x20), AccessTech::Lte);
+        assert_eq!(access_tech_from(0x10000), AccessTech::Lte);
+        assert_eq!(access_tech_from(0x8000 | 0x4000), AccessTech::Nr5g);
+    }
+
+    #[test]
+    fn sim_locks() {
+        assert_eq!(sim_lock_from(LOCK_UNKNOWN), SimLock::Unknown);
+        assert_eq!(sim_lock_from(LOCK_NONE), SimLock::None);
+        assert_eq!(sim_lock_from(LOCK_SIM_PIN), SimLock::Pin);
+        assert_eq!(sim_lock_from(LOCK_SIM_PUK), SimLock::Puk);
+        // PH-SIM PIN
+        assert_eq!(sim_lock_from(3), SimLock::Other);
+    }
+}
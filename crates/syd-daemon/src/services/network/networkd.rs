@@ -0,0 +1,544 @@
+use zbus::{proxy, Connection, Result, zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value}};
+use zbus::proxy::CacheProperties;
+use zbus::names::InterfaceName;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use futures::StreamExt;
+use syd_core::{wifi_security, ConnectStage, Metered, SavedNetwork, EapMethod, ConnectionState, ConnectionType, Connectivity, DeviceState, DeviceType, KeyMgmt, NetDevice, NetworkError, NetworkStatus, RadioType, SecretKind, SecretRequest, WifiBand, WifiNet, WifiSettings};
+use super::{agent::Prompter, backend, links, nm::channel_from};
+use crate::services::dbus::{at, prop};
+use crate::services::rfkill;
+
+pub const NETWORKD: &str = "org.freedesktop.network1";
+pub const SUPPLICANT: &str = "fi.w1.wpa_supplicant1";
+const STAGE_TIMEOUT: Duration = Duration::from_secs(40);
+/// How long networkd gets to configure an address once the link is associated.
+const DHCP_TIMEOUT: Duration = Duration::from_secs(30);
+// wpa_supplicant's reason code when the 4-way handshake times out, i.e. a wrong PSK.
+const REASON_4WAY_HANDSHAKE_TIMEOUT: i32 = 15;
+
+#[proxy(interface = "org.freedesktop.network1.Manager", default_service = "org.freedesktop.network1", default_path = "/org/freedesktop/network1")]
+pub trait Manager {
+    fn list_links(&self) -> Result<Vec<(i32, String, OwnedObjectPath)>>;
+}
+
+#[proxy(interface = "fi.w1.wpa_supplicant1", default_service = "fi.w1.wpa_supplicant1", default_path = "/fi/w1/wpa_supplicant1")]
+pub trait Supplicant {
+    fn get_interface(&self, ifname: &str) -> Result<OwnedObjectPath>;
+    fn create_interface(&self, args: HashMap<&str, Value<'_>>) -> Result<OwnedObjectPath>;
+}
+
+#[proxy(interface = "fi.w1.wpa_supplicant1.Interface", default_service = "fi.w1.wpa_supplicant1")]
+pub trait SupplicantInterface {
+    fn scan(&self, args: HashMap<&str, Value<'_>>) -> Result<()>;
+    fn add_network(&self, args: HashMap<&str, Value<'_>>) -> Result<OwnedObjectPath>;
+    fn select_network(&self, network: &ObjectPath<'_>) -> Result<()>;
+    fn remove_network(&self, network: &ObjectPath<'_>) -> Result<()>;
+    /// Only works when the interface was created with a config file that has `update_config=1`.
+    fn save_config(&self) -> Result<()>;
+
+    #[zbus(property)] fn state(&self) -> Result<String>;
+}
+
+/// wpa_supplicant for Wi-Fi, systemd-networkd for addresses and link state.
+#[derive(Clone)]
+pub struct Networkd { conn: Connection, prompter: Prompter }
+
+impl Networkd {
+    pub fn new(conn: Connection, prompter: Prompter) -> Self { Self { conn, prompter } }
+
+    pub fn connection(&self) -> &Connection { &self.conn }
+
+    async fn props(&self, service: &'static str, path: &OwnedObjectPath, iface: &'static str) -> Result<HashMap<String, OwnedValue>> {
+        let p = zbus::fdo::PropertiesProxy::builder(&self.conn)
+            .destination(service)?
+            .path(ObjectPath::from(path))?
+            .cache_properties(CacheProperties::No)
+            .build().await?;
+        Ok(p.get_all(Some(InterfaceName::from_static_str_unchecked(iface)).into()).await?)
+    }
+
+    /// wpa_supplicant's interface objects by name, for the Wi-Fi links it already handles.
+    async fn wifi_interfaces(&self) -> Result<Vec<(String, OwnedObjectPath)>> {
+        let supplicant = SupplicantProxy::new(&self.conn).await?;
+        let mut res = Vec::new();
+        for l in links::links().await.into_iter().filter(|l| l.device_type == DeviceType::Wifi) {
+            if let Ok(path) = supplicant.get_interface(&l.name).await { res.push((l.name, path)); }
+        }
+        Ok(res)
+    }
+
+    /// Like `wifi_interfaces`, but first hands wpa_supplicant the Wi-Fi links it does not
+    /// handle yet, using the per-interface config from `wpa_supplicant@.service` when there
+    /// is one so saved networks persist. Only for when the user scans or connects.
+    async fn add_wifi_interfaces(&self) -> Result<Vec<(String, OwnedObjectPath)>> {
+        let supplicant = SupplicantProxy::new(&self.conn).await?;
+        let mut res = Vec::new();
+        for l in links::links().await.into_iter().filter(|l| l.device_type == DeviceType::Wifi) {
+            let path = match supplicant.get_interface(&l.name).await {
+                Ok(path) => path,
+                Err(_) => {
+                    let config = format!("/etc/wpa_supplicant/wpa_supplicant-{}.conf", l.name);
+                    let mut args = HashMap::from([("Ifname", Value::from(l.name.as_str()))]);
+                    if Path::new(&config).exists() { args.insert("ConfigFile", Value::from(config.as_str())); }
+                    match supplicant.create_interface(args).await {
+                        Ok(path) => path,
+                        Err(e) => { eprintln!("wpa_supplicant cannot manage {}: {}", l.name, e); continue; }
+                    }
+                }
+            };
+            res.push((l.name, path));
+        }
+        Ok(res)
+    }
+
+    /// Link state as networkd reports it: (operational, administrative) by interface name.
+    async fn link_states(&self) -> HashMap<String, (String, String)> {
+        let mut res = HashMap::new();
+        let Ok(manager) = ManagerProxy::new(&self.conn).await else { return res };
+        for (_, name, path) in manager.list_links().await.unwrap_or_default() {
+            let Ok(p) = self.props(NETWORKD, &path, "org.freedesktop.network1.Link").await else { continue };
+            res.insert(name, (prop(&p, "OperationalState").unwrap_or_default(), prop(&p, "AdministrativeState").unwrap_or_default()));
+        }
+        res
+    }
+
+    /// Saved networks on `iface`, keyed by SSID.
+    async fn saved(&self, iface: &OwnedObjectPath) -> Result<HashMap<String, OwnedObjectPath>> {
+        let mut res = HashMap::new();
+        let p = self.props(SUPPLICANT, iface, "fi.w1.wpa_supplicant1.Interface").await?;
+        for net in prop::<Vec<OwnedObjectPath>>(&p, "Networks").unwrap_or_default() {
+            let Ok(n) = self.props(SUPPLICANT, &net, "fi.w1.wpa_supplicant1.Network").await else { continue };
+            let Some(settings) = prop::<HashMap<String, OwnedValue>>(&n, "Properties") else { continue };
+            if let Some(ssid) = prop::<String>(&settings, "ssid") { res.insert(unquote(&ssid), net); }
+        }
+        Ok(res)
+    }
+
+    pub async fn request_scan(&self) -> Result<()> {
+        for (_, path) in self.add_wifi_interfaces().await? {
+            let iface: SupplicantInterfaceProxy = at(&self.conn, &path).await?;
+            // Refused while a scan is running; the result will arrive either way.
+            let _ = iface.scan(HashMap::from([("Type", Value::from("active"))])).await;
+        }
+        Ok(())
+    }
+
+    /// wpa_supplicant has no radio switch of its own, so this goes through rfkill.
+    pub async fn set_wifi(&self, on: bool) -> zbus::fdo::Result<()> {
+        rfkill::set_blocked(RadioType::Wlan, !on)
+    }
+
+    pub async fn access_points(&self) -> Result<Vec<WifiNet>> {
+        let mut by_ssid: HashMap<String, WifiNet> = HashMap::new();
+        for (_, path) in self.wifi_interfaces().await? {
+            let known = self.saved(&path).await.unwrap_or_default();
+            let p = self.props(SUPPLICANT, &path, "fi.w1.wpa_supplicant1.Interface").await?;
+            let current: Option<OwnedObjectPath> = prop(&p, "CurrentBSS");
+            for bss_path in prop::<Vec<OwnedObjectPath>>(&p, "BSSs").unwrap_or_default() {
+                let Ok(b) = self.props(SUPPLICANT, &bss_path, "fi.w1.wpa_supplicant1.BSS").await else { continue };
+                let Some(net) = wifi_net(&b, current.as_ref() == Some(&bss_path), &known) else { continue };
+                match by_ssid.get_mut(&net.ssid) {
+                    Some(cur) if cur.strength >= net.strength => cur.active |= net.active,
+                    Some(cur) => { let active = cur.active || net.active; *cur = WifiNet { active, ..net }; }
+                    None => { by_ssid.insert(net.ssid.clone(), net); }
+                }
+            }
+        }
+        let mut res: Vec<WifiNet> = by_ssid.into_values().collect();
+        res.sort_by(|a, b| {
+            if a.active != b.active { return b.active.cmp(&a.active); }
+            b.strength.cmp(&a.strength)
+        });
+        Ok(res)
+    }
+
+    /// The interface seeing `ssid` best, with that access point's properties.
+    async fn find_bss(&self, ssid: &str) -> Result<Option<(String, OwnedObjectPath, HashMap<String, OwnedValue>)>> {
+        let mut best: Option<(String, OwnedObjectPath, HashMap<String, OwnedValue>)> = None;
+        for (name, path) in self.wifi_interfaces().await? {
+            let p = self.props(SUPPLICANT, &path, "fi.w1.wpa_supplicant1.Interface").await?;
+            for bss_path in prop::<Vec<OwnedObjectPath>>(&p, "BSSs").unwrap_or_default() {
+                let Ok(b) = self.props(SUPPLICANT, &bss_path, "fi.w1.wpa_supplicant1.BSS").await else { continue };
+                if prop::<Vec<u8>>(&b, "SSID").unwrap_or_default() != ssid.as_bytes() { continue; }
+                let signal: i16 = prop(&b, "Signal").unwrap_or(i16::MIN);
+                if best.as_ref().is_some_and(|(_, _, cur)| prop::<i16>(cur, "Signal").unwrap_or(i16::MIN) >= signal) { continue; }
+                best = Some((name.clone(), path.clone(), b));
+            }
+        }
+        Ok(best)
+    }
+
+    /// Connects to `ssid`, asking the shell for a password if the network needs one and
+    /// none is saved. A new `pass` replaces the saved one.
+    pub async fn connect(&self, ssid: &str, pass: &str, progress: mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+        if self.add_wifi_interfaces().await?.is_empty() {
+            return Err(NetworkError::RadioDisabled("No Wi-Fi device".into()));
+        }
+        let Some((ifname, iface, bss)) = self.find_bss(ssid).await? else {
+            return Err(NetworkError::NotFound(format!("{} is not in range", ssid)));
+        };
+        let (flags, _) = security_from(&bss);
+        let key_mgmt = if flags & wifi_security::ENTERPRISE != 0 {
+            KeyMgmt::Enterprise
+        } else if flags & wifi_security::WPA3 != 0 && flags & (wifi_security::WPA | wifi_security::WPA2) == 0 {
+            KeyMgmt::Wpa3Sae
+        } else if flags & (wifi_security::WEP | wifi_security::WPA | wifi_security::WPA2) != 0 {
+            KeyMgmt::Wpa2
+        } else {
+            KeyMgmt::Open
+        };
+        let w = WifiSettings { ssid: ssid.to_string(), key_mgmt, password: pass.to_string(), ..Default::default() };
+        self.activate(&ifname, &iface, &w, flags & wifi_security::WEP != 0, progress).await
+    }
+
+    pub async fn connect_advanced(&self, w: &WifiSettings, progress: mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+        self.add_wifi_interfaces().await?;
+        let (ifname, iface) = match self.find_bss(&w.ssid).await? {
+            Some((ifname, iface, _)) => (ifname, iface),
+            None if w.hidden => match self.add_wifi_interfaces().await?.into_iter().next() {
+                Some(i) => i,
+                None => return Err(NetworkError::RadioDisabled("No Wi-Fi device".into())),
+            },
+            None => return Err(NetworkError::NotFound(format!("{} is not in range", w.ssid))),
+        };
+        self.activate(&ifname, &iface, w, false, progress).await
+    }
+
+    async fn activate(&self, ifname: &str, path: &OwnedObjectPath, w: &WifiSettings, wep: bool, progress: mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+        let iface: SupplicantInterfaceProxy = zbus::proxy::Builder::new(&self.conn).path(path)?.build().await?;
+        let mut states = iface.receive_state_changed().await;
+        let _ = progress.send(ConnectStage::Preparing);
+
+        let saved = self.saved(path).await?.remove(&w.ssid);
+        let (network, added) = match saved {
+            Some(net) if w.password.is_empty() => (net, false),
+            saved => {
+                let mut w = w.clone();
+                if w.password.is_empty() && w.key_mgmt != KeyMgmt::Open {
+                    let (kind, fields) = match w.key_mgmt {
+                        KeyMgmt::Enterprise if w.identity.is_empty() => (SecretKind::Enterprise, vec!["identity".into(), "password".into()]),
+                        KeyMgmt::Enterprise => (SecretKind::Enterprise, vec!["password".into()]),
+                        _ => (SecretKind::Wifi, vec!["psk".into()]),
+                    };
+                    let request = SecretRequest { id: 0, kind, connection: w.ssid.clone(), ssid: w.ssid.clone(), fields, retry: false };
+                    let Some(mut secrets) = self.prompter.ask(request, path.clone(), "802-11-wireless-security").await else {
+                        return Err(NetworkError::Cancelled(format!("No password was given for {}", w.ssid)));
+                    };
+                    if let Some(id) = secrets.remove("identity") { w.identity = id; }
+                    w.password = secrets.remove("psk").or_else(|| secrets.remove("password")).unwrap_or_default();
+                }
+                if let Some(old) = saved { let _ = iface.remove_network(&old.as_ref()).await; }
+                (iface.add_network(network_args(&w, wep)).await?, true)
+            }
+        };
+        iface.select_network(&network.as_ref()).await?;
+
+        let res = wait_association(&w.ssid, &mut states, &progress).await;
+        let res = match res {
+            Ok(()) => self.wait_address(&w.ssid, ifname, &progress).await,
+            Err(e) => {
+                // Do not keep a network around with a password that did not work.
+                if added { let _ = iface.remove_network(&network.as_ref()).await; }
+                let reason: i32 = self.props(SUPPLICANT, path, "fi.w1.wpa_supplicant1.Interface").await.ok()
+                    .and_then(|p| prop(&p, "DisconnectReason"))
+                    .unwrap_or(0);
+                Err(if reason.abs() == REASON_4WAY_HANDSHAKE_TIMEOUT {
+                    NetworkError::WrongPassword(format!("Authentication with {} failed", w.ssid))
+                } else { e })
+            }
+        };
+        if added && res.is_ok() { let _ = iface.save_config().await; }
+        let _ = progress.send(if res.is_ok() { ConnectStage::Connected } else { ConnectStage::Failed });
+        res
+    }
+
+    /// Waits for networkd to put a routable address on `ifname`.
+    async fn wait_address(&self, ssid: &str, ifname: &str, progress: &mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+        let _ = progress.send(ConnectStage::GettingIp);
+        let deadline = Instant::now() + DHCP_TIMEOUT;
+        while Instant::now() < deadline {
+            if self.link_states().await.get(ifname).is_some_and(|(oper, _)| oper == "routable") { return Ok(()); }
+            sleep(Duration::from_millis(500)).await;
+        }
+        Err(NetworkError::DhcpFailed(format!("{} did not hand out an address", ssid)))
+    }
+
+    pub async fn forget(&self, ssid: &str) -> Result<()> {
+        for (_, path) in self.wifi_interfaces().await? {
+            let Some(net) = self.saved(&path).await?.remove(ssid) else { continue };
+            let iface: SupplicantInterfaceProxy = at(&self.conn, &path).await?;
+            iface.remove_network(&net.as_ref()).await?;
+            let _ = iface.save_config().await;
+        }
+        Ok(())
+    }
+
+    /// Networks in wpa_supplicant's config. It keeps no history, so `last_used` stays 0.
+    pub async fn saved_networks(&self) -> Result<Vec<SavedNetwork>> {
+        let mut res = Vec::new();
+        for (_, path) in self.wifi_interfaces().await? {
+            for (ssid, net) in self.saved(&path).await? {
+                let Ok(n) = self.props(SUPPLICANT, &net, "fi.w1.wpa_supplicant1.Network").await else { continue };
+                let settings: HashMap<String, OwnedValue> = prop(&n, "Properties").unwrap_or_default();
+                res.push(SavedNetwork {
+                    id: ssid.clone(),
+                    kind: ConnectionType::Wifi,
+                    ssid,
+                    autoconnect: prop(&n, "Enabled").unwrap_or(true),
+                    priority: prop::<String>(&settings, "priority").and_then(|p| p.parse().ok()).unwrap_or(0),
+                    metered: Metered::Auto,
+                    ..Default::default()
+                });
+            }
+        }
+        res.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(res)
+    }
+
+    /// Sets a property of the saved network for `ssid` on every interface, then saves the config.
+    async fn edit_network(&self, ssid: &str, key: &'static str, value: impl Fn() -> Value<'static>) -> std::result::Result<(), NetworkError> {
+        let mut found = false;
+        for (_, path) in self.wifi_interfaces().await? {
+            let Some(net) = self.saved(&path).await?.remove(ssid) else { continue };
+            found = true;
+            let p = zbus::fdo::PropertiesProxy::builder(&self.conn).destination(SUPPLICANT)?.path(ObjectPath::from(&net))?.build().await?;
+            p.set(InterfaceName::from_static_str_unchecked("fi.w1.wpa_supplicant1.Network"), key, &value()).await.map_err(zbus::Error::from)?;
+            let iface: SupplicantInterfaceProxy = at(&self.conn, &path).await?;
+            let _ = iface.save_config().await;
+        }
+        if found { Ok(()) } else { Err(NetworkError::NotFound(format!("No saved network named {}", ssid))) }
+    }
+
+    pub async fn set_autoconnect(&self, ssid: &str, enabled: bool) -> std::result::Result<(), NetworkError> {
+        // Disabled networks stay saved but are never picked automatically.
+        self.edit_network(ssid, "Enabled", || Value::from(enabled)).await
+    }
+
+    /// Only the keys given are changed; the rest of the network block stays as it is.
+    pub async fn set_priority(&self, ssid: &str, priority: i32) -> std::result::Result<(), NetworkError> {
+        self.edit_network(ssid, "Properties", || Value::from(HashMap::from([("priority", Value::from(priority))]))).await
+    }
+
+    pub async fn status(&self) -> Result<NetworkStatus> {
+        let links = links::links().await;
+        let route = links::default_route().await;
+        let states = self.link_states().await;
+        let mut st = NetworkStatus::default();
+
+        for (name, path) in self.wifi_interfaces().await.unwrap_or_default() {
+            let p = self.props(SUPPLICANT, &path, "fi.w1.wpa_supplicant1.Interface").await?;
+            match prop::<String>(&p, "State").unwrap_or_default().as_str() {
+                "completed" => {}
+                "authenticating" | "associating" | "associated" | "4way_handshake" | "group_handshake" => {
+                    st.state = ConnectionState::Connecting;
+                    continue;
+                }
+                _ => continue,
+            }
+            let Some(bss) = prop::<OwnedObjectPath>(&p, "CurrentBSS") else { continue };
+            let Ok(b) = self.props(SUPPLICANT, &bss, "fi.w1.wpa_supplicant1.BSS").await else { continue };
+            st.connection_type = ConnectionType::Wifi;
+            st.ssid = String::from_utf8_lossy(&prop::<Vec<u8>>(&b, "SSID").unwrap_or_default()).into_owned();
+            st.strength = backend::strength_from_dbm(prop::<i16>(&b, "Signal").unwrap_or(-100) as i32);
+            st.interface = name;
+            break;
+        }
+        if st.connection_type == ConnectionType::None {
+            let wired = links.iter().find(|l| {
+                l.device_type == DeviceType::Ethernet && states.get(&l.name).is_some_and(|(oper, _)| oper == "routable")
+            });
+            if let Some(l) = wired {
+                st.connection_type = ConnectionType::Ethernet;
+                st.interface = l.name.clone();
+                st.ssid = l.name.clone();
+            }
+        }
+        if st.connection_type == ConnectionType::None { return Ok(st); }
+
+        let (oper, admin) = states.get(&st.interface).cloned().unwrap_or_default();
+        st.connectivity = self.connectivity().await;
+        st.state = match (oper.as_str(), admin.as_str()) {
+            (_, "configuring") => ConnectionState::Connecting,
+            ("routable", _) if matches!(st.connectivity, Connectivity::Full | Connectivity::Unknown) => ConnectionState::Connected,
+            _ => ConnectionState::Limited,
+        };
+        links::describe(&mut st, &links, route);
+        Ok(st)
+    }
+
+    /// networkd's online state (systemd 249 and later); it has no captive portal check.
+    async fn connectivity(&self) -> Connectivity {
+        let path = ObjectPath::from_static_str_unchecked("/org/freedesktop/network1").into();
+        let p = self.props(NETWORKD, &path, "org.freedesktop.network1.Manager").await.unwrap_or_default();
+        match prop::<String>(&p, "OnlineState").as_deref() {
+            Some("online") => Connectivity::Full,
+            Some("partial") => Connectivity::Limited,
+            Some("offline") => Connectivity::None,
+            _ => Connectivity::Unknown,
+        }
+    }
+
+    pub async fn devices(&self) -> Result<Vec<NetDevice>> {
+        let states = self.link_states().await;
+        let mut ssids = HashMap::new();
+        for (name, path) in self.wifi_interfaces().await.unwrap_or_default() {
+            let Ok(p) = self.props(SUPPLICANT, &path, "fi.w1.wpa_supplicant1.Interface").await else { continue };
+            let Some(bss) = prop::<OwnedObjectPath>(&p, "CurrentBSS") else { continue };
+            let Ok(b) = self.props(SUPPLICANT, &bss, "fi.w1.wpa_supplicant1.BSS").await else { continue };
+            ssids.insert(name, String::from_utf8_lossy(&prop::<Vec<u8>>(&b, "SSID").unwrap_or_default()).into_owned());
+        }
+        Ok(links::links().await.iter()
+            .map(|l| {
+                let mut dev = l.device();
+                if let Some((oper, admin)) = states.get(&l.name) {
+                    dev.state = device_state_from(oper, admin);
+                }
+                dev.connection = ssids.get(&l.name).cloned().unwrap_or_default();
+                dev
+            })
+            .collect())
+    }
+}
+
+fn device_state_from(oper: &str, admin: &str) -> DeviceState {
+    match (oper, admin) {
+        (_, "unmanaged") => DeviceState::Unmanaged,
+        (_, "failed") => DeviceState::Failed,
+        (_, "configuring" | "pending") => DeviceState::Connecting,
+        ("routable" | "enslaved", _) => DeviceState::Connected,
+        ("degraded" | "carrier" | "degraded-carrier", "configured") => DeviceState::Connected,
+        ("degraded" | "carrier" | "degraded-carrier", _) => DeviceState::Connecting,
+        ("dormant", _) => DeviceState::Disconnected,
+        ("off" | "no-carrier" | "missing", _) => DeviceState::Unavailable,
+        _ => DeviceState::Unknown,
+    }
+}
+
+/// wpa_supplicant reports string network settings in config file syntax, quotes included.
+fn unquote(s: &str) -> String {
+    s.strip_prefix('"').and_then(|s| s.strip_suffix('"')).unwrap_or(s).to_string()
+}
+
+fn key_mgmt_of(props: &HashMap<String, OwnedValue>, key: &str) -> Vec<String> {
+    prop::<HashMap<String, OwnedValue>>(props, key)
+        .and_then(|m| prop::<Vec<String>>(&m, "KeyMgmt"))
+        .unwrap_or_default()
+}
+
+/// Maps a BSS's WPA and RSN key management to syd's security bits and label.
+fn security_from(bss: &HashMap<String, OwnedValue>) -> (u32, String) {
+    let wpa = key_mgmt_of(bss, "WPA");
+    let rsn = key_mgmt_of(bss, "RSN");
+    let mut bits = 0;
+    let mut labels = Vec::new();
+    if prop(bss, "Privacy").unwrap_or(false) && wpa.is_empty() && rsn.is_empty() {
+        bits |= wifi_security::WEP; labels.push("WEP");
+    }
+    if !wpa.is_empty() {
+        bits |= wifi_security::WPA; labels.push("WPA1");
+    }
+    if rsn.iter().any(|k| k.contains("psk") || k.contains("eap")) {
+        bits |= wifi_security::WPA2; labels.push("WPA2");
+    }
+    if rsn.iter().any(|k| k.contains("sae")) {
+        bits |= wifi_security::WPA3; labels.push("WPA3");
+    }
+    if rsn.iter().any(|k| k == "owe") {
+        bits |= wifi_security::OWE; labels.push("OWE");
+    }
+    if wpa.iter().chain(&rsn).any(|k| k.contains("eap")) {
+        bits |= wifi_security::ENTERPRISE; labels.push("802.1X");
+    }
+    (bits, labels.join(" "))
+}
+
+fn wifi_net(b: &HashMap<String, OwnedValue>, active: bool, known: &HashMap<String, OwnedObjectPath>) -> Option<WifiNet> {
+    let ssid = String::from_utf8_lossy(&prop::<Vec<u8>>(b, "SSID").unwrap_or_default()).into_owned();
+    if ssid.is_empty() { return None; }
+    let frequency = prop::<u16>(b, "Frequency").unwrap_or(0) as u32;
+    let (security_flags, security) = security_from(b);
+    Some(WifiNet {
+        known: known.contains_key(&ssid),
+        active,
+        strength: backend::strength_from_dbm(prop::<i16>(b, "Signal").unwrap_or(-100) as i32),
+        bssid: prop::<Vec<u8>>(b, "BSSID").unwrap_or_default().iter().map(|o| format!("{:02X}", o)).collect::<Vec<_>>().join(":"),
+        band: WifiBand::from_frequency(frequency),
+        channel: channel_from(frequency),
+        // Rates come in bit/s.
+        max_bitrate: prop::<Vec<u32>>(b, "Rates").unwrap_or_default().into_iter().max().unwrap_or(0) / 1000,
+        frequency,
+        security,
+        security_flags,
+        ssid,
+    })
+}
+
+/// Network block settings for AddNetwork, as in wpa_supplicant.conf.
+fn network_args(w: &WifiSettings, wep: bool) -> HashMap<&'static str, Value<'_>> {
+    let mut args = HashMap::from([("ssid", Value::from(w.ssid.as_str()))]);
+    if w.hidden { args.insert("scan_ssid", Value::from(1u32)); }
+    match w.key_mgmt {
+        KeyMgmt::Open => { args.insert("key_mgmt", Value::from("NONE")); }
+        KeyMgmt::Wpa2 if wep => {
+            args.insert("key_mgmt", Value::from("NONE"));
+            args.insert("wep_key0", Value::from(w.password.as_str()));
+        }
+        KeyMgmt::Wpa2 => {
+            args.insert("key_mgmt", Value::from("WPA-PSK SAE"));
+            args.insert("psk", Value::from(w.password.as_str()));
+        }
+        KeyMgmt::Wpa3Sae => {
+            args.insert("key_mgmt", Value::from("SAE"));
+            args.insert("sae_password", Value::from(w.password.as_str()));
+            args.insert("ieee80211w", Value::from(2u32));
+        }
+        KeyMgmt::Enterprise => {
+            args.insert("key_mgmt", Value::from("WPA-EAP"));
+            let eap = match w.eap { EapMethod::Peap => "PEAP", EapMethod::Ttls => "TTLS", EapMethod::Tls => "TLS" };
+            args.insert("eap", Value::from(eap));
+            let mut fields = vec![("identity", &w.identity), ("anonymous_identity", &w.anonymous_identity), ("ca_cert", &w.ca_cert)];
+            if w.eap == EapMethod::Tls {
+                fields.extend([("client_cert", &w.client_cert), ("private_key", &w.private_key), ("private_key_passwd", &w.password)]);
+            } else {
+                if !w.phase2_auth.is_empty() { args.insert("phase2", Value::from(format!("auth={}", w.phase2_auth))); }
+                fields.push(("password", &w.password));
+            }
+            for (key, value) in fields.into_iter().filter(|(_, v)| !v.is_empty()) {
+                args.insert(key, Value::from(value.as_str()));
+            }
+        }
+    }
+    args
+}
+
+/// Follows wpa_supplicant's state until it has associated and authenticated, or gave up.
+async fn wait_association(ssid: &str, states: &mut zbus::proxy::PropertyStream<'_, String>, progress: &mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+    let mut stage = ConnectStage::Preparing;
+    let mut drops = 0;
+    loop {
+        let state = match timeout(STAGE_TIMEOUT, states.next()).await {
+            Ok(Some(change)) => match change.get().await { Ok(s) => s, Err(_) => continue },
+            Ok(None) => return Err(NetworkError::Failed("Wi-Fi interface went away".into())),
+            Err(_) => return Err(NetworkError::Timeout(format!("{} did not respond", ssid))),
+        };
+        let next = match state.as_str() {
+            "completed" => return Ok(()),
+            "authenticating" | "associating" | "associated" => ConnectStage::Associating,
+            "4way_handshake" | "group_handshake" => ConnectStage::Authenticating,
+            // wpa_supplicant retries on its own; dropping out twice means it is not going to work.
+            "disconnected" | "inactive" if stage != ConnectStage::Preparing => {
+                drops += 1;
+                if drops == 2 { return Err(NetworkError::Failed(format!("Connection to {} failed", ssid))); }
+                continue;
+            }
+            _ => continue,
+        };
+        if next != stage {
+            stage = next;
+            let _ = progress.send(stage);
+        }
+    }
+}
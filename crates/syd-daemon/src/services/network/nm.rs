@@ -0,0 +1,1110 @@
+use zbus::{proxy, Connection, Result, zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value}};
+use zbus::proxy::CacheProperties;
+use zbus::names::InterfaceName;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use futures::StreamExt;
+use super::share::Credentials;
+use crate::services::dbus::{at, prop};
+use syd_core::{wifi_security, ConnectStage, EapMethod, KeyMgmt, Metered, NetworkError, SavedNetwork, WifiSettings, ConnectionState, ConnectionType, Connectivity, DeviceState, DeviceType, HotspotState, HotspotStatus, NetDevice, NetworkStatus, VpnProfile, VpnState, WifiBand, WifiNet};
+
+/// Connection settings as NetworkManager passes them around: setting name -> key -> value.
+pub type Settings = HashMap<String, HashMap<String, OwnedValue>>;
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+pub trait NetworkManager {
+    fn get_devices(&self) -> Result<Vec<OwnedObjectPath>>;
+    fn activate_connection(&self, connection: &ObjectPath<'_>, device: &ObjectPath<'_>, specific_object: &ObjectPath<'_>) -> Result<OwnedObjectPath>;
+    fn add_and_activate_connection(&self, connection: HashMap<&str, HashMap<&str, Value<'_>>>, device: &ObjectPath<'_>, specific_object: &ObjectPath<'_>) -> Result<(OwnedObjectPath, OwnedObjectPath)>;
+    fn deactivate_connection(&self, active_connection: &ObjectPath<'_>) -> Result<()>;
+    fn check_connectivity(&self) -> Result<u32>;
+
+    #[zbus(signal)] fn device_added(&self, device_path: OwnedObjectPath) -> Result<()>;
+    #[zbus(signal)] fn device_removed(&self, device_path: OwnedObjectPath) -> Result<()>;
+
+    #[zbus(property)] fn state(&self) -> Result<u32>;
+    #[zbus(property)] fn connectivity(&self) -> Result<u32>;
+    #[zbus(property)] fn connectivity_check_uri(&self) -> Result<String>;
+    #[zbus(property)] fn primary_connection(&self) -> Result<OwnedObjectPath>;
+    #[zbus(property)] fn activating_connection(&self) -> Result<OwnedObjectPath>;
+    #[zbus(property)] fn active_connections(&self) -> Result<Vec<OwnedObjectPath>>;
+    #[zbus(property)] fn wireless_enabled(&self) -> Result<bool>;
+    #[zbus(property)] fn wireless_hardware_enabled(&self) -> Result<bool>;
+}
+
+#[proxy(interface = "org.freedesktop.NetworkManager.Connection.Active", default_service = "org.freedesktop.NetworkManager")]
+pub trait ActiveConnection {
+    #[zbus(property)] fn id(&self) -> Result<String>;
+    #[zbus(property, name = "Type")] fn type_(&self) -> Result<String>;
+    #[zbus(property)] fn devices(&self) -> Result<Vec<OwnedObjectPath>>;
+    #[zbus(property)] fn ip4_config(&self) -> Result<OwnedObjectPath>;
+    #[zbus(property)] fn ip6_config(&self) -> Result<OwnedObjectPath>;
+}
+
+#[proxy(interface = "org.freedesktop.NetworkManager.Device", default_service = "org.freedesktop.NetworkManager")]
+pub trait Device {
+    fn disconnect(&self) -> Result<()>;
+    #[zbus(signal)] fn state_changed(&self, new_state: u32, old_state: u32, reason: u32) -> Result<()>;
+
+    #[zbus(property)] fn interface(&self) -> Result<String>;
+    /// For modems, the ModemManager object path.
+    #[zbus(property)] fn udi(&self) -> Result<String>;
+    #[zbus(property)] fn device_type(&self) -> Result<u32>;
+}
+
+#[proxy(interface = "org.freedesktop.NetworkManager.Device.Wireless", default_service = "org.freedesktop.NetworkManager")]
+pub trait Wireless {
+    fn get_all_access_points(&self) -> Result<Vec<OwnedObjectPath>>;
+    fn request_scan(&self, options: HashMap<&str, Value<'_>>) -> Result<()>;
+
+    #[zbus(signal)] fn access_point_added(&self, access_point: OwnedObjectPath) -> Result<()>;
+    #[zbus(signal)] fn access_point_removed(&self, access_point: OwnedObjectPath) -> Result<()>;
+
+    #[zbus(property)] fn active_access_point(&self) -> Result<OwnedObjectPath>;
+    #[zbus(property)] fn last_scan(&self) -> Result<i64>;
+}
+
+#[proxy(interface = "org.freedesktop.NetworkManager.AccessPoint", default_service = "org.freedesktop.NetworkManager")]
+pub trait AccessPoint {
+    #[zbus(property)] fn ssid(&self) -> Result<Vec<u8>>;
+    #[zbus(property)] fn strength(&self) -> Result<u8>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Settings",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager/Settings"
+)]
+pub trait Settings {
+    fn list_connections(&self) -> Result<Vec<OwnedObjectPath>>;
+}
+
+#[proxy(interface = "org.freedesktop.NetworkManager.Settings.Connection", default_service = "org.freedesktop.NetworkManager")]
+pub trait SettingsConnection {
+    fn get_settings(&self) -> Result<Settings>;
+    fn get_secrets(&self, setting_name: &str) -> Result<Settings>;
+    fn update(&self, properties: Settings) -> Result<()>;
+    fn delete(&self) -> Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.AgentManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager/AgentManager"
+)]
+pub trait AgentManager {
+    fn register_with_capabilities(&self, identifier: &str, capabilities: u32) -> Result<()>;
+}
+
+#[proxy(interface = "org.freedesktop.NetworkManager.IP4Config", default_service = "org.freedesktop.NetworkManager")]
+pub trait IP4Config {
+    #[zbus(property)] fn address_data(&self) -> Result<Vec<HashMap<String, OwnedValue>>>;
+    #[zbus(property)] fn gateway(&self) -> Result<String>;
+    #[zbus(property)] fn nameserver_data(&self) -> Result<Vec<HashMap<String, OwnedValue>>>;
+}
+
+#[proxy(interface = "org.freedesktop.NetworkManager.IP6Config", default_service = "org.freedesktop.NetworkManager")]
+pub trait IP6Config {
+    #[zbus(property)] fn address_data(&self) -> Result<Vec<HashMap<String, OwnedValue>>>;
+    #[zbus(property)] fn gateway(&self) -> Result<String>;
+    #[zbus(property)] fn nameservers(&self) -> Result<Vec<Vec<u8>>>;
+}
+
+pub const DEVICE_TYPE_WIFI: u32 = 2;
+
+// NM_ACTIVE_CONNECTION_STATE
+const ACTIVE_STATE_ACTIVATING: u32 = 1;
+const ACTIVE_STATE_ACTIVATED: u32 = 2;
+const ACTIVE_STATE_DEACTIVATING: u32 = 3;
+pub const ACTIVE_STATE_DEACTIVATED: u32 = 4;
+
+const DEVICE_STATE_ACTIVATED: u32 = 100;
+const DEVICE_STATE_FAILED: u32 = 120;
+
+// NM_DEVICE_STATE_REASON values that map to something the user can act on.
+const REASON_IP_CONFIG_UNAVAILABLE: u32 = 5;
+const REASON_IP_CONFIG_EXPIRED: u32 = 6;
+const REASON_NO_SECRETS: u32 = 7;
+const REASON_SUPPLICANT_DISCONNECT: u32 = 8;
+const REASON_SUPPLICANT_FAILED: u32 = 10;
+const REASON_SUPPLICANT_TIMEOUT: u32 = 11;
+const REASON_DHCP_START_FAILED: u32 = 15;
+const REASON_DHCP_FAILED: u32 = 17;
+const REASON_SSID_NOT_FOUND: u32 = 53;
+
+/// Profile name for the hotspot, matching what nmcli and GNOME use.
+const HOTSPOT_ID: &str = "Hotspot";
+
+// NMMetered, as stored in a profile's `connection.metered`.
+const METERED_UNKNOWN: i32 = 0;
+const METERED_YES: i32 = 1;
+const METERED_NO: i32 = 2;
+
+const STAGE_TIMEOUT: Duration = Duration::from_secs(40);
+const AUTH_TIMEOUT: Duration = Duration::from_secs(180);
+
+// NM_802_11_AP_FLAGS / NM_802_11_AP_SEC bits from NetworkManager's D-Bus API.
+const AP_FLAGS_PRIVACY: u32 = 0x1;
+const AP_SEC_KEY_MGMT_PSK: u32 = 0x100;
+const AP_SEC_KEY_MGMT_802_1X: u32 = 0x200;
+const AP_SEC_KEY_MGMT_SAE: u32 = 0x400;
+const AP_SEC_KEY_MGMT_OWE: u32 = 0x800 | 0x1000;
+
+fn is_null(path: &OwnedObjectPath) -> bool { path.as_str() == "/" }
+
+fn is_vpn(kind: &str) -> bool { kind == "vpn" || kind == "wireguard" }
+
+fn is_hotspot(s: &Settings) -> bool {
+    s.get("802-11-wireless").and_then(|w| prop::<String>(w, "mode")).as_deref() == Some("ap")
+}
+
+fn ssid_of(s: &Settings) -> String {
+    let ssid = s.get("802-11-wireless").and_then(|w| prop::<Vec<u8>>(w, "ssid")).unwrap_or_default();
+    String::from_utf8_lossy(&ssid).into_owned()
+}
+
+#[derive(Clone)]
+pub struct Nm { conn: Connection }
+
+impl Nm {
+    pub fn new(conn: Connection) -> Self { Self { conn } }
+
+    pub fn connection(&self) -> &Connection { &self.conn }
+
+    pub async fn manager(&self) -> Result<NetworkManagerProxy<'static>> {
+        NetworkManagerProxy::new(&self.conn).await
+    }
+
+    /// Fetches every property of `iface` on one object in a single round trip.
+    async fn props(&self, path: &OwnedObjectPath, iface: &'static str) -> Result<HashMap<String, OwnedValue>> {
+        let p = zbus::fdo::PropertiesProxy::builder(&self.conn)
+            .destination("org.freedesktop.NetworkManager")?
+            .path(ObjectPath::from(path))?
+            .cache_properties(CacheProperties::No)
+            .build().await?;
+        Ok(p.get_all(Some(InterfaceName::from_static_str_unchecked(iface)).into()).await?)
+    }
+
+    pub async fn wifi_devices(&self) -> Result<Vec<OwnedObjectPath>> {
+        let mut res = Vec::new();
+        for path in self.manager().await?.get_devices().await? {
+            let dev: DeviceProxy = at(&self.conn, &path).await?;
+            if dev.device_type().await.unwrap_or(0) == DEVICE_TYPE_WIFI { res.push(path); }
+        }
+        Ok(res)
+    }
+
+    pub async fn request_scan(&self) -> Result<()> {
+        for path in self.wifi_devices().await? {
+            let wifi: WirelessProxy = at(&self.conn, &path).await?;
+            // NM refuses scans while one is already running; the result will arrive either way.
+            let _ = wifi.request_scan(HashMap::new()).await;
+        }
+        Ok(())
+    }
+
+    /// Every saved profile with its settings, secrets excluded.
+    async fn saved(&self) -> Result<Vec<(OwnedObjectPath, Settings)>> {
+        let mut res = Vec::new();
+        let settings = SettingsProxy::new(&self.conn).await?;
+        for path in settings.list_connections().await? {
+            let con: SettingsConnectionProxy = at(&self.conn, &path).await?;
+            if let Ok(s) = con.get_settings().await { res.push((path, s)); }
+        }
+        Ok(res)
+    }
+
+    /// Saved Wi-Fi profiles keyed by SSID.
+    async fn saved_wifi(&self) -> Result<HashMap<String, OwnedObjectPath>> {
+        let mut res = HashMap::new();
+        for (path, s) in self.saved().await? {
+            if let Some(ssid) = s.get("802-11-wireless").and_then(|w| prop::<Vec<u8>>(w, "ssid")) {
+                res.insert(String::from_utf8_lossy(&ssid).into_owned(), path);
+            }
+        }
+        Ok(res)
+    }
+
+    pub async fn known_ssids(&self) -> Result<HashSet<String>> {
+        Ok(self.saved_wifi().await?.into_keys().collect())
+    }
+
+    /// Deletes every profile for `ssid`, whatever it has been renamed to.
+    pub async fn forget(&self, ssid: &str) -> Result<()> {
+        for (path, s) in self.saved().await? {
+            if ssid_of(&s) != ssid { continue; }
+            let con: SettingsConnectionProxy = at(&self.conn, &path).await?;
+            con.delete().await?;
+        }
+        Ok(())
+    }
+
+    /// Saved Wi-Fi and wired profiles, most recently used first. VPNs are listed by `vpns`.
+    pub async fn saved_networks(&self) -> Result<Vec<SavedNetwork>> {
+        let mut res = Vec::new();
+        for (_, s) in self.saved().await? {
+            let Some(con) = s.get("connection") else { continue };
+            let kind = match prop::<String>(con, "type").as_deref() {
+                Some("802-11-wireless") => ConnectionType::Wifi,
+                Some("802-3-ethernet") => ConnectionType::Ethernet,
+                _ => continue,
+            };
+            res.push(SavedNetwork {
+                id: prop(con, "id").unwrap_or_default(),
+                uuid: prop(con, "uuid").unwrap_or_default(),
+                kind,
+                ssid: ssid_of(&s),
+                // NM leaves out keys that still have their default value.
+                autoconnect: prop(con, "autoconnect").unwrap_or(true),
+                priority: prop(con, "autoconnect-priority").unwrap_or(0),
+                metered: match prop::<i32>(con, "metered") {
+                    Some(METERED_YES) => Metered::Yes,
+                    Some(METERED_NO) => Metered::No,
+                    _ => Metered::Auto,
+                },
+                last_used: prop(con, "timestamp").unwrap_or(0),
+            });
+        }
+        res.sort_by_key(|n| std::cmp::Reverse(n.last_used));
+        Ok(res)
+    }
+
+    /// Sets one key of the `connection` setting of a profile, found by name or UUID.
+    pub async fn edit_profile(&self, id: &str, key: &str, value: Value<'_>) -> std::result::Result<(), NetworkError> {
+        for (path, mut s) in self.saved().await? {
+            let Some(con) = s.get_mut("connection") else { continue };
+            if prop::<String>(con, "uuid").as_deref() != Some(id) && prop::<String>(con, "id").as_deref() != Some(id) { continue; }
+            con.insert(key.to_string(), value.try_into().map_err(zbus::Error::from)?);
+            let profile: SettingsConnectionProxy = at(&self.conn, &path).await?;
+            profile.update(s).await?;
+            return Ok(());
+        }
+        Err(NetworkError::NotFound(format!("No saved network named {}", id)))
+    }
+
+    pub async fn set_metered(&self, id: &str, metered: Metered) -> std::result::Result<(), NetworkError> {
+        let v = match metered { Metered::Auto => METERED_UNKNOWN, Metered::Yes => METERED_YES, Metered::No => METERED_NO };
+        self.edit_profile(id, "metered", Value::from(v)).await
+    }
+
+    /// Finds the strongest access point broadcasting `ssid`, with the device that sees it.
+    async fn find_ap(&self, ssid: &str) -> Result<Option<(OwnedObjectPath, OwnedObjectPath, HashMap<String, OwnedValue>)>> {
+        let mut best: Option<(OwnedObjectPath, OwnedObjectPath, HashMap<String, OwnedValue>)> = None;
+        for dev_path in self.wifi_devices().await? {
+            let wifi: WirelessProxy = at(&self.conn, &dev_path).await?;
+            for ap_path in wifi.get_all_access_points().await? {
+                let Ok(p) = self.props(&ap_path, "org.freedesktop.NetworkManager.AccessPoint").await else { continue };
+                if prop::<Vec<u8>>(&p, "Ssid").unwrap_or_default() != ssid.as_bytes() { continue; }
+                let strength: u8 = prop(&p, "Strength").unwrap_or(0);
+                if best.as_ref().is_some_and(|(_, _, b)| prop::<u8>(b, "Strength").unwrap_or(0) >= strength) { continue; }
+                best = Some((dev_path.clone(), ap_path, p));
+            }
+        }
+        Ok(best)
+    }
+
+    async fn ensure_radio(&self) -> std::result::Result<(), NetworkError> {
+        let nm = self.manager().await?;
+        if !nm.wireless_enabled().await? || !nm.wireless_hardware_enabled().await? {
+            return Err(NetworkError::RadioDisabled("Wi-Fi is turned off".into()));
+        }
+        Ok(())
+    }
+
+    /// Connects to `ssid` and waits until the attempt succeeds or fails, reporting each stage
+    /// on `progress`. Missing secrets are requested through the secret agent.
+    pub async fn connect(&self, ssid: &str, pass: &str, progress: mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+        self.ensure_radio().await?;
+        let Some((dev, ap, ap_props)) = self.find_ap(ssid).await? else {
+            return Err(NetworkError::NotFound(format!("{} is not in range", ssid)));
+        };
+
+        // Subscribe before activating so no transition is missed.
+        let device: DeviceProxy = at(&self.conn, &dev).await?;
+        let mut states = device.receive_state_changed().await?;
+        self.activate_wifi(ssid, pass, &dev, &ap, &ap_props).await?;
+        wait_activation(ssid, &mut states, progress).await
+    }
+
+    /// Like `connect`, for networks that need more than a PSK: hidden SSIDs, WPA3-only
+    /// networks and 802.1X. An existing profile for the SSID is updated rather than duplicated.
+    pub async fn connect_advanced(&self, w: &WifiSettings, progress: mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+        self.ensure_radio().await?;
+        let (dev, ap) = match self.find_ap(&w.ssid).await? {
+            Some((dev, ap, _)) => (dev, ap),
+            None if w.hidden => match self.wifi_devices().await?.into_iter().next() {
+                Some(dev) => (dev, ObjectPath::from_static_str_unchecked("/").into()),
+                None => return Err(NetworkError::RadioDisabled("No Wi-Fi device".into())),
+            },
+            None => return Err(NetworkError::NotFound(format!("{} is not in range", w.ssid))),
+        };
+
+        let device: DeviceProxy = at(&self.conn, &dev).await?;
+        let mut states = device.receive_state_changed().await?;
+        let nm = self.manager().await?;
+        let mut settings = wifi_settings(w);
+        match self.saved_wifi().await?.remove(&w.ssid) {
+            Some(path) => {
+                // The profile may have been renamed; only new ones are named after the SSID.
+                if let Some(c) = settings.get_mut("connection") { c.remove("id"); }
+                let con: SettingsConnectionProxy = at(&self.conn, &path).await?;
+                let mut current = con.get_settings().await?;
+                for stale in ["802-11-wireless-security", "802-1x"] { current.remove(stale); }
+                merge(&mut current, settings)?;
+                con.update(current).await?;
+                nm.activate_connection(&path.as_ref(), &dev.as_ref(), &ap.as_ref()).await?;
+            }
+            None => { nm.add_and_activate_connection(settings, &dev.as_ref(), &ap.as_ref()).await?; }
+        }
+        wait_activation(&w.ssid, &mut states, progress).await
+    }
+
+    /// Activates the saved profile for `ssid`, or creates one.
+    async fn activate_wifi(&self, ssid: &str, pass: &str, dev: &OwnedObjectPath, ap: &OwnedObjectPath, ap_props: &HashMap<String, OwnedValue>) -> Result<OwnedObjectPath> {
+        let nm = self.manager().await?;
+
+        if let Some(path) = self.saved_wifi().await?.remove(ssid) {
+            if !pass.is_empty() {
+                let con: SettingsConnectionProxy = at(&self.conn, &path).await?;
+                let mut settings = con.get_settings().await?;
+                if let Some(sec) = settings.get_mut("802-11-wireless-security") {
+                    let key = if prop::<String>(sec, "key-mgmt").as_deref() == Some("none") { "wep-key0" } else { "psk" };
+                    sec.insert(key.into(), Value::from(pass).try_into()?);
+                    con.update(settings).await?;
+                }
+            }
+            return nm.activate_connection(&path.as_ref(), &dev.as_ref(), &ap.as_ref()).await;
+        }
+
+        let mut settings = HashMap::from([
+            ("connection", HashMap::from([("id", Value::from(ssid)), ("type", Value::from("802-11-wireless"))])),
+        ]);
+        if !pass.is_empty() {
+            let (flags, _) = security_from(
+                prop(ap_props, "Flags").unwrap_or(0),
+                prop(ap_props, "WpaFlags").unwrap_or(0),
+                prop(ap_props, "RsnFlags").unwrap_or(0),
+            );
+            let sec = if flags & wifi_security::WEP != 0 {
+                HashMap::from([("key-mgmt", Value::from("none")), ("wep-key0", Value::from(pass))])
+            } else if flags & wifi_security::WPA3 != 0 && flags & (wifi_security::WPA | wifi_security::WPA2) == 0 {
+                HashMap::from([("key-mgmt", Value::from("sae")), ("psk", Value::from(pass))])
+            } else {
+                HashMap::from([("key-mgmt", Value::from("wpa-psk")), ("psk", Value::from(pass))])
+            };
+            settings.insert("802-11-wireless-security", sec);
+        }
+        let (_, active) = nm.add_and_activate_connection(settings, &dev.as_ref(), &ap.as_ref()).await?;
+        Ok(active)
+    }
+
+    pub async fn devices(&self) -> Result<Vec<(OwnedObjectPath, NetDevice)>> {
+        let mut res = Vec::new();
+        for path in self.manager().await?.get_devices().await? {
+            if let Ok(dev) = self.device(&path).await { res.push((path, dev)); }
+        }
+        Ok(res)
+    }
+
+    /// Asks NM to run its connectivity check now and waits for the result.
+    pub async fn check_connectivity(&self) -> Result<Connectivity> {
+        Ok(connectivity_from(self.manager().await?.check_connectivity().await?))
+    }
+
+    /// The URL NM probes for connectivity, if checking is configured.
+    pub async fn check_uri(&self) -> Result<String> {
+        self.manager().await?.connectivity_check_uri().await
+    }
+
+    /// Interfaces whose connection NM considers metered, by configuration or by guess.
+    pub async fn metered_interfaces(&self) -> Result<HashSet<String>> {
+        let mut res = HashSet::new();
+        for path in self.manager().await?.get_devices().await? {
+            let Ok(p) = self.props(&path, "org.freedesktop.NetworkManager.Device").await else { continue };
+            // NM_METERED_YES and NM_METERED_GUESS_YES
+            if matches!(prop::<u32>(&p, "Metered"), Some(1 | 3)) {
+                res.insert(prop(&p, "Interface").unwrap_or_default());
+            }
+        }
+        Ok(res)
+    }
+
+    pub async fn device(&self, path: &OwnedObjectPath) -> Result<NetDevice> {
+        let p = self.props(path, "org.freedesktop.NetworkManager.Device").await?;
+        let state = prop(&p, "State").unwrap_or(0);
+        let device_type = device_type_from(prop(&p, "DeviceType").unwrap_or(0));
+        let mut dev = NetDevice {
+            interface: prop(&p, "Interface").unwrap_or_default(),
+            state: device_state_from(state),
+            mac: prop(&p, "HwAddress").unwrap_or_default(),
+            carrier: state == DEVICE_STATE_ACTIVATED,
+            device_type,
+            ..Default::default()
+        };
+        match device_type {
+            DeviceType::Ethernet => {
+                let w = self.props(path, "org.freedesktop.NetworkManager.Device.Wired").await?;
+                dev.carrier = prop(&w, "Carrier").unwrap_or(false);
+                dev.speed = prop(&w, "Speed").unwrap_or(0);
+            }
+            DeviceType::Wifi => {
+                let w = self.props(path, "org.freedesktop.NetworkManager.Device.Wireless").await?;
+                dev.carrier = prop::<OwnedObjectPath>(&w, "ActiveAccessPoint").is_some_and(|ap| !is_null(&ap));
+                // Reported in kb/s.
+                dev.speed = prop::<u32>(&w, "Bitrate").unwrap_or(0) / 1000;
+            }
+            _ => {}
+        }
+
+        if let Some(ac) = prop::<OwnedObjectPath>(&p, "ActiveConnection").filter(|ac| !is_null(ac)) {
+            let ac: ActiveConnectionProxy = at(&self.conn, &ac).await?;
+            dev.connection = ac.id().await.unwrap_or_default();
+        }
+        if let Some(ip4) = prop::<OwnedObjectPath>(&p, "Ip4Config").filter(|c| !is_null(c)) {
+            let ip4: IP4ConfigProxy = at(&self.conn, &ip4).await?;
+            dev.ipv4 = addresses(ip4.address_data().await.unwrap_or_default());
+        }
+        if let Some(ip6) = prop::<OwnedObjectPath>(&p, "Ip6Config").filter(|c| !is_null(c)) {
+            let ip6: IP6ConfigProxy = at(&self.conn, &ip6).await?;
+            dev.ipv6 = addresses(ip6.address_data().await.unwrap_or_default());
+        }
+        Ok(dev)
+    }
+
+    /// The active connection running a hotspot, with its properties and profile settings.
+    async fn active_hotspot(&self) -> Result<Option<(OwnedObjectPath, HashMap<String, OwnedValue>, Settings)>> {
+        for path in self.manager().await?.active_connections().await? {
+            let Ok(p) = self.props(&path, "org.freedesktop.NetworkManager.Connection.Active").await else { continue };
+            if prop::<String>(&p, "Type").as_deref() != Some("802-11-wireless") { continue; }
+            let Some(profile) = prop::<OwnedObjectPath>(&p, "Connection") else { continue };
+            let con: SettingsConnectionProxy = at(&self.conn, &profile).await?;
+            let Ok(s) = con.get_settings().await else { continue };
+            if is_hotspot(&s) { return Ok(Some((path, p, s))); }
+        }
+        Ok(None)
+    }
+
+    pub async fn hotspot(&self) -> Result<HotspotStatus> {
+        let Some((_, p, s)) = self.active_hotspot().await? else { return Ok(HotspotStatus::default()) };
+        let mut st = HotspotStatus {
+            state: match prop(&p, "State").unwrap_or(0) {
+                ACTIVE_STATE_ACTIVATING => HotspotState::Starting,
+                ACTIVE_STATE_ACTIVATED => HotspotState::Active,
+                ACTIVE_STATE_DEACTIVATING => HotspotState::Stopping,
+                _ => HotspotState::Off,
+            },
+            ssid: ssid_of(&s),
+            band: match s.get("802-11-wireless").and_then(|w| prop::<String>(w, "band")).as_deref() {
+                Some("a") => WifiBand::Ghz5,
+                Some("bg") => WifiBand::Ghz2_4,
+                _ => WifiBand::Unknown,
+            },
+            ..Default::default()
+        };
+        if let Some(dev) = prop::<Vec<OwnedObjectPath>>(&p, "Devices").and_then(|d| d.into_iter().next()) {
+            let dev: DeviceProxy = at(&self.conn, &dev).await?;
+            st.interface = dev.interface().await.unwrap_or_default();
+        }
+        Ok(st)
+    }
+
+    /// Turns the first Wi-Fi device into an access point that shares the current connection,
+    /// reusing the saved hotspot profile if there is one. Returns once the hotspot is up.
+    pub async fn start_hotspot(&self, ssid: &str, password: &str, band: WifiBand) -> std::result::Result<(), NetworkError> {
+        if !(8..=63).contains(&password.len()) {
+            return Err(NetworkError::Failed("The hotspot password needs 8 to 63 characters".into()));
+        }
+        let band = match band {
+            WifiBand::Unknown => None,
+            WifiBand::Ghz2_4 => Some("bg"),
+            WifiBand::Ghz5 => Some("a"),
+            WifiBand::Ghz6 => return Err(NetworkError::Failed("Hotspots on 6 GHz are not supported".into())),
+        };
+        self.ensure_radio().await?;
+        let Some(dev) = self.wifi_devices().await?.into_iter().next() else {
+            return Err(NetworkError::RadioDisabled("No Wi-Fi device".into()));
+        };
+
+        let device: DeviceProxy = at(&self.conn, &dev).await?;
+        let mut states = device.receive_state_changed().await?;
+        let nm = self.manager().await?;
+        let settings = hotspot_settings(ssid, password, band);
+        let any = ObjectPath::from_static_str_unchecked("/");
+        match self.saved().await?.into_iter().find(|(_, s)| is_hotspot(s)) {
+            Some((path, mut current)) => {
+                let con: SettingsConnectionProxy = at(&self.conn, &path).await?;
+                if let Some(w) = current.get_mut("802-11-wireless") { w.remove("band"); }
+                current.remove("802-11-wireless-security");
+                merge(&mut current, settings)?;
+                con.update(current).await?;
+                nm.activate_connection(&path.as_ref(), &dev.as_ref(), &any).await?;
+            }
+            None => { nm.add_and_activate_connection(settings, &dev.as_ref(), &any).await?; }
+        }
+        // Hotspot stages are of no interest to the shell; `HotspotChanged` reports the outcome.
+        let (progress, _) = mpsc::unbounded_channel();
+        wait_activation(ssid, &mut states, progress).await
+    }
+
+    pub async fn stop_hotspot(&self) -> Result<()> {
+        if let Some((path, _, _)) = self.active_hotspot().await? {
+            self.manager().await?.deactivate_connection(&path.as_ref()).await?;
+        }
+        Ok(())
+    }
+
+    /// Looks up the credentials of a saved network. An empty `ssid` picks the running
+    /// hotspot, or else the network currently in use.
+    pub async fn credentials(&self, ssid: &str) -> std::result::Result<Credentials, NetworkError> {
+        let ssid = if !ssid.is_empty() {
+            ssid.to_string()
+        } else if let Some((_, _, s)) = self.active_hotspot().await? {
+            ssid_of(&s)
+        } else {
+            self.access_points().await?.into_iter().find(|n| n.active).map(|n| n.ssid)
+                .ok_or_else(|| NetworkError::NotFound("Not connected to a Wi-Fi network".into()))?
+        };
+        let Some(path) = self.saved_wifi().await?.remove(&ssid) else {
+            return Err(NetworkError::NotFound(format!("{} is not a saved network", ssid)));
+        };
+
+        let con: SettingsConnectionProxy = at(&self.conn, &path).await?;
+        let s = con.get_settings().await?;
+        let hidden = s.get("802-11-wireless").and_then(|w| prop(w, "hidden")).unwrap_or(false);
+        let (security, key) = match s.get("802-11-wireless-security").and_then(|sec| prop::<String>(sec, "key-mgmt")).as_deref() {
+            None | Some("owe") => return Ok(Credentials { ssid, security: "nopass", password: String::new(), hidden }),
+            Some("none") => ("WEP", "wep-key0"),
+            Some("sae") => ("SAE", "psk"),
+            Some("wpa-psk") => ("WPA", "psk"),
+            Some(_) => return Err(NetworkError::Failed(format!("{} uses 802.1X and cannot be shared", ssid))),
+        };
+        let secrets = con.get_secrets("802-11-wireless-security").await?;
+        let password = secrets.get("802-11-wireless-security").and_then(|sec| prop(sec, key)).unwrap_or_default();
+        Ok(Credentials { ssid, security, password, hidden })
+    }
+
+    /// Active VPN and WireGuard connections keyed by profile UUID, with their path and state.
+    pub async fn active_vpns(&self) -> Result<HashMap<String, (OwnedObjectPath, u32)>> {
+        let mut res = HashMap::new();
+        for path in self.manager().await?.active_connections().await? {
+            let Ok(p) = self.props(&path, "org.freedesktop.NetworkManager.Connection.Active").await else { continue };
+            if !is_vpn(&prop::<String>(&p, "Type").unwrap_or_default()) { continue; }
+            let state = prop(&p, "State").unwrap_or(0);
+            res.insert(prop(&p, "Uuid").unwrap_or_default(), (path, state));
+        }
+        Ok(res)
+    }
+
+    /// Saved VPN and WireGuard profiles, sorted by name.
+    pub async fn vpns(&self) -> Result<Vec<VpnProfile>> {
+        let active = self.active_vpns().await?;
+        let mut res = Vec::new();
+        for (_, s) in self.saved().await? {
+            let Some(con) = s.get("connection") else { continue };
+            let kind: String = prop(con, "type").unwrap_or_default();
+            if !is_vpn(&kind) { continue; }
+            let uuid: String = prop(con, "uuid").unwrap_or_default();
+            res.push(VpnProfile {
+                id: prop(con, "id").unwrap_or_default(),
+                // Plugin VPNs name their service, e.g. org.freedesktop.NetworkManager.openvpn.
+                kind: match s.get("vpn").and_then(|v| prop::<String>(v, "service-type")) {
+                    Some(service) => service.rsplit('.').next().unwrap_or_default().to_string(),
+                    None => kind,
+                },
+                state: active.get(&uuid).map(|(_, st)| vpn_state_from(*st)).unwrap_or_default(),
+                uuid,
+            });
+        }
+        res.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(res)
+    }
+
+    /// Looks up a VPN profile by name or UUID.
+    async fn find_vpn(&self, id: &str) -> std::result::Result<(OwnedObjectPath, String), NetworkError> {
+        for (path, s) in self.saved().await? {
+            let Some(con) = s.get("connection") else { continue };
+            if !is_vpn(&prop::<String>(con, "type").unwrap_or_default()) { continue; }
+            let uuid: String = prop(con, "uuid").unwrap_or_default();
+            if uuid == id || prop::<String>(con, "id").as_deref() == Some(id) { return Ok((path, uuid)); }
+        }
+        Err(NetworkError::NotFound(format!("No VPN profile named {}", id)))
+    }
+
+    /// Starts activating a VPN profile. Progress and failures arrive through `VpnStateChanged`.
+    pub async fn activate_vpn(&self, id: &str) -> std::result::Result<(), NetworkError> {
+        let (path, _) = self.find_vpn(id).await?;
+        // Without a device NM picks the connection the VPN should run over.
+        let any = ObjectPath::from_static_str_unchecked("/");
+        self.manager().await?.activate_connection(&path.as_ref(), &any, &any).await?;
+        Ok(())
+    }
+
+    pub async fn deactivate_vpn(&self, id: &str) -> std::result::Result<(), NetworkError> {
+        let (_, uuid) = self.find_vpn(id).await?;
+        if let Some((active, _)) = self.active_vpns().await?.remove(&uuid) {
+            self.manager().await?.deactivate_connection(&active.as_ref()).await?;
+        }
+        Ok(())
+    }
+
+    /// Brings mobile data up or down on the device NetworkManager made for a ModemManager
+    /// modem. Going up picks the best matching mobile broadband profile.
+    pub async fn set_modem_data(&self, modem: &str, on: bool) -> std::result::Result<(), NetworkError> {
+        for path in self.manager().await?.get_devices().await? {
+            let dev: DeviceProxy = at(&self.conn, &path).await?;
+            if dev.udi().await.unwrap_or_default() != modem { continue; }
+            if on {
+                let any = ObjectPath::from_static_str_unchecked("/");
+                self.manager().await?.activate_connection(&any, &path.as_ref(), &any).await?;
+            } else {
+                dev.disconnect().await?;
+            }
+            return Ok(());
+        }
+        Err(NetworkError::NotFound(format!("NetworkManager does not manage {}", modem)))
+    }
+
+    /// Lists visible networks, keeping only the strongest access point per SSID.
+    pub async fn access_points(&self) -> Result<Vec<WifiNet>> {
+        let known = self.known_ssids().await.unwrap_or_default();
+        let mut by_ssid: HashMap<String, WifiNet> = HashMap::new();
+
+        for dev_path in self.wifi_devices().await? {
+            let wifi: WirelessProxy = at(&self.conn, &dev_path).await?;
+            let active_ap = wifi.active_access_point().await?;
+            for ap_path in wifi.get_all_access_points().await? {
+                let Ok(p) = self.props(&ap_path, "org.freedesktop.NetworkManager.AccessPoint").await else { continue };
+                let ssid = String::from_utf8_lossy(&prop::<Vec<u8>>(&p, "Ssid").unwrap_or_default()).into_owned();
+                if ssid.is_empty() { continue; }
+
+                let frequency = prop(&p, "Frequency").unwrap_or(0);
+                let (security_flags, security) = security_from(
+                    prop(&p, "Flags").unwrap_or(0),
+                    prop(&p, "WpaFlags").unwrap_or(0),
+                    prop(&p, "RsnFlags").unwrap_or(0),
+                );
+                let net = WifiNet {
+                    known: known.contains(&ssid),
+                    active: ap_path == active_ap,
+                    strength: prop(&p, "Strength").unwrap_or(0),
+                    bssid: prop(&p, "HwAddress").unwrap_or_default(),
+                    band: WifiBand::from_frequency(frequency),
+                    channel: channel_from(frequency),
+                    max_bitrate: prop(&p, "MaxBitrate").unwrap_or(0),
+                    frequency,
+                    security,
+                    security_flags,
+                    ssid,
+                };
+                match by_ssid.get_mut(&net.ssid) {
+                    Some(cur) if cur.strength >= net.strength => cur.active |= net.active,
+                    Some(cur) => { let active = cur.active || net.active; *cur = WifiNet { active, ..net }; }
+                    None => { by_ssid.insert(net.ssid.clone(), net); }
+                }
+            }
+        }
+
+        let mut res: Vec<WifiNet> = by_ssid.into_values().collect();
+        res.sort_by(|a, b| {
+            if a.active != b.active { return b.active.cmp(&a.active); }
+            b.strength.cmp(&a.strength)
+        });
+        Ok(res)
+    }
+
+    pub async fn status(&self) -> Result<NetworkStatus> {
+        let nm = self.manager().await?;
+        let connectivity = connectivity_from(nm.connectivity().await.unwrap_or(0));
+        let mut st = NetworkStatus {
+            state: state_from(nm.state().await?, connectivity),
+            connectivity,
+            ..Default::default()
+        };
+
+        // A VPN runs over another connection. Report it by name and describe the link
+        // underneath, so the VPN profile never shows up as the network itself.
+        let mut link = None;
+        for path in nm.active_connections().await.unwrap_or_default() {
+            let Ok(p) = self.props(&path, "org.freedesktop.NetworkManager.Connection.Active").await else { continue };
+            match prop::<String>(&p, "Type").unwrap_or_default().as_str() {
+                "vpn" | "wireguard" if st.vpn.is_empty() => st.vpn = prop(&p, "Id").unwrap_or_default(),
+                "802-11-wireless" | "802-3-ethernet" if link.is_none() => link = Some(path),
+                _ => {}
+            }
+        }
+
+        let mut active = nm.primary_connection().await?;
+        if is_null(&active) { active = nm.activating_connection().await?; }
+        if is_null(&active) { return Ok(st); }
+
+        let ac: ActiveConnectionProxy = at(&self.conn, &active).await?;
+        st.connection_type = match ac.type_().await?.as_str() {
+            "802-11-wireless" => ConnectionType::Wifi,
+            "802-3-ethernet" => ConnectionType::Ethernet,
+            "vpn" | "wireguard" => ConnectionType::Vpn,
+            _ => ConnectionType::None,
+        };
+        let link: ActiveConnectionProxy = match &link {
+            Some(path) if st.connection_type == ConnectionType::Vpn => at(&self.conn, path).await?,
+            _ => ac.clone(),
+        };
+        st.ssid = link.id().await.unwrap_or_default();
+
+        if let Some(dev_path) = link.devices().await?.first() {
+            let dev: DeviceProxy = at(&self.conn, dev_path).await?;
+            st.interface = dev.interface().await.unwrap_or_default();
+            if dev.device_type().await.unwrap_or(0) == DEVICE_TYPE_WIFI {
+                let wifi: WirelessProxy = at(&self.conn, dev_path).await?;
+                let ap_path = wifi.active_access_point().await?;
+                if !is_null(&ap_path) {
+                    let ap: AccessPointProxy = at(&self.conn, &ap_path).await?;
+                    st.ssid = String::from_utf8_lossy(&ap.ssid().await?).into_owned();
+                    st.strength = ap.strength().await.unwrap_or(0);
+                }
+            }
+        }
+
+        let ip4_path = ac.ip4_config().await?;
+        if !is_null(&ip4_path) {
+            let ip4: IP4ConfigProxy = at(&self.conn, &ip4_path).await?;
+            st.ipv4 = addresses(ip4.address_data().await.unwrap_or_default());
+            st.gateway = ip4.gateway().await.unwrap_or_default();
+            st.dns = ip4.nameserver_data().await.unwrap_or_default().iter()
+                .filter_map(|d| prop(d, "address"))
+                .collect();
+        }
+        let ip6_path = ac.ip6_config().await?;
+        if !is_null(&ip6_path) {
+            let ip6: IP6ConfigProxy = at(&self.conn, &ip6_path).await?;
+            st.ipv6 = addresses(ip6.address_data().await.unwrap_or_default());
+            if st.gateway.is_empty() { st.gateway = ip6.gateway().await.unwrap_or_default(); }
+            for ns in ip6.nameservers().await.unwrap_or_default() {
+                if let Ok(octets) = <[u8; 16]>::try_from(ns.as_slice()) {
+                    st.dns.push(std::net::Ipv6Addr::from(octets).to_string());
+                }
+            }
+        }
+        Ok(st)
+    }
+}
+
+fn addresses(data: Vec<HashMap<String, OwnedValue>>) -> Vec<String> {
+    data.iter()
+        .filter_map(|d| {
+            let addr: String = prop(d, "address")?;
+            let prefix: u32 = prop(d, "prefix").unwrap_or(0);
+            Some(format!("{}/{}", addr, prefix))
+        })
+        .collect()
+}
+
+// NM_DEVICE_TYPE
+fn device_type_from(v: u32) -> DeviceType {
+    match v {
+        0 => DeviceType::Unknown,
+        1 => DeviceType::Ethernet,
+        DEVICE_TYPE_WIFI => DeviceType::Wifi,
+        8 => DeviceType::Modem,
+        13 => DeviceType::Bridge,
+        16 => DeviceType::Tun,
+        29 => DeviceType::Wireguard,
+        32 => DeviceType::Loopback,
+        _ => DeviceType::Other,
+    }
+}
+
+// NM_DEVICE_STATE
+fn device_state_from(v: u32) -> DeviceState {
+    match v {
+        10 => DeviceState::Unmanaged,
+        20 => DeviceState::Unavailable,
+        30 => DeviceState::Disconnected,
+        40..=90 => DeviceState::Connecting,
+        DEVICE_STATE_ACTIVATED => DeviceState::Connected,
+        110 => DeviceState::Disconnecting,
+        DEVICE_STATE_FAILED => DeviceState::Failed,
+        _ => DeviceState::Unknown,
+    }
+}
+
+fn vpn_state_from(v: u32) -> VpnState {
+    match v {
+        ACTIVE_STATE_ACTIVATING => VpnState::Connecting,
+        ACTIVE_STATE_ACTIVATED => VpnState::Connected,
+        ACTIVE_STATE_DEACTIVATING => VpnState::Disconnecting,
+        _ => VpnState::Disconnected,
+    }
+}
+
+/// Whether an NM_ACTIVE_CONNECTION_STATE_REASON for a deactivated connection means the
+/// attempt failed, as opposed to the user or NM shutting it down on purpose.
+pub fn vpn_failed(reason: u32) -> bool {
+    // service-stopped through login-failed, plus dependency-failed.
+    matches!(reason, 4..=10 | 12)
+}
+
+fn connectivity_from(v: u32) -> Connectivity {
+    match v {
+        1 => Connectivity::None,
+        2 => Connectivity::Portal,
+        3 => Connectivity::Limited,
+        4 => Connectivity::Full,
+        _ => Connectivity::Unknown,
+    }
+}
+
+/// Folds NM_STATE and the connectivity check result into a single state for the shell.
+fn state_from(nm_state: u32, connectivity: Connectivity) -> ConnectionState {
+    match nm_state {
+        40 => ConnectionState::Connecting,
+        50..=70 => match connectivity {
+            Connectivity::Portal => ConnectionState::Portal,
+            Connectivity::Limited | Connectivity::None => ConnectionState::Limited,
+            _ if nm_state < 70 => ConnectionState::Limited,
+            _ => ConnectionState::Connected,
+        },
+        _ => ConnectionState::Disconnected,
+    }
+}
+
+/// Maps NM's AP flags to syd's security bits plus the label nmcli would show.
+fn security_from(flags: u32, wpa: u32, rsn: u32) -> (u32, String) {
+    let mut bits = 0;
+    let mut labels = Vec::new();
+    if flags & AP_FLAGS_PRIVACY != 0 && wpa == 0 && rsn == 0 {
+        bits |= wifi_security::WEP; labels.push("WEP");
+    }
+    if wpa != 0 {
+        bits |= wifi_security::WPA; labels.push("WPA1");
+    }
+    if rsn & (AP_SEC_KEY_MGMT_PSK | AP_SEC_KEY_MGMT_802_1X) != 0 {
+        bits |= wifi_security::WPA2; labels.push("WPA2");
+    }
+    if rsn & AP_SEC_KEY_MGMT_SAE != 0 {
+        bits |= wifi_security::WPA3; labels.push("WPA3");
+    }
+    if rsn & AP_SEC_KEY_MGMT_OWE != 0 {
+        bits |= wifi_security::OWE; labels.push("OWE");
+    }
+    if (wpa | rsn) & AP_SEC_KEY_MGMT_802_1X != 0 {
+        bits |= wifi_security::ENTERPRISE; labels.push("802.1X");
+    }
+    (bits, labels.join(" "))
+}
+
+pub fn channel_from(mhz: u32) -> u32 {
+    match mhz {
+        2484 => 14,
+        2412..=2472 => (mhz - 2407) / 5,
+        5150..=5925 => (mhz - 5000) / 5,
+        5955..=7115 => (mhz - 5950) / 5,
+        _ => 0,
+    }
+}
+
+/// Follows a device through activation until it is up or has failed.
+async fn wait_activation(ssid: &str, states: &mut StateChangedStream<'_>, progress: mpsc::UnboundedSender<ConnectStage>) -> std::result::Result<(), NetworkError> {
+    let mut stage = ConnectStage::Preparing;
+    let _ = progress.send(stage);
+    loop {
+        // NM waits on the secret agent while authenticating, so give the user time to type.
+        let limit = if stage == ConnectStage::Authenticating { AUTH_TIMEOUT } else { STAGE_TIMEOUT };
+        let args = match timeout(limit, states.next()).await {
+            Ok(Some(sig)) => match sig.args() { Ok(a) => (a.new_state, a.reason), Err(_) => continue },
+            Ok(None) => {
+                let _ = progress.send(ConnectStage::Failed);
+                return Err(NetworkError::Failed("Wi-Fi device went away".into()));
+            }
+            Err(_) => {
+                let _ = progress.send(ConnectStage::Failed);
+                return Err(NetworkError::Timeout(format!("{} did not respond", ssid)));
+            }
+        };
+        let next = match args {
+            (DEVICE_STATE_ACTIVATED, _) => {
+                let _ = progress.send(ConnectStage::Connected);
+                return Ok(());
+            }
+            (DEVICE_STATE_FAILED, reason) => {
+                let _ = progress.send(ConnectStage::Failed);
+                return Err(connect_error(reason, ssid));
+            }
+            (40, _) => ConnectStage::Preparing,
+            (50, _) => ConnectStage::Associating,
+            (60, _) => ConnectStage::Authenticating,
+            (70, _) => ConnectStage::GettingIp,
+            (80 | 90, _) => ConnectStage::Verifying,
+            _ => continue,
+        };
+        if next != stage {
+            stage = next;
+            let _ = progress.send(stage);
+        }
+    }
+}
+
+/// Writes `new` over `current` key by key, keeping whatever else the profile has set.
+fn merge(current: &mut Settings, new: HashMap<&str, HashMap<&str, Value<'_>>>) -> Result<()> {
+    for (name, section) in new {
+        let entry = current.entry(name.to_string()).or_default();
+        for (k, v) in section { entry.insert(k.to_string(), v.try_into()?); }
+    }
+    Ok(())
+}
+
+fn hotspot_settings<'a>(ssid: &'a str, password: &'a str, band: Option<&'static str>) -> HashMap<&'static str, HashMap<&'static str, Value<'a>>> {
+    let mut wifi = HashMap::from([("ssid", Value::from(ssid.as_bytes())), ("mode", Value::from("ap"))]);
+    if let Some(band) = band { wifi.insert("band", Value::from(band)); }
+    HashMap::from([
+        ("connection", HashMap::from([
+            ("id", Value::from(HOTSPOT_ID)),
+            ("type", Value::from("802-11-wireless")),
+            ("autoconnect", Value::from(false)),
+        ])),
+        ("802-11-wireless", wifi),
+        ("802-11-wireless-security", HashMap::from([
+            ("key-mgmt", Value::from("wpa-psk")),
+            ("psk", Value::from(password)),
+            ("proto", Value::from(vec!["rsn"])),
+            ("pairwise", Value::from(vec!["ccmp"])),
+            ("group", Value::from(vec!["ccmp"])),
+        ])),
+        ("ipv4", HashMap::from([("method", Value::from("shared"))])),
+        ("ipv6", HashMap::from([("method", Value::from("ignore"))])),
+    ])
+}
+
+/// NM stores certificates as byte arrays; a path is passed as a NUL-terminated `file://` URI.
+fn cert_path(path: &str) -> Value<'static> {
+    let mut uri = format!("file://{}", path).into_bytes();
+    uri.push(0);
+    Value::from(uri)
+}
+
+fn wifi_settings(w: &WifiSettings) -> HashMap<&'static str, HashMap<&'static str, Value<'_>>> {
+    let mut settings = HashMap::from([
+        ("connection", HashMap::from([("id", Value::from(w.ssid.as_str())), ("type", Value::from("802-11-wireless"))])),
+        ("802-11-wireless", HashMap::from([
+            ("ssid", Value::from(w.ssid.as_bytes())),
+            ("mode", Value::from("infrastructure")),
+            ("hidden", Value::from(w.hidden)),
+        ])),
+    ]);
+    let key_mgmt = match w.key_mgmt {
+        KeyMgmt::Open => return settings,
+        KeyMgmt::Wpa2 => "wpa-psk",
+        KeyMgmt::Wpa3Sae => "sae",
+        KeyMgmt::Enterprise => "wpa-eap",
+    };
+    let mut sec = HashMap::from([("key-mgmt", Value::from(key_mgmt))]);
+    let enterprise = w.key_mgmt == KeyMgmt::Enterprise;
+    if !enterprise && !w.password.is_empty() { sec.insert("psk", Value::from(w.password.as_str())); }
+    settings.insert("802-11-wireless-security", sec);
+    if !enterprise { return settings; }
+
+    let eap = match w.eap { EapMethod::Peap => "peap", EapMethod::Ttls => "ttls", EapMethod::Tls => "tls" };
+    let mut x = HashMap::from([("eap", Value::from(vec![eap]))]);
+    if !w.identity.is_empty() { x.insert("identity", Value::from(w.identity.as_str())); }
+    if !w.anonymous_identity.is_empty() { x.insert("anonymous-identity", Value::from(w.anonymous_identity.as_str())); }
+    if !w.ca_cert.is_empty() { x.insert("ca-cert", cert_path(&w.ca_cert)); }
+    if w.eap == EapMethod::Tls {
+        if !w.client_cert.is_empty() { x.insert("client-cert", cert_path(&w.client_cert)); }
+        if !w.private_key.is_empty() { x.insert("private-key", cert_path(&w.private_key)); }
+        if !w.password.is_empty() { x.insert("private-key-password", Value::from(w.password.as_str())); }
+    } else {
+        if !w.phase2_auth.is_empty() { x.insert("phase2-auth", Value::from(w.phase2_auth.as_str())); }
+        if !w.password.is_empty() { x.insert("password", Value::from(w.password.as_str())); }
+    }
+    settings.insert("802-1x", x);
+    settings
+}
+
+fn connect_error(reason: u32, ssid: &str) -> NetworkError {
+    match reason {
+        REASON_NO_SECRETS => NetworkError::Cancelled(format!("No password was given for {}", ssid)),
+        REASON_SUPPLICANT_DISCONNECT..=REASON_SUPPLICANT_FAILED => NetworkError::WrongPassword(format!("Authentication with {} failed", ssid)),
+        REASON_SUPPLICANT_TIMEOUT => NetworkError::Timeout(format!("{} did not respond", ssid)),
+        REASON_IP_CONFIG_UNAVAILABLE | REASON_IP_CONFIG_EXPIRED | REASON_DHCP_START_FAILED..=REASON_DHCP_FAILED => {
+            NetworkError::DhcpFailed(format!("{} did not hand out an address", ssid))
+        }
+        REASON_SSID_NOT_FOUND => NetworkError::NotFound(format!("{} is not in range", ssid)),
+        _ => NetworkError::Failed(format!("Connection to {} failed (reason {})", ssid, reason)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_state_and_connectivity() {
+        assert_eq!(state_from(20, Connectivity::Unknown), ConnectionState::Disconnected);
+        assert_eq!(state_from(40, Connectivity::Unknown), ConnectionState::Connecting);
+        assert_eq!(state_from(70, Connectivity::Full), ConnectionState::Connected);
+        assert_eq!(state_from(70, Connectivity::Unknown), ConnectionState::Connected);
+        assert_eq!(state_from(70, Connectivity::Portal), ConnectionState::Portal);
+        assert_eq!(state_from(70, Connectivity::None), ConnectionState::Limited);
+        // CONNECTED_SITE without a check result is still not the internet.
+        assert_eq!(state_from(60, Connectivity::Full), ConnectionState::Limited);
+        assert_eq!(state_from(50, Connectivity::Portal), ConnectionState::Portal);
+    }
+
+    #[test]
+    fn labels_security_like_nmcli() {
+        use wifi_security::*;
+        assert_eq!(security_from(0, 0, 0), (0, String::new()));
+        assert_eq!(security_from(AP_FLAGS_PRIVACY, 0, 0), (WEP, "WEP".into()));
+        assert_eq!(security_from(AP_FLAGS_PRIVACY, 0, AP_SEC_KEY_MGMT_PSK), (WPA2, "WPA2".into()));
+        assert_eq!(security_from(AP_FLAGS_PRIVACY, AP_SEC_KEY_MGMT_PSK, AP_SEC_KEY_MGMT_PSK), (WPA | WPA2, "WPA1 WPA2".into()));
+        assert_eq!(security_from(AP_FLAGS_PRIVACY, 0, AP_SEC_KEY_MGMT_PSK | AP_SEC_KEY_MGMT_SAE), (WPA2 | WPA3, "WPA2 WPA3".into()));
+        assert_eq!(security_from(AP_FLAGS_PRIVACY, 0, AP_SEC_KEY_MGMT_802_1X), (WPA2 | ENTERPRISE, "WPA2 802.1X".into()));
+        assert_eq!(security_from(0, 0, 0x1000), (OWE, "OWE".into()));
+    }
+
+    #[test]
+    fn channels_across_bands() {
+        assert_eq!(channel_from(2412), 1);
+        assert_eq!(channel_from(2472), 13);
+        assert_eq!(channel_from(2484), 14);
+        assert_eq!(channel_from(5180), 36);
+        assert_eq!(channel_from(5825), 165);
+        assert_eq!(channel_from(5955), 1);
+        assert_eq!(channel_from(6115), 33);
+        assert_eq!(channel_from(0), 0);
+        assert_eq!(channel_from(60480), 0);
+    }
+
+    #[test]
+    fn maps_failure_reasons() {
+        assert!(matches!(connect_error(REASON_NO_SECRETS, "Home"), NetworkError::Cancelled(_)));
+        assert!(matches!(connect_error(9, "Home"), NetworkError::WrongPassword(_)));
+        assert!(matches!(connect_error(REASON_SUPPLICANT_TIMEOUT, "Home"), NetworkError::Timeout(_)));
+        assert!(matches!(connect_error(REASON_IP_CONFIG_UNAVAILABLE, "Home"), NetworkError::DhcpFailed(_)));
+        assert!(matches!(connect_error(16, "Home"), NetworkError::DhcpFailed(_)));
+        assert!(matches!(connect_error(REASON_SSID_NOT_FOUND, "Home"), NetworkError::NotFound(m) if m == "Home is not in range"));
+        assert!(matches!(connect_error(1, "Home"), NetworkError::Failed(m) if m.contains("reason 1")));
+    }
+
+    #[test]
+    fn device_types() {
+        assert_eq!(device_type_from(1), DeviceType::Ethernet);
+        assert_eq!(device_type_from(DEVICE_TYPE_WIFI), DeviceType::Wifi);
+        assert_eq!(device_type_from(8), DeviceType::Modem);
+        assert_eq!(device_type_from(29), DeviceType::Wireguard);
+        assert_eq!(device_type_from(14), DeviceType::Other);
+    }
+
+    #[test]
+    fn vpn_failures_are_not_user_disconnects() {
+        // user-disconnected, device-disconnected
+        assert!(!vpn_failed(2) && !vpn_failed(3));
+        // service-stopped, ip-config-invalid, connect-timeout, login-failed, dependency-failed
+        assert!(vpn_failed(4) && vpn_failed(5) && vpn_failed(6) && vpn_failed(10) && vpn_failed(12));
+        assert!(!vpn_failed(11));
+    }
+}
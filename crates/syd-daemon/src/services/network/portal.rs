@@ -0,0 +1,131 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+
+/// Used when neither syd's config nor NetworkManager names a check URL.
+pub const DEFAULT_CHECK_URI: &str = "http://nmcheck.gnome.org/check_network_status.txt";
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Only the status line and headers matter; portals sometimes send whole pages.
+const MAX_RESPONSE: u64 = 16 * 1024;
+/// What NetworkManager's check URLs serve when nothing is in the way.
+const ONLINE_TEXT: &str = "NetworkManager is online";
+
+/// What the check URL answered.
+#[derive(Debug, PartialEq, Eq)]
+enum Answer {
+    /// 204, or NetworkManager's check text: nothing in the way.
+    Online,
+    /// A portal redirected the request to its sign-in page.
+    Redirect(String),
+    /// A portal served a page of its own in place of the check.
+    Page,
+}
+
+/// Finds the page a captive portal wants the user to sign in on. Portals hijack plain
+/// HTTP requests and redirect them, so this requests `check_uri` and reads the redirect.
+/// Falls back to `check_uri` itself, which a browser will be redirected from in turn.
+pub async fn sign_in_url(check_uri: &str) -> String {
+    match timeout(PROBE_TIMEOUT, probe(check_uri)).await {
+        Ok(Some(Answer::Redirect(location))) => location,
+        _ => check_uri.to_string(),
+    }
+}
+
+async fn probe(uri: &str) -> Option<Answer> {
+    let rest = uri.strip_prefix("http://")?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+    let mut stream = TcpStream::connect(addr).await.ok()?;
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: syd\r\nConnection: close\r\n\r\n", path, authority);
+    stream.write_all(request.as_bytes()).await.ok()?;
+    let mut response = Vec::new();
+    stream.take(MAX_RESPONSE).read_to_end(&mut response).await.ok()?;
+    Some(match answer(&String::from_utf8_lossy(&response))? {
+        Answer::Redirect(location) if location.starts_with('/') => Answer::Redirect(format!("http://{}{}", authority, location)),
+        a => a,
+    })
+}
+
+/// Reads an HTTP response to the check. `None` if it is not one, or a 3xx without a
+/// `Location`.
+fn answer(response: &str) -> Option<Answer> {
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((response, ""));
+    let mut lines = head.lines();
+    let status: u16 = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+    match status {
+        204 => Some(Answer::Online),
+        300..=399 => lines.find_map(|l| {
+            let (key, value) = l.split_once(':')?;
+            key.trim().eq_ignore_ascii_case("location").then(|| Answer::Redirect(value.trim().to_string()))
+        }),
+        _ if body.trim() == ONLINE_TEXT => Some(Answer::Online),
+        _ => Some(Answer::Page),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn redirect() {
+        let r = "HTTP/1.1 302 Found\r\nContent-Length: 0\r\nLocation: http://portal.example/login?x=1\r\n\r\n";
+        assert_eq!(answer(r), Some(Answer::Redirect("http://portal.example/login?x=1".into())));
+    }
+
+    #[test]
+    fn no_content_is_online() {
+        assert_eq!(answer("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n"), Some(Answer::Online));
+        assert_eq!(answer("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nNetworkManager is online\n"), Some(Answer::Online));
+    }
+
+    #[test]
+    fn page_in_place_of_the_check() {
+        let r = "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<html><form action=\"/login\">Accept the terms</form></html>";
+        assert_eq!(answer(r), Some(Answer::Page));
+    }
+
+    #[test]
+    fn not_http() {
+        assert_eq!(answer(""), None);
+        assert_eq!(answer("HTTP/1.1 302 Found\r\n\r\n"), None);
+    }
+
+    /// Serves `response` to one request on a local port and returns the check URL.
+    fn serve(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 512];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = conn.read(&mut buf).unwrap();
+                if n == 0 { break; }
+                request.extend_from_slice(&buf[..n]);
+            }
+            assert!(request.starts_with(b"GET /check HTTP/1.1\r\n"));
+            conn.write_all(response.as_bytes()).unwrap();
+        });
+        format!("http://127.0.0.1:{}/check", port)
+    }
+
+    #[tokio::test]
+    async fn probe_resolves_relative_redirects() {
+        let uri = serve("HTTP/1.1 302 Found\r\nLocation: /login\r\nConnection: close\r\n\r\n");
+        let authority = uri.trim_start_matches("http://").trim_end_matches("/check");
+        assert_eq!(probe(&uri).await, Some(Answer::Redirect(format!("http://{}/login", authority))));
+    }
+
+    #[tokio::test]
+    async fn probe_online() {
+        let uri = serve("HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n");
+        assert_eq!(probe(&uri).await, Some(Answer::Online));
+    }
+}
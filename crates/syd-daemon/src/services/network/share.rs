@@ -0,0 +1,121 @@
+use qrcode::{Color, QrCode};
+use syd_core::{NetworkError, SharedNetwork};
+
+/// Blank border around the code, in modules, as scanners expect.
+const QUIET_ZONE: usize = 4;
+/// Pixels per module in the PNG.
+const SCALE: usize = 8;
+
+/// What a phone needs to join a network.
+pub struct Credentials {
+    pub ssid: String,
+    /// `WPA`, `SAE`, `WEP` or `nopass`.
+    pub security: &'static str,
+    pub password: String,
+    pub hidden: bool,
+}
+
+impl Credentials {
+    /// The `WIFI:` URI understood by phone cameras.
+    pub fn uri(&self) -> String {
+        let mut uri = format!("WIFI:T:{};S:{};", self.security, escape(&self.ssid));
+        if !self.password.is_empty() { uri += &format!("P:{};", escape(&self.password)); }
+        if self.hidden { uri += "H:true;"; }
+        uri + ";"
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') { res.push('\\'); }
+        res.push(c);
+    }
+    res
+}
+
+pub fn share(c: &Credentials) -> Result<SharedNetwork, NetworkError> {
+    let uri = c.uri();
+    let code = QrCode::new(uri.as_bytes()).map_err(|e| NetworkError::Failed(e.to_string()))?;
+    let (size, modules) = modules(&code);
+    Ok(SharedNetwork {
+        ssid: c.ssid.clone(),
+        qr_png: png(size, &modules)?,
+        qr_text: text(size, &modules),
+        uri,
+    })
+}
+
+/// The code's modules row by row, quiet zone included; `true` is dark.
+fn modules(code: &QrCode) -> (usize, Vec<bool>) {
+    let w = code.width();
+    let size = w + 2 * QUIET_ZONE;
+    let colors = code.to_colors();
+    let mut res = vec![false; size * size];
+    for y in 0..w {
+        for x in 0..w {
+            res[(y + QUIET_ZONE) * size + x + QUIET_ZONE] = colors[y * w + x] == Color::Dark;
+        }
+    }
+    (size, res)
+}
+
+fn png(size: usize, modules: &[bool]) -> Result<Vec<u8>, NetworkError> {
+    let px = size * SCALE;
+    let mut data = Vec::with_capacity(px * px);
+    for y in 0..px {
+        for x in 0..px {
+            data.push(if modules[(y / SCALE) * size + x / SCALE] { 0 } else { 255 });
+        }
+    }
+    let mut out = Vec::new();
+    let mut enc = png::Encoder::new(&mut out, px as u32, px as u32);
+    enc.set_color(png::ColorType::Grayscale);
+    enc.set_depth(png::BitDepth::Eight);
+    enc.write_header()
+        .and_then(|mut w| w.write_image_data(&data))
+        .map_err(|e| NetworkError::Failed(e.to_string()))?;
+    Ok(out)
+}
+
+fn text(size: usize, modules: &[bool]) -> String {
+    let dark = |x: usize, y: usize| y < size && modules[y * size + x];
+    let mut res = String::new();
+    for y in (0..size).step_by(2) {
+        for x in 0..size {
+            res.push(match (dark(x, y), dark(x, y + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        res.push('\n');
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn creds(ssid: &str, security: &'static str, password: &str, hidden: bool) -> Credentials {
+        Credentials { ssid: ssid.into(), security, password: password.into(), hidden }
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape("Home"), "Home");
+        assert_eq!(escape(r#"a\b;c,d:e"f"#), r#"a\\b\;c\,d\:e\"f"#);
+        assert_eq!(escape("Café 5G"), "Café 5G");
+    }
+
+    #[test]
+    fn builds_wifi_uris() {
+        assert_eq!(creds("Home", "WPA", "hunter22", false).uri(), "WIFI:T:WPA;S:Home;P:hunter22;;");
+        assert_eq!(creds("My;Net", "SAE", "p:w", false).uri(), r"WIFI:T:SAE;S:My\;Net;P:p\:w;;");
+        assert_eq!(creds("Hidden", "WPA", "secret", true).uri(), "WIFI:T:WPA;S:Hidden;P:secret;H:true;;");
+        assert_eq!(creds("Cafe", "nopass", "", false).uri(), "WIFI:T:nopass;S:Cafe;;");
+        assert_eq!(creds("Cafe", "nopass", "", true).uri(), "WIFI:T:nopass;S:Cafe;H:true;;");
+    }
+}
@@ -0,0 +1,191 @@
+use zbus::Connection;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, atomic::{AtomicU32, Ordering}};
+use tokio::sync::Notify;
+use tokio::time::{sleep, Duration, Instant};
+use serde::{Deserialize, Serialize};
+use syd_core::{DataUsage, TrafficRate};
+use super::{backend::Backend, NetworkService};
+use crate::config;
+
+/// How often usage is counted while no client wants live rates.
+const ACCOUNTING_INTERVAL: Duration = Duration::from_secs(10);
+const MIN_INTERVAL_MS: u32 = 250;
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sampling settings and the latest usage totals, shared with `NetworkService`.
+#[derive(Clone, Default)]
+pub struct Traffic {
+    interval_ms: Arc<AtomicU32>,
+    changed: Arc<Notify>,
+    usage: Arc<Mutex<DataUsage>>,
+}
+
+impl Traffic {
+    /// Sets how often `TrafficChanged` is sent; 0 stops it. The last caller wins.
+    pub fn set_interval(&self, ms: u32) {
+        let ms = if ms == 0 { 0 } else { ms.max(MIN_INTERVAL_MS) };
+        self.interval_ms.store(ms, Ordering::Relaxed);
+        self.changed.notify_one();
+    }
+
+    pub fn usage(&self) -> DataUsage { self.usage.lock().unwrap().clone() }
+}
+
+/// Usage counters as persisted between runs.
+#[derive(Serialize, Deserialize, Default)]
+struct Saved {
+    day: String,
+    month: String,
+    usage: DataUsage,
+    /// Whether this month's warning has been sent.
+    warned: bool,
+}
+
+impl Saved {
+    fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path).ok().and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) {
+        if let Some(dir) = path.parent() { let _ = std::fs::create_dir_all(dir); }
+        if let Ok(json) = serde_json::to_string(self) {
+            if let Err(e) = std::fs::write(path, json) { eprintln!("Could not save {}: {}", path.display(), e); }
+        }
+    }
+
+    /// Starts new day and month totals when the calendar has moved on.
+    fn roll_over(&mut self) {
+        let now = chrono::Local::now();
+        let (day, month) = (now.format("%Y-%m-%d").to_string(), now.format("%Y-%m").to_string());
+        if self.day != day {
+            self.usage.day_rx = 0;
+            self.usage.day_tx = 0;
+            self.day = day;
+        }
+        if self.month != month {
+            self.usage.month_rx = 0;
+            self.usage.month_tx = 0;
+            self.usage.metered_month = 0;
+            self.warned = false;
+            self.month = month;
+        }
+    }
+}
+
+fn state_file() -> PathBuf { config::state_dir().join("usage.json") }
+
+/// Byte counters per interface, received and transmitted.
+fn read_counters() -> HashMap<String, (u64, u64)> {
+    std::fs::read_to_string("/proc/net/dev").map(|s| parse_net_dev(&s)).unwrap_or_default()
+}
+
+fn parse_net_dev(text: &str) -> HashMap<String, (u64, u64)> {
+    // Two header lines, then `iface: rx_bytes rx_packets ... (8 rx fields) tx_bytes ...`.
+    text.lines().skip(2)
+        .filter_map(|line| {
+            let (name, stats) = line.split_once(':')?;
+            let fields: Vec<u64> = stats.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+            Some((name.trim().to_string(), (*fields.first()?, *fields.get(8)?)))
+        })
+        .collect()
+}
+
+/// Only hardware interfaces count towards usage; tunnels and bridges would count traffic twice.
+fn is_physical(iface: &str) -> bool {
+    Path::new("/sys/class/net").join(iface).join("device").exists()
+}
+
+pub async fn monitor(conn: Connection, backend: Backend, traffic: Traffic, warning_threshold: u64) {
+    let iface = conn.object_server().interface::<_, NetworkService>("/org/syd/Network").await.unwrap();
+    let path = state_file();
+    let mut saved = Saved::load(&path);
+    saved.usage.warning_threshold = warning_threshold;
+    saved.roll_over();
+    *traffic.usage.lock().unwrap() = saved.usage.clone();
+
+    let mut metered: HashSet<String> = backend.metered_interfaces().await.unwrap_or_default();
+    let mut prev = read_counters();
+    let mut prev_at = Instant::now();
+    let mut saved_at = Instant::now();
+    loop {
+        let wait = match traffic.interval_ms.load(Ordering::Relaxed) {
+            0 => ACCOUNTING_INTERVAL,
+            ms => Duration::from_millis(ms as u64),
+        };
+        tokio::select! {
+            _ = sleep(wait) => {},
+            _ = traffic.changed.notified() => {},
+        }
+        // Read again: a client that just asked for 0 wants no more rates, not one last batch.
+        let ms = traffic.interval_ms.load(Ordering::Relaxed);
+
+        let curr = read_counters();
+        let secs = prev_at.elapsed().as_secs_f64().max(0.001);
+        prev_at = Instant::now();
+        saved.roll_over();
+        let mut rates = Vec::new();
+        for (name, &(rx, tx)) in &curr {
+            if name == "lo" { continue; }
+            let (prx, ptx) = prev.get(name).copied().unwrap_or((rx, tx));
+            // Counters start over when an interface is recreated.
+            let drx = if rx >= prx { rx - prx } else { rx };
+            let dtx = if tx >= ptx { tx - ptx } else { tx };
+            rates.push(TrafficRate { interface: name.clone(), rx: (drx as f64 / secs) as u64, tx: (dtx as f64 / secs) as u64 });
+
+            if !is_physical(name) { continue; }
+            let u = &mut saved.usage;
+            u.day_rx += drx; u.day_tx += dtx;
+            u.month_rx += drx; u.month_tx += dtx;
+            if metered.contains(name) { u.metered_month += drx + dtx; }
+        }
+        prev = curr;
+        *traffic.usage.lock().unwrap() = saved.usage.clone();
+
+        if ms != 0 {
+            rates.sort_by(|a, b| a.interface.cmp(&b.interface));
+            let _ = NetworkService::traffic_changed(&*iface.get().await, iface.signal_context(), rates).await;
+        }
+        if warning_threshold > 0 && !saved.warned && saved.usage.metered_month >= warning_threshold {
+            saved.warned = true;
+            let _ = NetworkService::usage_warning(&*iface.get().await, iface.signal_context(), saved.usage.clone()).await;
+        }
+        if saved_at.elapsed() >= SAVE_INTERVAL {
+            saved.save(&path);
+            saved_at = Instant::now();
+            metered = backend.metered_interfaces().await.unwrap_or(metered);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Recorded from a laptop with Wi-Fi, a docked Ethernet port and a WireGuard tunnel.
+    const NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 18473612   91245    0    0    0     0          0         0 18473612   91245    0    0    0     0       0          0
+enp0s31f6:       0       0    0    0    0     0          0         0        0       0    0    0    0     0       0          0
+wlp0s20f3: 2378465123 1893456    0 1204    0     0          0     12873 184736521  812345    0    0    0     0       0          0
+   wg0:  5129384   23411    0    0    0     0          0         0  1923845   19422    0   12    0     0       0          0
+";
+
+    #[test]
+    fn reads_rx_and_tx_bytes() {
+        let counters = parse_net_dev(NET_DEV);
+        assert_eq!(counters.len(), 4);
+        assert_eq!(counters["lo"], (18473612, 18473612));
+        assert_eq!(counters["enp0s31f6"], (0, 0));
+        assert_eq!(counters["wlp0s20f3"], (2378465123, 184736521));
+        assert_eq!(counters["wg0"], (5129384, 1923845));
+    }
+
+    #[test]
+    fn skips_short_lines() {
+        let counters = parse_net_dev("Inter-|\n face |\n  eth0: 1 2 3\n");
+        assert!(counters.is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+use zbus::zvariant::Value;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use syd_core::{NotifData, Urgency};
+use crate::config;
+
+/// Where pixels sent with `image-data` are saved. Emptied when the daemon starts.
+pub fn image_dir() -> PathBuf { config::cache_dir().join("notifications") }
+
+/// Fills in what `hints` and `app_icon` say about `n`. Unknown hints are ignored.
+pub fn apply(n: &mut NotifData, app_icon: String, hints: &HashMap<String, Value<'_>>) {
+    // Older versions of the spec used underscores, and some apps still do.
+    let get = |key: &str| hints.get(key).or_else(|| hints.get(&key.replace('-', "_"))).map(inner);
+    let text = |key: &str| get(key).and_then(|v| match v { Value::Str(s) => Some(s.to_string()), _ => None }).unwrap_or_default();
+    let flag = |key: &str| get(key).and_then(int).is_some_and(|i| i != 0);
+
+    n.urgency = match get("urgency").and_then(int) {
+        Some(0) => Urgency::Low,
+        Some(2) => Urgency::Critical,
+        _ => Urgency::Normal,
+    };
+    n.category = text("category");
+    n.desktop_entry = text("desktop-entry").trim_end_matches(".desktop").to_string();
+    n.transient = flag("transient");
+    n.resident = flag("resident");
+    n.sound_file = text("sound-file");
+    n.suppress_sound = flag("suppress-sound");
+    n.x = get("x").and_then(int).map_or(-1, |x| x as i32);
+    n.y = get("y").and_then(int).map_or(-1, |y| y as i32);
+    n.app_icon = file_or_icon(&app_icon);
+    // The spec's order: inline pixels, then a path, then the app icon, which shells show anyway.
+    n.image = get("image-data").or_else(|| get("icon_data")).and_then(|v| save_image(v, &image_dir()))
+        .unwrap_or_else(|| file_or_icon(&text("image-path")));
+}
+
+/// Variants may come wrapped in another variant.
+fn inner<'a>(v: &'a Value<'a>) -> &'a Value<'a> {
+    match v { Value::Value(b) => inner(b), v => v }
+}
+
+/// Numbers and booleans alike, as apps disagree on the types of some hints.
+fn int(v: &Value<'_>) -> Option<i64> {
+    Some(match *v {
+        Value::Bool(b) => b as i64,
+        Value::U8(i) => i.into(),
+        Value::I16(i) => i.into(),
+        Value::U16(i) => i.into(),
+        Value::I32(i) => i.into(),
+        Value::U32(i) => i.into(),
+        Value::I64(i) => i,
+        Value::U64(i) => i as i64,
+        _ => return None,
+    })
+}
+
+/// Icon names stay as they are; `file://` URIs become paths.
+fn file_or_icon(s: &str) -> String {
+    s.strip_prefix("file://").unwrap_or(s).to_string()
+}
+
+/// Writes `image-data`, `(iiibiiay)` with rows of RGB or RGBA bytes, as a PNG in `dir` named
+/// after its contents, so an avatar sent with every message is stored once.
+fn save_image(v: &Value<'_>, dir: &Path) -> Option<String> {
+    let (width, height, rowstride, alpha, bits, channels, data) =
+        <(i32, i32, i32, bool, i32, i32, Vec<u8>)>::try_from(v.try_clone().ok()?).ok()?;
+    let (w, h, stride, ch) = (usize::try_from(width).ok()?, usize::try_from(height).ok()?, usize::try_from(rowstride).ok()?, usize::try_from(channels).ok()?);
+    if bits != 8 || ch != if alpha { 4 } else { 3 } || w == 0 || h == 0 || stride < w * ch { return None; }
+    // The last row need not be padded to the full stride.
+    if data.len() < (h - 1) * stride + w * ch { return None; }
+    let pixels: Vec<u8> = (0..h).flat_map(|y| &data[y * stride..y * stride + w * ch]).copied().collect();
+
+    let mut hasher = DefaultHasher::new();
+    (width, height, alpha, &pixels).hash(&mut hasher);
+    let path = dir.join(format!("{:016x}.png", hasher.finish()));
+    if path.exists() { return Some(path.to_string_lossy().into_owned()); }
+
+    let mut out = Vec::new();
+    let mut enc = png::Encoder::new(&mut out, width as u32, height as u32);
+    enc.set_color(if alpha { png::ColorType::Rgba } else { png::ColorType::Rgb });
+    enc.set_depth(png::BitDepth::Eight);
+    if let Err(e) = enc.write_header().and_then(|mut w| w.write_image_data(&pixels)) {
+        eprintln!("Could not encode a notification image: {}", e);
+        return None;
+    }
+    let _ = std::fs::create_dir_all(dir);
+    if let Err(e) = std::fs::write(&path, out) {
+        eprintln!("Could not save {}: {}", path.display(), e);
+        return None;
+    }
+    Some(path.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(app_icon: &str, hints: Vec<(&str, Value<'static>)>) -> NotifData {
+        let mut n = NotifData::default();
+        apply(&mut n, app_icon.into(), &hints.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+        n
+    }
+
+    #[test]
+    fn underscore_keys() {
+        let n = applied("", vec![("desktop_entry", Value::from("org.gnome.Nautilus.desktop")), ("suppress_sound", Value::from(true))]);
+        assert_eq!(n.desktop_entry, "org.gnome.Nautilus");
+        assert!(n.suppress_sound);
+        // The dashed key wins when both are there.
+        let n = applied("", vec![("sound-file", Value::from("/a.oga")), ("sound_file", Value::from("/b.oga"))]);
+        assert_eq!(n.sound_file, "/a.oga");
+    }
+
+    #[test]
+    fn urgency_as_byte_int_or_variant() {
+        assert_eq!(applied("", vec![("urgency", Value::from(0u8))]).urgency, Urgency::Low);
+        assert_eq!(applied("", vec![("urgency", Value::from(2i32))]).urgency, Urgency::Critical);
+        assert_eq!(applied("", vec![("urgency", Value::Value(Box::new(Value::from(2u8))))]).urgency, Urgency::Critical);
+        assert_eq!(applied("", vec![("urgency", Value::from("critical"))]).urgency, Urgency::Normal);
+        assert_eq!(applied("", vec![]).urgency, Urgency::Normal);
+    }
+
+    #[test]
+    fn flags_and_positions() {
+        let n = applied("", vec![("transient", Value::from(1i32)), ("resident", Value::Value(Box::new(Value::from(true)))), ("x", Value::from(10i32))]);
+        assert!(n.transient && n.resident);
+        assert_eq!((n.x, n.y), (10, -1));
+        assert!(!applied("", vec![("transient", Value::from(false))]).transient);
+    }
+
+    #[test]
+    fn file_uris_become_paths() {
+        let n = applied("file:///usr/share/icons/app.png", vec![("image-path", Value::from("file:///tmp/avatar.png"))]);
+        assert_eq!((n.app_icon.as_str(), n.image.as_str()), ("/usr/share/icons/app.png", "/tmp/avatar.png"));
+        let n = applied("mail-unread", vec![("image-path", Value::from("avatar-default"))]);
+        assert_eq!((n.app_icon.as_str(), n.image.as_str()), ("mail-unread", "avatar-default"));
+    }
+
+    fn image(width: i32, height: i32, rowstride: i32, alpha: bool, channels: i32, data: Vec<u8>) -> Value<'static> {
+        Value::from((width, height, rowstride, alpha, 8i32, channels, data))
+    }
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("syd-hints-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn rejects_bad_images() {
+        let d = dir("bad");
+        // 2x2 RGB needs at least 6 + 6 bytes.
+        assert_eq!(save_image(&image(2, 2, 6, false, 3, vec![0; 11]), &d), None);
+        assert_eq!(save_image(&image(2, 2, 8, true, 3, vec![0; 16]), &d), None);
+        assert_eq!(save_image(&image(2, 2, 4, false, 3, vec![0; 16]), &d), None);
+        assert_eq!(save_image(&image(0, 2, 6, false, 3, vec![]), &d), None);
+        assert!(!d.exists());
+    }
+
+    #[test]
+    fn saves_rows_without_padding() {
+        let d = dir("rows");
+        // Rows padded to 8 bytes, except the last, as GdkPixbuf sends them.
+        let data = vec![1, 2, 3, 4, 5, 6, 0, 0, 7, 8, 9, 10, 11, 12];
+        let path = save_image(&image(2, 2, 8, false, 3, data.clone()), &d).unwrap();
+        assert!(path.starts_with(d.to_str().unwrap()));
+        // Same pixels, same file.
+        assert_eq!(save_image(&image(2, 2, 8, false, 3, data), &d), Some(path.clone()));
+
+        let mut reader = png::Decoder::new(std::fs::File::open(&path).unwrap()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height, info.color_type), (2, 2, png::ColorType::Rgb));
+        assert_eq!(&pixels[..info.buffer_size()], [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        let _ = std::fs::remove_dir_all(&d);
+    }
+}
@@ -0,0 +1,420 @@
+pub mod hints;
+
+use zbus::{interface, message::Header, object_server::SignalContext, Connection};
+use zbus::names::{BusName, UniqueName};
+use syd_core::{CloseReason, NotifAction, NotifData};
+use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use tokio::time::{sleep_until, Duration, Instant};
+use zbus::zvariant::Value;
+use syd_core::Urgency;
+use crate::config::NotificationsConfig;
+
+/// How many notifications the history keeps.
+const HISTORY_LEN: usize = 50;
+
+/// The history, newest first, and which of its notifications are still open; shared by
+/// both interfaces.
+#[derive(Clone, Default)]
+pub struct Store {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    history: Vec<NotifData>,
+    /// Notifications apps may still replace or close and whose actions may be invoked.
+    /// All of them are in `history`.
+    open: HashSet<u32>,
+    last_id: u32,
+    /// The unique bus name of each notification's app, which its actions are sent to.
+    senders: HashMap<u32, UniqueName<'static>>,
+    /// When each notification that times out does so. A replacement gets a deadline of its
+    /// own, so the timer of the one it replaced finds nothing to do.
+    deadlines: HashMap<u32, Instant>,
+}
+
+impl Inner {
+    /// Forgets everything but the history entry of `id`. Returns whether it was open.
+    fn close(&mut self, id: u32) -> bool {
+        self.senders.remove(&id);
+        self.deadlines.remove(&id);
+        self.open.remove(&id)
+    }
+
+    /// Deletes the cached images of `gone` that nothing in the history shows.
+    fn drop_images(&self, gone: &[NotifData]) {
+        let dir = hints::image_dir();
+        for n in gone {
+            if !std::path::Path::new(&n.image).starts_with(&dir) || self.history.iter().any(|o| o.image == n.image) { continue; }
+            let _ = std::fs::remove_file(&n.image);
+        }
+    }
+}
+
+impl Store {
+    /// Stores `n` under a fresh id, or in place of `replaces` while that one is still open,
+    /// to expire at `deadline` if there is one.
+    /// Returns the stored notification, whether it replaced another, and the open
+    /// notifications that fell out of the history to make room, which are closed now.
+    fn add(&self, mut n: NotifData, replaces: u32, sender: Option<UniqueName<'static>>, deadline: Option<Instant>) -> (NotifData, bool, Vec<u32>) {
+        let mut inner = self.inner.lock().unwrap();
+        n.open = true;
+        let replacing = replaces != 0 && inner.open.contains(&replaces);
+        let (replaced, gone, evicted) = match inner.history.iter_mut().find(|o| replacing && o.id == replaces) {
+            Some(old) => {
+                n.id = replaces;
+                (true, vec![std::mem::replace(old, n.clone())], Vec::new())
+            }
+            None => {
+                // 0 means "no notification" to apps, so it is skipped when the counter wraps.
+                inner.last_id = inner.last_id.checked_add(1).unwrap_or(1);
+                n.id = inner.last_id;
+                inner.history.insert(0, n.clone());
+                inner.open.insert(n.id);
+                let keep = HISTORY_LEN.min(inner.history.len());
+                let dropped: Vec<NotifData> = inner.history.drain(keep..).collect();
+                let evicted = dropped.iter().filter(|d| inner.close(d.id)).map(|d| d.id).collect();
+                (false, dropped, evicted)
+            }
+        };
+        match sender {
+            Some(s) => { inner.senders.insert(n.id, s); }
+            None => { inner.senders.remove(&n.id); }
+        }
+        match deadline {
+            Some(at) => { inner.deadlines.insert(n.id, at); }
+            None => { inner.deadlines.remove(&n.id); }
+        }
+        inner.drop_images(&gone);
+        (n, replaced, evicted)
+    }
+
+    /// Closes `id`, which stays in the history unless it is transient. Returns whether it
+    /// was open.
+    fn close(&self, id: u32) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.close(id) { return false; }
+        if let Some(pos) = inner.history.iter().position(|n| n.id == id && n.transient) {
+            let gone = inner.history.remove(pos);
+            inner.drop_images(&[gone]);
+        }
+        true
+    }
+
+    /// Closes `id` and drops it from the history. Returns whether it was open.
+    fn remove(&self, id: u32) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let was_open = inner.close(id);
+        if let Some(pos) = inner.history.iter().position(|n| n.id == id) {
+            let gone = inner.history.remove(pos);
+            inner.drop_images(&[gone]);
+        }
+        was_open
+    }
+
+    /// Closes `id` if it is still the notification that was to expire at `at`.
+    fn expire(&self, id: u32, at: Instant) -> bool {
+        if self.inner.lock().unwrap().deadlines.get(&id) != Some(&at) { return false; }
+        self.close(id)
+    }
+
+    /// Empties the history. Returns the notifications that were open.
+    fn clear(&self) -> Vec<u32> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.senders.clear();
+        inner.deadlines.clear();
+        let open = inner.open.drain().collect();
+        let gone = std::mem::take(&mut inner.history);
+        inner.drop_images(&gone);
+        open
+    }
+
+    /// The history without transient notifications, each marked open or not.
+    fn history(&self) -> Vec<NotifData> {
+        let inner = self.inner.lock().unwrap();
+        inner.history.iter().filter(|n| !n.transient)
+            .map(|n| NotifData { open: inner.open.contains(&n.id), ..n.clone() })
+            .collect()
+    }
+
+    /// An open notification and its sender.
+    fn get(&self, id: u32) -> Option<(NotifData, Option<UniqueName<'static>>)> {
+        let inner = self.inner.lock().unwrap();
+        if !inner.open.contains(&id) { return None; }
+        let n = inner.history.iter().find(|n| n.id == id)?.clone();
+        Some((n, inner.senders.get(&id).cloned()))
+    }
+}
+
+/// Sends `NotificationClosed` to apps and `Closed` to the shell.
+async fn closed(conn: &Connection, id: u32, reason: CloseReason) {
+    let code = match reason {
+        CloseReason::Expired => 1,
+        CloseReason::Dismissed => 2,
+        CloseReason::Closed => 3,
+        CloseReason::Undefined => 4,
+    };
+    if let Ok(iface) = conn.object_server().interface::<_, FdoNotificationServer>("/org/freedesktop/Notifications").await {
+        let _ = FdoNotificationServer::notification_closed(&*iface.get().await, iface.signal_context(), id, code).await;
+    }
+    if let Ok(iface) = conn.object_server().interface::<_, NotificationService>("/org/syd/Notifications").await {
+        let _ = NotificationService::closed(&*iface.get().await, iface.signal_context(), id, reason).await;
+    }
+}
+
+
+/// Closes notification `id` at `at`, unless it is closed or replaced by then. It stays in
+/// the history.
+async fn expire_at(conn: Connection, store: Store, id: u32, at: Instant) {
+    sleep_until(at).await;
+    if !store.expire(id, at) { return; }
+    if let Ok(iface) = conn.object_server().interface::<_, NotificationService>("/org/syd/Notifications").await {
+        let _ = NotificationService::expired(&*iface.get().await, iface.signal_context(), id).await;
+    }
+    closed(&conn, id, CloseReason::Expired).await;
+}
+
+/// Sends `ActivationToken`, if there is one, and `ActionInvoked` to the app that sent
+/// notification `id` only; other apps may have their own notification by that number.
+async fn invoked(conn: &Connection, sender: Option<UniqueName<'static>>, id: u32, key: &str, token: &str) -> zbus::Result<()> {
+    let Ok(iface) = conn.object_server().interface::<_, FdoNotificationServer>("/org/freedesktop/Notifications").await else { return Ok(()) };
+    let mut ctxt = iface.signal_context().clone();
+    if let Some(sender) = sender { ctxt = ctxt.set_destination(BusName::Unique(sender)); }
+    let server = iface.get().await;
+    if !token.is_empty() { FdoNotificationServer::activation_token(&server, &ctxt, id, token).await?; }
+    FdoNotificationServer::action_invoked(&server, &ctxt, id, key).await
+}
+
+/// `actions` comes as key, label, key, label and so on.
+fn actions_from(actions: &[String]) -> (Vec<NotifAction>, bool) {
+    let pairs = actions.chunks_exact(2).map(|p| (p[0].as_str(), p[1].as_str()));
+    let default = pairs.clone().any(|(key, _)| key == "default");
+    let list = pairs.filter(|(key, _)| *key != "default").map(|(key, label)| NotifAction { key: key.into(), label: label.into() }).collect();
+    (list, default)
+}
+
+
+pub struct NotificationService {
+    store: Store,
+}
+
+impl NotificationService {
+    pub fn new() -> (Self, Store) {
+        // Images of the notifications of the last run.
+        let _ = std::fs::remove_dir_all(hints::image_dir());
+        let s = Store::default();
+        (Self { store: s.clone() }, s)
+    }
+
+    async fn invoke(&self, conn: &Connection, id: u32, key: &str, token: &str) -> zbus::fdo::Result<()> {
+        let (n, sender) = self.store.get(id).ok_or_else(|| zbus::fdo::Error::InvalidArgs(format!("No open notification {}", id)))?;
+        let offered = if key == "default" { n.default_action } else { n.actions.iter().any(|a| a.key == key) };
+        if !offered {
+            return Err(zbus::fdo::Error::InvalidArgs(format!("Notification {} has no action {}", id, key)));
+        }
+        invoked(conn, sender, id, key, token).await?;
+        if !n.resident && self.store.close(id) { closed(conn, id, CloseReason::Dismissed).await; }
+        Ok(())
+    }
+}
+
+#[interface(name = "org.syd.Notifications")]
+impl NotificationService {
+    #[zbus(signal)]
+    async fn received(&self, ctxt: &SignalContext<'_>, note: NotifData) -> zbus::Result<()>;
+
+    /// The app sent a new version of a notification that is still open, under the same id.
+    #[zbus(signal)]
+    async fn updated(&self, ctxt: &SignalContext<'_>, note: NotifData) -> zbus::Result<()>;
+
+    /// The popup of notification `id` timed out and should go; the notification stays in the
+    /// history, closed. `Closed` with reason `Expired` follows for everything but the popup:
+    /// shells hide popups on this signal, and on `Closed` only for the other reasons.
+    #[zbus(signal)]
+    async fn expired(&self, ctxt: &SignalContext<'_>, id: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn closed(&self, ctxt: &SignalContext<'_>, id: u32, reason: CloseReason) -> zbus::Result<()>;
+
+    /// The last notifications, open or not, newest first, without transient ones.
+    async fn get_history(&self) -> Vec<NotifData> { self.store.history() }
+
+    /// Dismisses every open notification and empties the history.
+    async fn clear_history(&self, #[zbus(signal_context)] ctxt: SignalContext<'_>) {
+        for id in self.store.clear() { closed(ctxt.connection(), id, CloseReason::Dismissed).await; }
+    }
+
+    /// Dismisses one notification, as when the user clicks its popup away. It stays in
+    /// the history.
+    async fn close(&self, id: u32, #[zbus(signal_context)] ctxt: SignalContext<'_>) {
+        if self.store.close(id) { closed(ctxt.connection(), id, CloseReason::Dismissed).await; }
+    }
+
+    /// Drops one notification from the history, dismissing it if it is open.
+    async fn remove(&self, id: u32, #[zbus(signal_context)] ctxt: SignalContext<'_>) {
+        if self.store.remove(id) { closed(ctxt.connection(), id, CloseReason::Dismissed).await; }
+    }
+
+    /// Tells the app the user picked action `key` of notification `id`, then dismisses it
+    /// unless it is resident.
+    async fn invoke_action(&self, id: u32, key: String, #[zbus(signal_context)] ctxt: SignalContext<'_>) -> zbus::fdo::Result<()> {
+        self.invoke(ctxt.connection(), id, &key, "").await
+    }
+
+    /// `invoke_action`, passing the app an XDG activation token that lets it raise its window.
+    async fn invoke_action_with_token(&self, id: u32, key: String, activation_token: String, #[zbus(signal_context)] ctxt: SignalContext<'_>) -> zbus::fdo::Result<()> {
+        self.invoke(ctxt.connection(), id, &key, &activation_token).await
+    }
+}
+
+
+pub struct FdoNotificationServer {
+    store: Store,
+    config: NotificationsConfig,
+}
+
+impl FdoNotificationServer {
+    pub fn new(store: Store, config: NotificationsConfig) -> Self {
+        Self { store, config }
+    }
+
+    /// How long a notification stays open: `expire_timeout` in ms if the app gave one, else
+    /// the configured default for its urgency. `None` if it stays until dismissed, as critical
+    /// ones always do.
+    fn timeout(&self, urgency: Urgency, expire_timeout: i32) -> Option<Duration> {
+        let ms = match (urgency, expire_timeout) {
+            (Urgency::Critical, _) => 0,
+            (_, ms) if ms >= 0 => ms as u32,
+            (Urgency::Low, _) => self.config.low_timeout_ms,
+            (Urgency::Normal, _) => self.config.normal_timeout_ms,
+        };
+        (ms > 0).then(|| Duration::from_millis(ms.into()))
+    }
+}
+
+#[interface(name = "org.freedesktop.Notifications")]
+impl FdoNotificationServer {
+    fn get_capabilities(&self) -> Vec<String> { vec!["body".into(), "actions".into(), "icon-static".into()] }
+    fn get_server_information(&self) -> (String, String, String, String) { ("Syd".into(), "Syd".into(), "0.1".into(), "1.2".into()) }
+
+    // The spec fixes the arguments; zbus adds the header and signal context.
+    #[allow(clippy::too_many_arguments)]
+    async fn notify(&self, app_name: String, replaces_id: u32, app_icon: String, summary: String, body: String, actions: Vec<String>, hints: HashMap<String, Value<'_>>, expire_timeout: i32, #[zbus(header)] hdr: Header<'_>, #[zbus(signal_context)] ctxt: SignalContext<'_>) -> u32 {
+        let (actions, default_action) = actions_from(&actions);
+        let mut n = NotifData { app_name, summary, body, actions, default_action, ..Default::default() };
+        hints::apply(&mut n, app_icon, &hints);
+        let sender = hdr.sender().map(|s| s.to_owned());
+        let deadline = self.timeout(n.urgency, expire_timeout).map(|t| Instant::now() + t);
+        let (n, replaced, evicted) = self.store.add(n, replaces_id, sender, deadline);
+        let id = n.id;
+
+        let conn = ctxt.connection();
+        // Closed by the server, which is all the spec has for a full history.
+        for old in evicted { closed(conn, old, CloseReason::Closed).await; }
+        if let Some(at) = deadline { tokio::spawn(expire_at(conn.clone(), self.store.clone(), id, at)); }
+        if let Ok(iface) = conn.object_server().interface::<_, NotificationService>("/org/syd/Notifications").await {
+            let _ = if replaced {
+                NotificationService::updated(&*iface.get().await, iface.signal_context(), n).await
+            } else {
+                NotificationService::received(&*iface.get().await, iface.signal_context(), n).await
+            };
+        }
+        id
+    }
+
+    /// Closing one that is already gone is not an error, and sends nothing.
+    async fn close_notification(&self, id: u32, #[zbus(signal_context)] ctxt: SignalContext<'_>) {
+        if self.store.close(id) { closed(ctxt.connection(), id, CloseReason::Closed).await; }
+    }
+
+    #[zbus(signal)]
+    async fn action_invoked(&self, ctxt: &SignalContext<'_>, id: u32, action_key: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn activation_token(&self, ctxt: &SignalContext<'_>, id: u32, activation_token: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn notification_closed(&self, ctxt: &SignalContext<'_>, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(summary: &str) -> NotifData { NotifData { summary: summary.into(), ..Default::default() } }
+
+    #[test]
+    fn ids_skip_zero_when_they_wrap() {
+        let store = Store::default();
+        store.inner.lock().unwrap().last_id = u32::MAX - 1;
+        assert_eq!(store.add(note("a"), 0, None, None).0.id, u32::MAX);
+        assert_eq!(store.add(note("b"), 0, None, None).0.id, 1);
+    }
+
+    #[test]
+    fn replaces_only_open_notifications() {
+        let store = Store::default();
+        let (first, _, _) = store.add(note("a"), 0, None, None);
+        let (n, replaced, _) = store.add(note("b"), first.id, None, None);
+        assert!(replaced);
+        assert_eq!(n.id, first.id);
+        assert_eq!(store.history().len(), 1);
+
+        assert!(store.close(first.id));
+        let (n, replaced, _) = store.add(note("c"), first.id, None, None);
+        assert!(!replaced);
+        assert_ne!(n.id, first.id);
+        let history = store.history();
+        assert_eq!(history.iter().map(|n| (n.summary.as_str(), n.open)).collect::<Vec<_>>(), [("c", true), ("b", false)]);
+    }
+
+    #[test]
+    fn eviction_returns_open_ids() {
+        let store = Store::default();
+        let ids: Vec<u32> = (0..HISTORY_LEN).map(|i| store.add(note(&i.to_string()), 0, None, None).0.id).collect();
+        assert!(store.close(ids[1]));
+        let (_, _, evicted) = store.add(note("new"), 0, None, None);
+        assert_eq!(evicted, [ids[0]]);
+        // The closed one goes quietly.
+        let (_, _, evicted) = store.add(note("newer"), 0, None, None);
+        assert!(evicted.is_empty());
+        assert_eq!(store.history().len(), HISTORY_LEN);
+        assert!(!store.close(ids[0]));
+    }
+
+    #[test]
+    fn transient_ones_leave_on_close() {
+        let store = Store::default();
+        let (n, _, _) = store.add(NotifData { transient: true, ..note("a") }, 0, None, None);
+        assert!(store.history().is_empty());
+        assert_eq!(store.inner.lock().unwrap().history.len(), 1);
+        assert!(store.close(n.id));
+        assert!(store.inner.lock().unwrap().history.is_empty());
+    }
+
+    #[test]
+    fn remove_drops_the_entry() {
+        let store = Store::default();
+        let (a, _, _) = store.add(note("a"), 0, None, None);
+        let (b, _, _) = store.add(note("b"), 0, None, None);
+        assert!(store.close(a.id));
+        assert!(!store.remove(a.id));
+        assert!(store.remove(b.id));
+        assert!(store.history().is_empty());
+    }
+
+    #[test]
+    fn expiry_of_a_replaced_notification_does_nothing() {
+        let store = Store::default();
+        let first = Instant::now() + Duration::from_secs(5);
+        let (n, _, _) = store.add(note("a"), 0, None, Some(first));
+        let second = first + Duration::from_secs(5);
+        store.add(note("b"), n.id, None, Some(second));
+        assert!(!store.expire(n.id, first));
+        assert!(store.get(n.id).is_some());
+        assert!(store.expire(n.id, second));
+        assert!(store.get(n.id).is_none());
+        assert_eq!(store.history()[0].summary, "b");
+    }
+}
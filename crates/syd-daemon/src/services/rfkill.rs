@@ -0,0 +1,225 @@
+use zbus::{interface, object_server::SignalContext, Connection};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use syd_core::{RadioState, RadioType};
+
+const RFKILL_DEV: &str = "/dev/rfkill";
+/// Size of the original `struct rfkill_event`. Newer kernels append fields, which
+/// they leave out when asked for fewer bytes.
+const EVENT_SIZE: usize = 8;
+
+// enum rfkill_type
+const TYPE_ALL: u8 = 0;
+const TYPE_WLAN: u8 = 1;
+const TYPE_BLUETOOTH: u8 = 2;
+const TYPE_WWAN: u8 = 5;
+const TYPE_NFC: u8 = 8;
+
+// enum rfkill_operation
+const OP_ADD: u8 = 0;
+const OP_DEL: u8 = 1;
+const OP_CHANGE: u8 = 2;
+const OP_CHANGE_ALL: u8 = 3;
+
+/// One `struct rfkill_event` as read from or written to `/dev/rfkill`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RfkillEvent {
+    pub idx: u32,
+    pub kind: u8,
+    pub op: u8,
+    pub soft: bool,
+    pub hard: bool,
+}
+
+impl RfkillEvent {
+    /// Decodes the first event in `b`, which may come from the device or a recording.
+    pub fn parse(b: &[u8]) -> Option<Self> {
+        let b = b.get(..EVENT_SIZE)?;
+        Some(Self {
+            idx: u32::from_ne_bytes(b[..4].try_into().ok()?),
+            kind: b[4],
+            op: b[5],
+            soft: b[6] != 0,
+            hard: b[7] != 0,
+        })
+    }
+
+    pub fn to_bytes(self) -> [u8; EVENT_SIZE] {
+        let idx = self.idx.to_ne_bytes();
+        [idx[0], idx[1], idx[2], idx[3], self.kind, self.op, self.soft as u8, self.hard as u8]
+    }
+}
+
+fn radio_type(kind: u8) -> RadioType {
+    match kind {
+        TYPE_WLAN => RadioType::Wlan,
+        TYPE_BLUETOOTH => RadioType::Bluetooth,
+        TYPE_WWAN => RadioType::Wwan,
+        TYPE_NFC => RadioType::Nfc,
+        _ => RadioType::Other,
+    }
+}
+
+fn kind_of(radio: RadioType) -> u8 {
+    match radio {
+        RadioType::Wlan => TYPE_WLAN,
+        RadioType::Bluetooth => TYPE_BLUETOOTH,
+        RadioType::Wwan => TYPE_WWAN,
+        RadioType::Nfc => TYPE_NFC,
+        RadioType::Other => TYPE_ALL,
+    }
+}
+
+/// rfkill switches by index, as last reported by the kernel.
+pub type Switches = Arc<Mutex<HashMap<u32, RfkillEvent>>>;
+
+/// Combined state of every switch of one radio type: soft-blocked only if all of them are,
+/// hard-blocked if any of them is.
+fn radio_state(switches: &HashMap<u32, RfkillEvent>, radio: RadioType) -> Option<RadioState> {
+    let mut matching = switches.values().filter(|e| radio_type(e.kind) == radio).peekable();
+    matching.peek()?;
+    let (mut soft, mut hard) = (true, false);
+    for e in matching {
+        soft &= e.soft;
+        hard |= e.hard;
+    }
+    Some(RadioState { radio, soft_blocked: soft, hard_blocked: hard })
+}
+
+fn radios(switches: &HashMap<u32, RfkillEvent>) -> Vec<RadioState> {
+    [RadioType::Wlan, RadioType::Bluetooth, RadioType::Wwan, RadioType::Nfc, RadioType::Other]
+        .into_iter()
+        .filter_map(|r| radio_state(switches, r))
+        .collect()
+}
+
+/// Airplane mode is on when every radio is blocked, in software or by a hardware switch.
+fn airplane_mode(switches: &HashMap<u32, RfkillEvent>) -> bool {
+    !switches.is_empty() && switches.values().all(|e| e.soft || e.hard)
+}
+
+fn write_event(ev: RfkillEvent) -> zbus::fdo::Result<()> {
+    std::fs::OpenOptions::new().write(true).open(RFKILL_DEV)
+        .and_then(|mut f| f.write_all(&ev.to_bytes()))
+        .map_err(|e| zbus::fdo::Error::Failed(format!("{}: {}", RFKILL_DEV, e)))
+}
+
+/// Soft-blocks or unblocks every switch of one radio type.
+pub fn set_blocked(radio: RadioType, blocked: bool) -> zbus::fdo::Result<()> {
+    write_event(RfkillEvent { idx: 0, kind: kind_of(radio), op: OP_CHANGE_ALL, soft: blocked, hard: false })
+}
+
+pub struct RfkillService { switches: Switches }
+impl RfkillService {
+    pub fn new() -> (Self, Switches) {
+        let s = Switches::default();
+        (Self { switches: s.clone() }, s)
+    }
+}
+
+#[interface(name = "org.syd.Rfkill")]
+impl RfkillService {
+    async fn get_radios(&self) -> Vec<RadioState> { radios(&self.switches.lock().unwrap()) }
+
+    async fn get_airplane_mode(&self) -> bool { airplane_mode(&self.switches.lock().unwrap()) }
+
+    /// Soft-blocks or unblocks every radio. Hardware switches stay as they are.
+    async fn set_airplane_mode(&self, enabled: bool) -> zbus::fdo::Result<()> {
+        write_event(RfkillEvent { idx: 0, kind: TYPE_ALL, op: OP_CHANGE_ALL, soft: enabled, hard: false })
+    }
+
+    /// Soft-blocks or unblocks every switch of one radio type.
+    async fn set_radio_blocked(&self, radio: RadioType, blocked: bool) -> zbus::fdo::Result<()> {
+        if radio == RadioType::Other {
+            return Err(zbus::fdo::Error::InvalidArgs("Pick a specific radio, or use set_airplane_mode".into()));
+        }
+        set_blocked(radio, blocked)
+    }
+
+    #[zbus(signal)] async fn radio_changed(&self, ctxt: &SignalContext<'_>, state: RadioState) -> zbus::Result<()>;
+    #[zbus(signal)] async fn airplane_mode_changed(&self, ctxt: &SignalContext<'_>, enabled: bool) -> zbus::Result<()>;
+}
+
+/// Reads events until the device goes away. Opening `/dev/rfkill` first replays an
+/// `OP_ADD` for every existing switch, so this also picks up the initial state.
+fn read_events(tx: mpsc::UnboundedSender<RfkillEvent>) {
+    let mut f = match std::fs::File::open(RFKILL_DEV) {
+        Ok(f) => f,
+        Err(e) => { eprintln!("{}: {}", RFKILL_DEV, e); return; }
+    };
+    let mut buf = [0u8; EVENT_SIZE];
+    while f.read_exact(&mut buf).is_ok() {
+        if let Some(ev) = RfkillEvent::parse(&buf) {
+            if tx.send(ev).is_err() { return; }
+        }
+    }
+}
+
+pub async fn monitor(conn: Connection, switches: Switches) {
+    let iface = conn.object_server().interface::<_, RfkillService>("/org/syd/Rfkill").await.unwrap();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::task::spawn_blocking(move || read_events(tx));
+
+    let mut airplane = false;
+    while let Some(ev) = rx.recv().await {
+        let radio = radio_type(ev.kind);
+        let (before, after, now_airplane) = {
+            let mut s = switches.lock().unwrap();
+            let before = radio_state(&s, radio);
+            match ev.op {
+                OP_ADD | OP_CHANGE => { s.insert(ev.idx, ev); }
+                OP_DEL => { s.remove(&ev.idx); }
+                _ => {}
+            }
+            (before, radio_state(&s, radio), airplane_mode(&s))
+        };
+        // Hardware switch flips arrive as OP_CHANGE with `hard` set, like any other change.
+        if let Some(state) = after.filter(|a| before.as_ref() != Some(a)) {
+            let _ = RfkillService::radio_changed(&*iface.get().await, iface.signal_context(), state).await;
+        }
+        if now_airplane != airplane {
+            airplane = now_airplane;
+            let _ = RfkillService::airplane_mode_changed(&*iface.get().await, iface.signal_context(), airplane).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Read from /dev/rfkill on x86_64: idx, type, op, soft, hard, then on kernels since
+    // 5.11 a `hard_block_reasons` byte when the buffer has room for it.
+    const ADD_WLAN: [u8; 8] = [0, 0, 0, 0, 1, 0, 0, 0];
+    const ADD_BLUETOOTH: [u8; 9] = [1, 0, 0, 0, 2, 0, 1, 0, 0];
+    const CHANGE_HARD: [u8; 8] = [0, 0, 0, 0, 1, 2, 0, 1];
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn parses_added_switches() {
+        assert_eq!(RfkillEvent::parse(&ADD_WLAN), Some(RfkillEvent { idx: 0, kind: TYPE_WLAN, op: OP_ADD, soft: false, hard: false }));
+        assert_eq!(RfkillEvent::parse(&ADD_BLUETOOTH), Some(RfkillEvent { idx: 1, kind: TYPE_BLUETOOTH, op: OP_ADD, soft: true, hard: false }));
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn parses_hard_block() {
+        let e = RfkillEvent::parse(&CHANGE_HARD).unwrap();
+        assert_eq!((e.op, e.soft, e.hard), (OP_CHANGE, false, true));
+        assert_eq!(radio_type(e.kind), RadioType::Wlan);
+    }
+
+    #[test]
+    fn short_buffer_is_no_event() {
+        assert_eq!(RfkillEvent::parse(&ADD_WLAN[..EVENT_SIZE - 1]), None);
+        assert_eq!(RfkillEvent::parse(&[]), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let e = RfkillEvent { idx: 0x0102_0304, kind: TYPE_WWAN, op: OP_CHANGE_ALL, soft: true, hard: true };
+        assert_eq!(RfkillEvent::parse(&e.to_bytes()), Some(e));
+    }
+}
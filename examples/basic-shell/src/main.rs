@@ -8,7 +8,8 @@ use std::time::Duration;
 use futures::StreamExt;
 use std::cell::RefCell;
 use std::rc::Rc;
-use syd_core::StatusNotifierItemProxy;
+use std::collections::HashMap;
+use syd_core::{AccessTech, BluetoothError, BtAdapter, BtDevice, CloseReason, ConnectStage, ConnectionType, DeviceState, DeviceType, DisconnectReason, EapMethod, HotspotState, HotspotStatus, RadioState, RadioType, KeyMgmt, Modem, NetDevice, NetworkError, NotifData, NetworkStatus, PairingKind, PairingRequest, SecretRequest, SimLock, StatusNotifierItemProxy, TransferRequest, TransferStatus, Urgency, VpnProfile, VpnState, WifiBand, WifiNet, WifiSettings};
 
 fn main() {
     let rt = tokio::runtime::Runtime::new().unwrap();
@@ -37,12 +38,13 @@ fn build_ui(app: &Application) {
 
 fn setup_full_ui(c: &GtkBox, ov: &gtk4::Overlay, win: &ApplicationWindow, syd: Arc<Syd>) {
     
-    let (toast, t_lbl) = create_toast();
-    ov.add_overlay(&toast);
+    let toast = create_toast(syd.clone());
+    ov.add_overlay(&toast.revealer);
 
     
     let head = GtkBox::new(Orientation::Horizontal, 10);
     let (user_lbl, bat_lbl) = create_header_labels();
+    let speed_lbl = Label::new(None); speed_lbl.add_css_class("caption");
     
     let notif_btn = Button::with_label("🔔");
     let notif_pop = create_notif_history(syd.clone());
@@ -50,6 +52,7 @@ fn setup_full_ui(c: &GtkBox, ov: &gtk4::Overlay, win: &ApplicationWindow, syd: A
     notif_btn.connect_clicked(move |_| notif_pop.popup());
     
     head.append(&user_lbl);
+    head.append(&speed_lbl);
     head.append(&bat_lbl);
     head.append(&notif_btn);
     c.append(&head);
@@ -61,8 +64,18 @@ fn setup_full_ui(c: &GtkBox, ov: &gtk4::Overlay, win: &ApplicationWindow, syd: A
     c.append(&gtk4::Separator::new(Orientation::Horizontal));
 
     
-    let (net_box, wifi_sw, bt_sw) = create_net_bt_row(win, syd.clone());
+    let (net_box, wifi_btn, wifi_list, wifi_status, wifi_sw, hotspot_sw, bt_sw, bt_list, bt_adapters) = create_net_bt_row(syd.clone());
+    let bt_bat = Label::new(None); bt_bat.add_css_class("caption"); net_box.append(&bt_bat);
+    let bt_xfer = Label::new(None); bt_xfer.add_css_class("caption"); net_box.append(&bt_xfer);
     c.append(&net_box);
+    let (air_box, air_sw) = create_airplane_row(syd.clone());
+    c.append(&air_box);
+    let dev_lbl = Label::new(None); dev_lbl.add_css_class("caption"); dev_lbl.set_halign(Align::Start);
+    c.append(&dev_lbl);
+    let (vpn_box, vpn_list) = create_vpn_section();
+    c.append(&vpn_box);
+    let (modem_box, modem_list) = create_modem_section();
+    c.append(&modem_box);
     c.append(&gtk4::Separator::new(Orientation::Horizontal));
 
     
@@ -86,15 +99,31 @@ fn setup_full_ui(c: &GtkBox, ov: &gtk4::Overlay, win: &ApplicationWindow, syd: A
 
     
     let is_playing = Rc::new(RefCell::new(false));
+    let prompts: Rc<RefCell<HashMap<u32, gtk4::Window>>> = Rc::new(RefCell::new(HashMap::new()));
+    // Transfer requests are numbered apart from pairing and secrets requests.
+    let transfer_prompts: Rc<RefCell<HashMap<u32, gtk4::Window>>> = Rc::new(RefCell::new(HashMap::new()));
+    let devices: Rc<RefCell<HashMap<String, NetDevice>>> = Rc::new(RefCell::new(HashMap::new()));
 
-    glib::MainContext::default().spawn_local(clone!(@weak m_seek, @weak m_time, @strong is_playing, @weak m_lbl, @weak tray_box => async move {
+    let win = win.clone();
+    glib::MainContext::default().spawn_local(clone!(@weak m_seek, @weak m_time, @strong is_playing, @weak m_lbl, @weak tray_box, @weak win, @weak vpn_box, @weak vpn_list, @weak modem_box, @weak modem_list, @weak dev_lbl, @weak bt_bat, @weak bt_xfer => async move {
         
         if let Ok(u) = syd.system.get_user().await { user_lbl.set_label(&format!("Hello, {}", u)); }
         if let Ok((p, s)) = syd.system.get_battery().await { bat_lbl.set_label(&format!("{}% {}", p, s)); }
         if let Ok(v) = syd.audio.get_volume().await { v_scale.set_value(v as f64); }
         if let Ok(v) = syd.brightness.get_brightness().await { b_scale.set_value(v as f64); }
-        if let Ok(st) = syd.network.get_state().await { wifi_sw.set_active(st == "Connected"); }
+        if let Ok(st) = syd.network.get_state().await { show_net_status(&wifi_btn, &st); }
+        if let Ok(devs) = syd.network.get_devices().await {
+            devices.borrow_mut().extend(devs.into_iter().map(|d| (d.interface.clone(), d)));
+            show_devices(&wifi_sw, &dev_lbl, &devices.borrow());
+        }
         if let Ok(p) = syd.bluetooth.get_power().await { bt_sw.set_active(p); }
+        if let Ok(devs) = syd.bluetooth.get_devices().await { show_bt_batteries(&bt_bat, &devs); }
+        if let Ok(h) = syd.network.get_hotspot().await { show_hotspot(&hotspot_sw, &h); }
+        let _ = syd.network.set_traffic_interval(1000).await;
+        if let Ok(on) = syd.rfkill.get_airplane_mode().await { air_sw.set_state(on); }
+        for r in syd.rfkill.get_radios().await.unwrap_or_default() { show_radio(&wifi_sw, &bt_sw, &r); }
+        if let Ok(vpns) = syd.network.list_vpns().await { fill_vpn_list(&vpn_box, &vpn_list, vpns, syd.clone()); }
+        if let Ok(modems) = syd.network.list_modems().await { fill_modem_list(&modem_box, &modem_list, modems, syd.clone(), &win); }
 
         if let Ok((status, title, artist)) = syd.media.get_metadata().await {
              let icon = if status == "Playing" { "🎵" } else { "⏸" };
@@ -143,14 +172,114 @@ fn setup_full_ui(c: &GtkBox, ov: &gtk4::Overlay, win: &ApplicationWindow, syd: A
                     m_lbl.set_label(&format!("{} {} - {}", icon, title, artist));
                 },
                 SydEvent::Battery(p, s) => bat_lbl.set_label(&format!("{}% {}", p, s)),
-                SydEvent::NetworkState(s) => wifi_sw.set_active(s == "Connected"),
+                SydEvent::NetworkState(st) => show_net_status(&wifi_btn, &st),
+                SydEvent::Hotspot(h) => show_hotspot(&hotspot_sw, &h),
+                SydEvent::Traffic(rates) => {
+                    // Sum real links only; tunnels carry the same bytes again.
+                    let devs = devices.borrow();
+                    let (rx, tx) = rates.iter()
+                        .filter(|r| devs.get(&r.interface).is_some_and(|d| matches!(d.device_type, DeviceType::Ethernet | DeviceType::Wifi | DeviceType::Modem)))
+                        .fold((0, 0), |(rx, tx), r| (rx + r.rx, tx + r.tx));
+                    speed_lbl.set_label(&format!("↓{} ↑{}", fmt_bytes(rx), fmt_bytes(tx)));
+                },
+                SydEvent::Radio(r) => show_radio(&wifi_sw, &bt_sw, &r),
+                SydEvent::AirplaneMode(on) => air_sw.set_state(on),
+                SydEvent::CaptivePortal(url) => show_portal(&win, &url),
+                SydEvent::UsageWarning(u) => {
+                    toast.show(&format!("Metered data used this month: {}", fmt_bytes(u.metered_month)));
+                },
+                SydEvent::NetDevice(d) => {
+                    devices.borrow_mut().insert(d.interface.clone(), d);
+                    show_devices(&wifi_sw, &dev_lbl, &devices.borrow());
+                },
+                SydEvent::NetDeviceRemoved(name) => {
+                    devices.borrow_mut().remove(&name);
+                    show_devices(&wifi_sw, &dev_lbl, &devices.borrow());
+                },
+                SydEvent::AccessPoints(nets) => fill_wifi_list(&wifi_list, nets, syd.clone(), &wifi_status),
+                SydEvent::ConnectProgress { ssid, stage } => wifi_status.set_label(&match stage {
+                    ConnectStage::Connected => format!("Connected to {}", ssid),
+                    ConnectStage::Failed => String::new(),
+                    ConnectStage::GettingIp => format!("{}: getting IP address…", ssid),
+                    st => format!("{}: {:?}…", ssid, st),
+                }),
+                SydEvent::SecretsRequested(req) => {
+                    let s = syd.clone(); let w = win.clone(); let prompts = prompts.clone();
+                    glib::MainContext::default().spawn_local(async move {
+                        match prompt_secrets(&w, &req, &prompts).await {
+                            Some(secrets) => { let _ = s.network.provide_secrets(req.id, secrets).await; }
+                            None => { let _ = s.network.cancel_secrets(req.id).await; }
+                        }
+                    });
+                },
+                SydEvent::VpnState(_) => {
+                    // Rebuild the rows rather than flipping switches, which would re-trigger them.
+                    if let Ok(vpns) = syd.network.list_vpns().await { fill_vpn_list(&vpn_box, &vpn_list, vpns, syd.clone()); }
+                },
+                SydEvent::Modem(_) | SydEvent::ModemRemoved(_) => {
+                    if let Ok(modems) = syd.network.list_modems().await { fill_modem_list(&modem_box, &modem_list, modems, syd.clone(), &win); }
+                },
+                SydEvent::PairingRequested(req) => {
+                    // DisplayPasskey repeats as digits are typed on the device; keep the open prompt.
+                    if prompts.borrow().contains_key(&req.id) { continue; }
+                    let s = syd.clone(); let w = win.clone(); let prompts = prompts.clone();
+                    glib::MainContext::default().spawn_local(async move {
+                        let (accept, value) = match prompt_pairing(&w, &req, &prompts).await { Some(v) => (true, v), None => (false, String::new()) };
+                        let _ = s.bluetooth.agent_reply(req.id, accept, value).await;
+                    });
+                },
+                SydEvent::TransferRequested(req) => {
+                    let s = syd.clone(); let w = win.clone(); let prompts = transfer_prompts.clone();
+                    glib::MainContext::default().spawn_local(async move {
+                        let accept = prompt_transfer(&w, &req, &prompts).await;
+                        let _ = s.bt_transfer.agent_reply(req.id, accept).await;
+                    });
+                },
+                SydEvent::TransferRequestCancelled(id) => if let Some(d) = transfer_prompts.borrow_mut().remove(&id) { d.close(); },
+                SydEvent::Transfer(t) => {
+                    let pct = (t.transferred * 100).checked_div(t.size).unwrap_or(0);
+                    bt_xfer.set_label(&format!("{} {} {}%", if t.incoming { "Receiving" } else { "Sending" }, t.name, pct));
+                },
+                SydEvent::TransferFinished(t) => {
+                    bt_xfer.set_label("");
+                    toast.show(&match (t.status, t.incoming) {
+                        (TransferStatus::Complete, true) => format!("Saved {}", t.filename),
+                        (TransferStatus::Complete, false) => format!("Sent {}", t.name),
+                        (_, true) => format!("Could not receive {}", t.name),
+                        (_, false) => format!("Could not send {}", t.name),
+                    });
+                },
+                SydEvent::PairingCancelled(id) => if let Some(d) = prompts.borrow_mut().remove(&id) { d.close(); },
+                SydEvent::SecretsCancelled(id) => if let Some(d) = prompts.borrow_mut().remove(&id) { d.close(); },
                 SydEvent::BtPower(p) => bt_sw.set_active(p),
-                SydEvent::Notification(n) => {
-                    t_lbl.set_label(&format!("{}: {}", n.app_name, n.summary));
-                    toast.set_reveal_child(true);
-                    let t = toast.clone();
-                    glib::timeout_add_seconds_local(3, move || { t.set_reveal_child(false); glib::ControlFlow::Break });
+                SydEvent::BtAdapter(_) | SydEvent::BtAdapterRemoved(_) => {
+                    if let Ok(adapters) = syd.bluetooth.get_adapters().await { fill_bt_adapters(&bt_adapters, adapters, syd.clone()); }
+                },
+                SydEvent::BtConnected(_) => {},
+                SydEvent::BtDisconnected(mac, reason) => {
+                    // Leaving on purpose or going to sleep needs no mention.
+                    let why = match reason {
+                        DisconnectReason::Timeout => "out of range",
+                        DisconnectReason::Authentication => "authentication failed",
+                        DisconnectReason::Remote => "disconnected by the device",
+                        _ => continue,
+                    };
+                    let name = syd.bluetooth.get_devices().await.ok()
+                        .and_then(|devs| devs.into_iter().find(|d| d.mac == mac)).map_or(mac, |d| d.alias);
+                    toast.show(&format!("{}: {}", name, why));
+                },
+                SydEvent::BtDeviceAdded(_) | SydEvent::BtDeviceRemoved(_) | SydEvent::BtDeviceChanged(_) => {
+                    if let Ok(devs) = syd.bluetooth.get_devices().await {
+                        show_bt_batteries(&bt_bat, &devs);
+                        if bt_list.is_mapped() { fill_bt_list(&bt_list, devs, syd.clone()); }
+                    }
                 },
+                SydEvent::Notification(n) => toast.show_notification(&n),
+                // A volume OSD or download progress; refresh the toast if it is still up.
+                SydEvent::NotificationUpdated(n) => if toast.id.get() == n.id && toast.revealer.reveals_child() { toast.set_notification(&n); },
+                SydEvent::NotificationExpired(id) => if toast.id.get() == id { toast.revealer.set_reveal_child(false); },
+                // Expiry has its own signal, just before this one.
+                SydEvent::NotificationClosed(id, reason) => if reason != CloseReason::Expired && toast.id.get() == id { toast.revealer.set_reveal_child(false); },
                 SydEvent::TrayItem(service) => {
                     
                     spawn_tray_item(service, tray_box.clone());
@@ -284,6 +413,14 @@ fn fmt_time(secs: f64) -> String {
     let s = secs as u64;
     format!("{:02}:{:02}", s / 60, s % 60)
 }
+fn fmt_bytes(b: u64) -> String {
+    match b {
+        0..=1023 => format!("{} B", b),
+        1024..=1048575 => format!("{:.0} KB", b as f64 / 1024.0),
+        1048576..=1073741823 => format!("{:.1} MB", b as f64 / 1048576.0),
+        _ => format!("{:.1} GB", b as f64 / 1073741824.0),
+    }
+}
 fn create_header_labels() -> (Label, Label) {
     let u = Label::new(Some("User")); u.set_hexpand(true); u.set_halign(Align::Start); u.add_css_class("title-3");
     let b = Label::new(Some("Bat: --%"));
@@ -319,7 +456,30 @@ fn create_notif_history(syd: Arc<Syd>) -> Popover {
                     lbl_title.set_halign(Align::Start);
                     let lbl_sum = Label::new(Some(&n.summary)); lbl_sum.set_halign(Align::Start); lbl_sum.set_wrap(true);
                     let lbl_body = Label::new(Some(&n.body)); lbl_body.set_halign(Align::Start); lbl_body.set_wrap(true); lbl_body.add_css_class("caption");
-                    row.append(&lbl_title); row.append(&lbl_sum); row.append(&lbl_body);
+                    let top = GtkBox::new(Orientation::Horizontal, 5);
+                    lbl_title.set_hexpand(true); top.append(&lbl_title);
+                    let dismiss = Button::from_icon_name("window-close-symbolic"); dismiss.add_css_class("flat");
+                    let s2 = s.clone(); let l2 = l.clone(); let r = row.clone(); let id = n.id;
+                    dismiss.connect_clicked(move |_| {
+                        l2.remove(&r.parent().unwrap_or_else(|| r.clone().upcast()));
+                        let s = s2.clone(); glib::MainContext::default().spawn_local(async move { let _ = s.notif.remove(id).await; });
+                    });
+                    top.append(&dismiss);
+                    row.append(&top); row.append(&lbl_sum); row.append(&lbl_body);
+                    if n.open && !n.actions.is_empty() {
+                        let acts = GtkBox::new(Orientation::Horizontal, 5);
+                        for a in &n.actions {
+                            let b = Button::with_label(&a.label);
+                            let s2 = s.clone(); let l2 = l.clone(); let r = row.clone(); let key = a.key.clone();
+                            b.connect_clicked(move |_| {
+                                l2.remove(&r.parent().unwrap_or_else(|| r.clone().upcast()));
+                                let s = s2.clone(); let key = key.clone();
+                                glib::MainContext::default().spawn_local(async move { let _ = s.notif.invoke_action(id, key).await; });
+                            });
+                            acts.append(&b);
+                        }
+                        row.append(&acts);
+                    }
                     row.append(&gtk4::Separator::new(Orientation::Horizontal));
                     l.append(&row);
                 }
@@ -328,7 +488,7 @@ fn create_notif_history(syd: Arc<Syd>) -> Popover {
     });
     p
 }
-fn create_net_bt_row(win: &ApplicationWindow, syd: Arc<Syd>) -> (GtkBox, Switch, Switch) {
+fn create_net_bt_row(syd: Arc<Syd>) -> (GtkBox, Button, ListBox, Label, Switch, Switch, Switch, ListBox, GtkBox) {
     let b = GtkBox::new(Orientation::Horizontal, 10);
     let wifi_btn = Button::with_label("Wi-Fi >");
     let wifi_pop = Popover::builder().child(&GtkBox::new(Orientation::Vertical, 5)).build();
@@ -340,44 +500,57 @@ fn create_net_bt_row(win: &ApplicationWindow, syd: Arc<Syd>) -> (GtkBox, Switch,
     sw.connect_state_set(move |_, st| { let s=s_sw.clone(); glib::MainContext::default().spawn_local(async move{let _=s.network.set_wifi(st).await;}); glib::Propagation::Proceed });
     head.append(&sw);
     let pb = wifi_pop.child().unwrap().downcast::<GtkBox>().unwrap(); pb.append(&head); pb.append(&scroll);
-    let s = syd.clone(); let wl = w_list.clone(); let w_win = win.clone();
+    let w_status = Label::new(None); w_status.set_wrap(true); w_status.add_css_class("caption"); pb.append(&w_status);
+    let other = Button::with_label("Other network…"); pb.append(&other);
+    let hs_row = GtkBox::new(Orientation::Horizontal, 5);
+    let hs_lbl = Label::new(Some("Hotspot")); hs_lbl.set_hexpand(true); hs_lbl.set_halign(Align::Start); hs_row.append(&hs_lbl);
+    let share = Button::with_label("Share"); share.set_tooltip_text(Some("Show a QR code for the current network")); hs_row.append(&share);
+    let hs_sw = Switch::new(); hs_sw.set_valign(Align::Center); hs_row.append(&hs_sw);
+    pb.append(&hs_row);
+    let s = syd.clone(); let ws = w_status.clone();
+    hs_sw.connect_state_set(move |sw, on| {
+        // set_state() updates the state before emitting, so this skips changes mirrored from the daemon.
+        if on == sw.state() { return glib::Propagation::Proceed; }
+        let s=s.clone(); let ws=ws.clone(); let sw=sw.clone(); let parent = sw.root().and_downcast::<gtk4::Window>();
+        glib::MainContext::default().spawn_local(async move {
+            let res = if !on { s.network.stop_hotspot().await } else {
+                match prompt_hotspot(parent.as_ref()).await {
+                    Some((ssid, pass, band)) => s.network.start_hotspot(ssid, pass, band).await,
+                    None => { sw.set_state(false); return; }
+                }
+            };
+            if let Err(e) = res { ws.set_label(&connect_error_text(&e)); sw.set_state(!on); }
+        });
+        glib::Propagation::Proceed
+    });
+    let s = syd.clone(); let ws = w_status.clone();
+    share.connect_clicked(move |btn| {
+        let s=s.clone(); let ws=ws.clone(); let parent = btn.root().and_downcast::<gtk4::Window>();
+        glib::MainContext::default().spawn_local(async move {
+            match s.network.share_network(String::new()).await {
+                Ok(shared) => show_qr(parent.as_ref(), &shared.ssid, &shared.qr_png),
+                Err(e) => ws.set_label(&connect_error_text(&e)),
+            }
+        });
+    });
+    let s = syd.clone(); let ws = w_status.clone();
+    other.connect_clicked(move |btn| {
+        let s=s.clone(); let ws=ws.clone(); let parent = btn.root().and_downcast::<gtk4::Window>();
+        glib::MainContext::default().spawn_local(async move {
+            if let Some(settings) = prompt_wifi_settings(parent.as_ref()).await {
+                if let Err(e) = s.network.connect_advanced(settings).await { ws.set_label(&connect_error_text(&e)); }
+            }
+        });
+    });
+    let s = syd.clone(); let wl = w_list.clone(); let ws = w_status.clone();
     wifi_btn.connect_clicked(move |_| {
         wifi_pop.popup();
         while let Some(c) = wl.first_child() { wl.remove(&c); }
         wl.append(&Label::new(Some("Scanning...")));
-        let s=s.clone(); let wl=wl.clone(); let w_win=w_win.clone();
+        let s=s.clone(); let wl=wl.clone(); let ws=ws.clone();
         glib::MainContext::default().spawn_local(async move {
-            if let Ok(nets) = s.network.scan().await {
-                while let Some(c) = wl.first_child() { wl.remove(&c); }
-                if nets.is_empty() { wl.append(&Label::new(Some("No networks"))); }
-                for n in nets {
-                    let row = GtkBox::new(Orientation::Horizontal, 5);
-                    let txt = format!("{} ({}%)", n.ssid, n.strength);
-                    let lbl = Label::new(Some(&txt)); lbl.set_hexpand(true); lbl.set_halign(Align::Start); row.append(&lbl);
-                    if n.security != "" { row.append(&Label::new(Some("🔒"))); }
-                    if n.active {
-                        row.append(&Label::new(Some("✅")));
-                        let b = Button::with_label("Disconnect"); let s=s.clone();
-                        b.connect_clicked(move |_| { let s=s.clone(); glib::MainContext::default().spawn_local(async move {let _=s.network.set_wifi(false).await;}); }); 
-                        row.append(&b);
-                    } else {
-                         let b = Button::with_label("Connect"); let s=s.clone(); let ssid=n.ssid.clone(); let w=w_win.clone();
-                         b.connect_clicked(move |_| { let s=s.clone(); let ssid=ssid.clone(); let w=w.clone();
-                             glib::MainContext::default().spawn_local(async move {
-                                 let res = s.network.connect(ssid.clone(), "".into()).await.unwrap_or("Err".into());
-                                 if res == "PASS_REQ" { if let Some(pass) = prompt_pass(&w, &ssid).await { let _ = s.network.connect(ssid, pass).await; } }
-                             });
-                         });
-                         row.append(&b);
-                    }
-                    if n.known {
-                         let b = Button::with_label("Forget"); let s=s.clone(); let ssid=n.ssid.clone();
-                         b.connect_clicked(move |_| { let s=s.clone(); let ssid=ssid.clone(); glib::MainContext::default().spawn_local(async move {let _=s.network.forget(ssid).await;}); });
-                         row.append(&b);
-                    }
-                    wl.append(&row);
-                }
-            }
+            if let Ok(nets) = s.network.scan().await { fill_wifi_list(&wl, nets, s.clone(), &ws); }
+            let _ = s.network.request_scan().await;
         });
     });
     b.append(&wifi_btn);
@@ -391,33 +564,326 @@ fn create_net_bt_row(win: &ApplicationWindow, syd: Arc<Syd>) -> (GtkBox, Switch,
     let sw_bt = Switch::new(); sw_bt.set_valign(Align::Center); let s_bt = syd.clone();
     sw_bt.connect_state_set(move |_, st| { let s=s_bt.clone(); glib::MainContext::default().spawn_local(async move{let _=s.bluetooth.set_power(st).await;}); glib::Propagation::Proceed });
     head_bt.append(&sw_bt);
-    let pb = bt_pop.child().unwrap().downcast::<GtkBox>().unwrap(); pb.append(&head_bt); pb.append(&scroll_bt);
-    let s = syd.clone(); let bl = b_list.clone(); let sw_bt_c = sw_bt.clone();
+    let adapters_bt = GtkBox::new(Orientation::Vertical, 5);
+    let pb = bt_pop.child().unwrap().downcast::<GtkBox>().unwrap(); pb.append(&head_bt); pb.append(&adapters_bt); pb.append(&scroll_bt);
+    let s = syd.clone();
+    bt_pop.connect_closed(move |_| { let s=s.clone(); glib::MainContext::default().spawn_local(async move { let _ = s.bluetooth.stop_discovery().await; }); });
+    let s = syd.clone(); let bl = b_list.clone(); let sw_bt_c = sw_bt.clone(); let ab = adapters_bt.clone();
     bt_btn.connect_clicked(move |_| {
         bt_pop.popup();
         while let Some(c) = bl.first_child() { bl.remove(&c); }
         bl.append(&Label::new(Some("Loading...")));
-        let s=s.clone(); let bl=bl.clone(); let sw_bt=sw_bt_c.clone();
+        let s=s.clone(); let bl=bl.clone(); let sw_bt=sw_bt_c.clone(); let ab=ab.clone();
         glib::MainContext::default().spawn_local(async move {
             if let Ok(p) = s.bluetooth.get_power().await { sw_bt.set_active(p); }
-            if let Ok(devs) = s.bluetooth.get_devices().await {
-                while let Some(c) = bl.first_child() { bl.remove(&c); }
-                if devs.is_empty() { bl.append(&Label::new(Some("No devices"))); }
-                for d in devs {
-                    let row = GtkBox::new(Orientation::Horizontal, 10);
-                    let icon = if d.connected { "🟢" } else { "⚪" };
-                    let lbl = Label::new(Some(&format!("{} {}", icon, d.name))); lbl.set_hexpand(true); lbl.set_halign(Align::Start); row.append(&lbl);
-                    let b = Button::with_label(if d.connected { "Disconnect" } else { "Connect" });
-                    let s=s.clone(); let mac=d.mac.clone(); let do_con = !d.connected;
-                    b.connect_clicked(move |_| { let s=s.clone(); let m=mac.clone(); glib::MainContext::default().spawn_local(async move{let _=s.bluetooth.connect_device(m, do_con).await;}); });
-                    row.append(&b);
-                    bl.append(&row);
-                }
-            }
+            if let Ok(adapters) = s.bluetooth.get_adapters().await { fill_bt_adapters(&ab, adapters, s.clone()); }
+            if let Ok(devs) = s.bluetooth.get_devices().await { fill_bt_list(&bl, devs, s.clone()); }
+            // New devices come in through DeviceAdded; discovery stops by itself.
+            let _ = s.bluetooth.start_discovery().await;
         });
     });
     b.append(&bt_btn);
-    (b, sw, sw_bt)
+    (b, wifi_btn, w_list, w_status, sw, hs_sw, sw_bt, b_list, adapters_bt)
+}
+/// A chooser when there is more than one adapter, and whether the default one is visible
+/// to devices that are searching, e.g. a phone about to pair.
+fn fill_bt_adapters(ab: &GtkBox, adapters: Vec<BtAdapter>, s: Arc<Syd>) {
+    while let Some(c) = ab.first_child() { ab.remove(&c); }
+    let Some(def) = adapters.iter().find(|a| a.default).cloned() else { return };
+    if adapters.len() > 1 {
+        let row = GtkBox::new(Orientation::Horizontal, 10); row.append(&Label::new(Some("Adapter:")));
+        let names: Vec<String> = adapters.iter().map(|a| format!("{} ({})", a.alias, a.name)).collect();
+        let dd = DropDown::from_strings(&names.iter().map(String::as_str).collect::<Vec<_>>());
+        dd.set_selected(adapters.iter().position(|a| a.default).unwrap_or(0) as u32);
+        let s=s.clone();
+        dd.connect_selected_notify(move |d| {
+            let Some(a) = adapters.get(d.selected() as usize) else { return };
+            let s=s.clone(); let name=a.name.clone();
+            glib::MainContext::default().spawn_local(async move { let _=s.bluetooth.set_default_adapter(name).await; });
+        });
+        row.append(&dd); ab.append(&row);
+    }
+    let row = GtkBox::new(Orientation::Horizontal, 10);
+    let lbl = Label::new(Some(&format!("Visible as \"{}\"", def.alias))); lbl.set_hexpand(true); lbl.set_halign(Align::Start); row.append(&lbl);
+    let sw = Switch::new(); sw.set_valign(Align::Center); sw.set_state(def.discoverable);
+    if def.discoverable_timeout > 0 { sw.set_tooltip_text(Some(&format!("Turns off after {} minutes", def.discoverable_timeout.div_ceil(60)))); }
+    sw.connect_state_set(move |sw, on| {
+        if on == sw.state() { return glib::Propagation::Proceed; }
+        let s=s.clone(); let sw=sw.clone();
+        glib::MainContext::default().spawn_local(async move {
+            // A phone can only pair with us while we are both visible and pairable.
+            let res = match s.bluetooth.set_discoverable(String::new(), on).await {
+                Ok(()) if on => s.bluetooth.set_pairable(String::new(), true).await,
+                res => res,
+            };
+            if let Err(e) = res { sw.set_state(!on); sw.set_tooltip_text(Some(&bt_error_text(&e))); }
+        });
+        glib::Propagation::Proceed
+    });
+    row.append(&sw); ab.append(&row);
+}
+/// Battery levels of connected devices, for the bar next to the Bluetooth button.
+fn show_bt_batteries(lbl: &Label, devs: &[BtDevice]) {
+    let levels: Vec<String> = devs.iter().filter(|d| d.connected && d.battery >= 0).map(|d| format!("{} {}%", d.alias, d.battery)).collect();
+    lbl.set_label(&levels.join(" · "));
+}
+fn fill_bt_list(bl: &ListBox, mut devs: Vec<BtDevice>, s: Arc<Syd>) {
+    while let Some(c) = bl.first_child() { bl.remove(&c); }
+    if devs.is_empty() { bl.append(&Label::new(Some("No devices"))); }
+    // Paired devices first, then whatever is nearby, strongest signal first.
+    devs.sort_by_key(|d| (!d.paired, std::cmp::Reverse(if d.rssi == 0 { i16::MIN } else { d.rssi })));
+    for d in devs {
+        let row = GtkBox::new(Orientation::Horizontal, 10);
+        let img = Image::from_icon_name(if d.icon.is_empty() { "bluetooth-symbolic" } else { &d.icon }); row.append(&img);
+        let icon = if d.connected { "🟢" } else { "⚪" };
+        let bat = if d.battery >= 0 { format!(" ({}%)", d.battery) } else { String::new() };
+        let lbl = Label::new(Some(&format!("{} {}{}", icon, d.alias, bat))); lbl.set_hexpand(true); lbl.set_halign(Align::Start); row.append(&lbl);
+        if !d.paired {
+            let b = Button::with_label("Pair");
+            let s=s.clone(); let mac=d.mac.clone();
+            b.connect_clicked(move |_| {
+                let s=s.clone(); let m=mac.clone();
+                glib::MainContext::default().spawn_local(async move { if s.bluetooth.pair(m.clone()).await.is_ok() { let _=s.bluetooth.connect_device(m, true).await; } });
+            });
+            row.append(&b);
+            bl.append(&row);
+            continue;
+        }
+        let b = Button::with_label(if d.connected { "Disconnect" } else { "Connect" });
+        let s1=s.clone(); let mac=d.mac.clone(); let do_con = !d.connected; let l=lbl.clone();
+        // The row is rebuilt from DeviceChanged once the daemon reports the new state; until
+        // then it only shows that something is happening.
+        b.connect_clicked(move |b| {
+            let s=s1.clone(); let m=mac.clone(); let b=b.clone(); let l=l.clone();
+            b.set_sensitive(false); b.set_label(if do_con { "Connecting…" } else { "Disconnecting…" }); l.set_tooltip_text(None);
+            glib::MainContext::default().spawn_local(async move {
+                if let Err(e) = s.bluetooth.connect_device(m, do_con).await {
+                    b.set_sensitive(true); b.set_label(if do_con { "Connect" } else { "Disconnect" });
+                    l.set_tooltip_text(Some(&bt_error_text(&e)));
+                }
+            });
+        });
+        row.append(&b);
+        if d.connected {
+            let send = Button::from_icon_name("document-send-symbolic"); send.set_tooltip_text(Some("Send file…"));
+            let s=s.clone(); let mac=d.mac.clone();
+            send.connect_clicked(move |btn| {
+                let parent = btn.root().and_downcast::<gtk4::Window>();
+                let fc = gtk4::FileChooserNative::new(Some("Send file"), parent.as_ref(), gtk4::FileChooserAction::Open, Some("Send"), None);
+                fc.set_select_multiple(true);
+                let s=s.clone(); let mac=mac.clone();
+                fc.connect_response(move |fc, resp| {
+                    if resp != gtk4::ResponseType::Accept { return; }
+                    let files: Vec<String> = fc.files().iter::<gtk4::gio::File>().flatten()
+                        .filter_map(|f| f.path()).map(|p| p.to_string_lossy().into_owned()).collect();
+                    let s=s.clone(); let m=mac.clone();
+                    glib::MainContext::default().spawn_local(async move { let _=s.bt_transfer.send_files(m, files).await; });
+                });
+                fc.show();
+            });
+            row.append(&send);
+        }
+        let forget = Button::from_icon_name("user-trash-symbolic"); forget.set_tooltip_text(Some("Forget"));
+        let s=s.clone(); let mac=d.mac.clone();
+        forget.connect_clicked(move |_| { let s=s.clone(); let m=mac.clone(); glib::MainContext::default().spawn_local(async move{let _=s.bluetooth.remove(m).await;}); });
+        row.append(&forget);
+        bl.append(&row);
+    }
+}
+fn show_hotspot(sw: &Switch, h: &HotspotStatus) {
+    sw.set_state(matches!(h.state, HotspotState::Starting | HotspotState::Active));
+    sw.set_tooltip_text(if h.state == HotspotState::Active { Some(h.ssid.as_str()) } else { None });
+}
+fn net_icon(st: &NetworkStatus) -> &'static str {
+    match st.connection_type {
+        ConnectionType::Wifi => match st.strength {
+            75.. => "network-wireless-signal-excellent-symbolic",
+            50..=74 => "network-wireless-signal-good-symbolic",
+            25..=49 => "network-wireless-signal-ok-symbolic",
+            _ => "network-wireless-signal-weak-symbolic",
+        },
+        ConnectionType::Ethernet => "network-wired-symbolic",
+        ConnectionType::Vpn => "network-vpn-symbolic",
+        ConnectionType::None => "network-offline-symbolic",
+    }
+}
+fn show_net_status(btn: &Button, st: &NetworkStatus) {
+    let row = GtkBox::new(Orientation::Horizontal, 5);
+    row.append(&Image::from_icon_name(net_icon(st)));
+    let name = if st.ssid.is_empty() { "Wi-Fi" } else { &st.ssid };
+    row.append(&Label::new(Some(&format!("{} >", name))));
+    btn.set_child(Some(&row));
+    let ip = st.ipv4.first().or(st.ipv6.first()).map(|s| s.as_str()).unwrap_or("No address");
+    let vpn = if st.vpn.is_empty() { String::new() } else { format!("\nVPN: {}", st.vpn) };
+    btn.set_tooltip_text(Some(&format!("{:?} via {}\n{}{}", st.state, st.interface, ip, vpn)));
+}
+fn show_devices(sw: &Switch, lbl: &Label, devs: &HashMap<String, NetDevice>) {
+    // NM marks Wi-Fi devices unavailable while the radio is off, whatever else is connected.
+    let wifi: Vec<&NetDevice> = devs.values().filter(|d| d.device_type == DeviceType::Wifi).collect();
+    sw.set_active(wifi.iter().any(|d| !matches!(d.state, DeviceState::Unavailable | DeviceState::Unmanaged)));
+    let mut lines: Vec<String> = devs.values()
+        .filter(|d| d.device_type != DeviceType::Loopback && d.state != DeviceState::Unmanaged)
+        .map(|d| {
+            let speed = if d.speed > 0 { format!(" · {} Mb/s", d.speed) } else { String::new() };
+            format!("{} · {:?} · {:?}{}", d.interface, d.device_type, d.state, speed)
+        })
+        .collect();
+    lines.sort();
+    lbl.set_label(&lines.join("\n"));
+}
+fn create_airplane_row(syd: Arc<Syd>) -> (GtkBox, Switch) {
+    let b = GtkBox::new(Orientation::Horizontal, 5);
+    let l = Label::new(Some("Airplane mode")); l.set_hexpand(true); l.set_halign(Align::Start); b.append(&l);
+    let sw = Switch::new(); sw.set_valign(Align::Center); b.append(&sw);
+    sw.connect_state_set(move |sw, on| {
+        // Changes made through set_state() come from the daemon; only act on the user's.
+        if on == sw.state() { return glib::Propagation::Proceed; }
+        let s = syd.clone(); let sw = sw.clone();
+        glib::MainContext::default().spawn_local(async move {
+            if s.rfkill.set_airplane_mode(on).await.is_err() { sw.set_state(!on); }
+        });
+        glib::Propagation::Proceed
+    });
+    (b, sw)
+}
+fn show_radio(wifi_sw: &Switch, bt_sw: &Switch, r: &RadioState) {
+    let sw = match r.radio { RadioType::Wlan => wifi_sw, RadioType::Bluetooth => bt_sw, _ => return };
+    // Software can't undo a hardware kill switch, so don't offer to.
+    sw.set_sensitive(!r.hard_blocked);
+    sw.set_tooltip_text(if r.hard_blocked { Some("Turned off by a hardware switch") } else { None });
+}
+fn create_vpn_section() -> (GtkBox, ListBox) {
+    let b = GtkBox::new(Orientation::Vertical, 5);
+    let l = Label::new(Some("VPN")); l.set_halign(Align::Start); b.append(&l);
+    let list = ListBox::new(); b.append(&list);
+    b.set_visible(false);
+    (b, list)
+}
+fn fill_vpn_list(section: &GtkBox, vl: &ListBox, vpns: Vec<VpnProfile>, s: Arc<Syd>) {
+    while let Some(c) = vl.first_child() { vl.remove(&c); }
+    section.set_visible(!vpns.is_empty());
+    for v in vpns {
+        let row = GtkBox::new(Orientation::Horizontal, 5);
+        let txt = match v.state {
+            VpnState::Connecting => format!("{} (connecting…)", v.id),
+            VpnState::Disconnecting => format!("{} (disconnecting…)", v.id),
+            VpnState::Failed => format!("{} (failed)", v.id),
+            _ => v.id.clone(),
+        };
+        let lbl = Label::new(Some(&txt)); lbl.set_hexpand(true); lbl.set_halign(Align::Start); lbl.set_tooltip_text(Some(&v.kind)); row.append(&lbl);
+        let sw = Switch::new(); sw.set_valign(Align::Center);
+        sw.set_active(matches!(v.state, VpnState::Connected | VpnState::Connecting));
+        let s=s.clone(); let id = v.uuid.clone();
+        sw.connect_state_set(move |_, on| {
+            let s=s.clone(); let id=id.clone(); let lbl=lbl.clone();
+            glib::MainContext::default().spawn_local(async move {
+                let res = if on { s.network.activate_vpn(id).await } else { s.network.deactivate_vpn(id).await };
+                if let Err(e) = res { lbl.set_tooltip_text(Some(&e.to_string())); }
+            });
+            glib::Propagation::Proceed
+        });
+        row.append(&sw);
+        vl.append(&row);
+    }
+}
+fn create_modem_section() -> (GtkBox, ListBox) {
+    let b = GtkBox::new(Orientation::Vertical, 5);
+    let l = Label::new(Some("Mobile data")); l.set_halign(Align::Start); b.append(&l);
+    let list = ListBox::new(); b.append(&list);
+    b.set_visible(false);
+    (b, list)
+}
+fn fill_modem_list(section: &GtkBox, ml: &ListBox, modems: Vec<Modem>, s: Arc<Syd>, win: &ApplicationWindow) {
+    while let Some(c) = ml.first_child() { ml.remove(&c); }
+    section.set_visible(!modems.is_empty());
+    for m in modems {
+        let row = GtkBox::new(Orientation::Horizontal, 5);
+        let tech = match m.access_tech { AccessTech::Nr5g => "5G", AccessTech::Lte => "LTE", AccessTech::Umts => "3G", AccessTech::Gsm | AccessTech::Cdma => "2G", AccessTech::Unknown => "" };
+        let name = if m.operator.is_empty() { &m.model } else { &m.operator };
+        let txt = format!("{} {} {}%{}", name, tech, m.signal, if m.roaming { " (roaming)" } else { "" });
+        let lbl = Label::new(Some(&txt)); lbl.set_hexpand(true); lbl.set_halign(Align::Start); lbl.set_tooltip_text(Some(&format!("{} {}", m.manufacturer, m.model))); row.append(&lbl);
+        if m.sim_lock == SimLock::Pin {
+            let b = Button::with_label("Unlock SIM");
+            let s=s.clone(); let path = m.path.clone(); let w = win.clone(); let retries = m.unlock_retries;
+            b.connect_clicked(move |_| {
+                let s=s.clone(); let path=path.clone(); let w=w.clone(); let lbl=lbl.clone();
+                glib::MainContext::default().spawn_local(async move {
+                    let Some(pin) = prompt_pin(&w, retries).await else { return };
+                    if let Err(e) = s.network.unlock_modem(path, pin).await { lbl.set_tooltip_text(Some(&e.to_string())); }
+                });
+            });
+            row.append(&b);
+        } else {
+            let sw = Switch::new(); sw.set_valign(Align::Center);
+            sw.set_state(m.connected);
+            let s=s.clone(); let path = m.path.clone();
+            sw.connect_state_set(move |sw, on| {
+                if on == sw.state() { return glib::Propagation::Proceed; }
+                let s=s.clone(); let path=path.clone(); let lbl=lbl.clone(); let sw=sw.clone();
+                glib::MainContext::default().spawn_local(async move {
+                    if let Err(e) = s.network.set_mobile_data(path, on).await { lbl.set_tooltip_text(Some(&e.to_string())); sw.set_state(!on); }
+                });
+                glib::Propagation::Proceed
+            });
+            row.append(&sw);
+        }
+        ml.append(&row);
+    }
+}
+fn fill_wifi_list(wl: &ListBox, nets: Vec<WifiNet>, s: Arc<Syd>, status: &Label) {
+    while let Some(c) = wl.first_child() { wl.remove(&c); }
+    if nets.is_empty() { wl.append(&Label::new(Some("No networks"))); }
+    for n in nets {
+        let row = GtkBox::new(Orientation::Horizontal, 5);
+        let txt = format!("{} ({}%)", n.ssid, n.strength);
+        let lbl = Label::new(Some(&txt)); lbl.set_hexpand(true); lbl.set_halign(Align::Start); row.append(&lbl);
+        lbl.set_tooltip_text(Some(&format!("{} · ch {} · {:?}", n.bssid, n.channel, n.band)));
+        if n.security != "" { row.append(&Label::new(Some("🔒"))); }
+        if n.active {
+            row.append(&Label::new(Some("✅")));
+            let b = Button::with_label("Disconnect"); let s=s.clone();
+            b.connect_clicked(move |_| { let s=s.clone(); glib::MainContext::default().spawn_local(async move {let _=s.network.set_wifi(false).await;}); }); 
+            row.append(&b);
+        } else {
+             // Password prompts come back through SecretsRequested if the network needs one.
+             let b = Button::with_label("Connect"); let s=s.clone(); let ssid=n.ssid.clone(); let st=status.clone();
+             b.connect_clicked(move |_| { let s=s.clone(); let ssid=ssid.clone(); let st=st.clone();
+                 glib::MainContext::default().spawn_local(async move {
+                     if let Err(e) = s.network.connect(ssid, "".into()).await { st.set_label(&connect_error_text(&e)); }
+                 });
+             });
+             row.append(&b);
+        }
+        if n.known {
+             let b = Button::with_label("Forget"); let s=s.clone(); let ssid=n.ssid.clone();
+             b.connect_clicked(move |_| { let s=s.clone(); let ssid=ssid.clone(); glib::MainContext::default().spawn_local(async move {let _=s.network.forget(ssid).await;}); });
+             row.append(&b);
+        }
+        wl.append(&row);
+    }
+}
+fn bt_error_text(e: &BluetoothError) -> String {
+    match e {
+        BluetoothError::NotAvailable(_) => "The device is off or out of range".into(),
+        BluetoothError::AuthFailed(_) => "The device refused the connection; try pairing again".into(),
+        BluetoothError::ProfileUnavailable(_) => "The device offers nothing this computer can use".into(),
+        BluetoothError::Timeout(_) => "The device did not respond".into(),
+        BluetoothError::NotFound(_) => "The device is gone".into(),
+        BluetoothError::Failed(m) => m.clone(),
+        BluetoothError::ZBus(e) => e.to_string(),
+    }
+}
+fn connect_error_text(e: &NetworkError) -> String {
+    match e {
+        NetworkError::WrongPassword(_) => "Wrong password".into(),
+        NetworkError::NotFound(_) => "Network is out of range".into(),
+        NetworkError::Timeout(_) => "The network did not respond".into(),
+        NetworkError::DhcpFailed(_) => "Could not get an IP address".into(),
+        NetworkError::RadioDisabled(_) => "Wi-Fi is turned off".into(),
+        NetworkError::Cancelled(_) => String::new(),
+        NetworkError::Failed(m) => m.clone(),
+        NetworkError::ZBus(e) => e.to_string(),
+    }
 }
 fn create_power_section(syd: Arc<Syd>) -> GtkBox {
     let b = GtkBox::new(Orientation::Vertical, 10);
@@ -465,25 +931,284 @@ fn create_slider(name: &str, syd: Arc<Syd>, is_vol: bool) -> (Scale, GtkBox) {
     s.connect_value_changed(move |sc| { let _ = tx.try_send(sc.value() as u32); });
     (s, b)
 }
-fn create_toast() -> (Revealer, Label) {
+/// The pop-up at the top of the window, for notifications and the shell's own messages.
+#[derive(Clone)]
+struct Toast {
+    revealer: Revealer,
+    label: Label,
+    image: Image,
+    actions: GtkBox,
+    /// The notification on show, 0 for the shell's own messages.
+    id: Rc<std::cell::Cell<u32>>,
+    /// Whether clicking the notification invokes its default action.
+    default: Rc<std::cell::Cell<bool>>,
+    /// Hides the shell's own messages; notifications stay until syd expires or closes them.
+    timer: Rc<RefCell<Option<glib::SourceId>>>,
+    syd: Arc<Syd>,
+}
+impl Toast {
+    fn reveal(&self) {
+        if let Some(t) = self.timer.borrow_mut().take() { t.remove(); }
+        self.revealer.set_reveal_child(true);
+    }
+    fn show(&self, text: &str) {
+        self.label.set_label(text);
+        self.image.set_visible(false);
+        self.label.remove_css_class("error"); self.label.remove_css_class("dim-label");
+        while let Some(c) = self.actions.first_child() { self.actions.remove(&c); }
+        self.id.set(0); self.default.set(false);
+        self.reveal();
+        let (r, timer) = (self.revealer.clone(), self.timer.clone());
+        *self.timer.borrow_mut() = Some(glib::timeout_add_seconds_local(3, move || {
+            timer.borrow_mut().take();
+            r.set_reveal_child(false);
+            glib::ControlFlow::Break
+        }));
+    }
+    fn show_notification(&self, n: &NotifData) {
+        self.set_notification(n);
+        self.reveal();
+    }
+    fn set_notification(&self, n: &NotifData) {
+        self.label.set_label(&format!("{}: {}", n.app_name, n.summary));
+        let icon = if n.image.is_empty() { &n.app_icon } else { &n.image };
+        if icon.starts_with('/') { self.image.set_from_file(Some(icon)); } else { self.image.set_icon_name(Some(icon)); }
+        self.image.set_visible(!icon.is_empty());
+        self.label.remove_css_class("error"); self.label.remove_css_class("dim-label");
+        match n.urgency {
+            Urgency::Critical => self.label.add_css_class("error"),
+            Urgency::Low => self.label.add_css_class("dim-label"),
+            Urgency::Normal => {}
+        }
+        self.id.set(n.id); self.default.set(n.default_action);
+        while let Some(c) = self.actions.first_child() { self.actions.remove(&c); }
+        for a in &n.actions {
+            let b = Button::with_label(&a.label);
+            let s = self.syd.clone(); let r = self.revealer.clone(); let (id, key) = (n.id, a.key.clone());
+            b.connect_clicked(move |_| {
+                r.set_reveal_child(false);
+                let s = s.clone(); let key = key.clone();
+                glib::MainContext::default().spawn_local(async move { let _ = s.notif.invoke_action(id, key).await; });
+            });
+            self.actions.append(&b);
+        }
+        // Critical ones never expire, and the rest may take a while.
+        let close = Button::from_icon_name("window-close-symbolic"); close.add_css_class("flat");
+        let s = self.syd.clone(); let r = self.revealer.clone(); let id = n.id;
+        close.connect_clicked(move |_| {
+            r.set_reveal_child(false);
+            let s = s.clone();
+            glib::MainContext::default().spawn_local(async move { let _ = s.notif.close(id).await; });
+        });
+        self.actions.append(&close);
+    }
+}
+fn create_toast(syd: Arc<Syd>) -> Toast {
     let r = Revealer::builder().valign(Align::Start).halign(Align::Center).transition_type(gtk4::RevealerTransitionType::SlideDown).build();
     let b = GtkBox::new(Orientation::Vertical, 5); b.add_css_class("osd"); b.set_width_request(250);
     let l = Label::new(None); l.set_wrap(true);
-    b.append(&l); r.set_child(Some(&b));
-    (r, l)
+    let img = Image::new(); img.set_pixel_size(48); img.set_visible(false);
+    let acts = GtkBox::new(Orientation::Horizontal, 5); acts.set_halign(Align::Center);
+    b.append(&img); b.append(&l); b.append(&acts); r.set_child(Some(&b));
+    let t = Toast { revealer: r, label: l, image: img, actions: acts, id: Rc::default(), default: Rc::default(), timer: Rc::default(), syd };
+    let click = GestureClick::new();
+    let t1 = t.clone();
+    click.connect_released(move |_, _, _, _| {
+        if !t1.default.get() { return; }
+        t1.revealer.set_reveal_child(false);
+        let s = t1.syd.clone(); let id = t1.id.get();
+        glib::MainContext::default().spawn_local(async move { let _ = s.notif.invoke_action(id, "default".into()).await; });
+    });
+    t.label.add_controller(click);
+    t
 }
-async fn prompt_pass(parent: &ApplicationWindow, ssid: &str) -> Option<String> {
-    let d = gtk4::Window::builder().transient_for(parent).modal(true).title("Password").default_width(300).build();
-    let b = GtkBox::new(Orientation::Vertical, 10); b.set_margin_top(20); b.set_margin_start(20); b.set_margin_end(20); b.set_margin_bottom(20);
-    b.append(&Label::new(Some(&format!("Enter password for {}", ssid))));
-    let e = PasswordEntry::new(); e.set_activates_default(true); b.append(&e);
+async fn prompt_wifi_settings(parent: Option<&gtk4::Window>) -> Option<WifiSettings> {
+    let d = gtk4::Window::builder().modal(true).title("Join network").default_width(320).build();
+    d.set_transient_for(parent);
+    let b = GtkBox::new(Orientation::Vertical, 8); b.set_margin_top(20); b.set_margin_start(20); b.set_margin_end(20); b.set_margin_bottom(20);
+    let ssid = gtk4::Entry::builder().placeholder_text("Network name").build(); b.append(&ssid);
+    let hidden = gtk4::CheckButton::with_label("Hidden network"); b.append(&hidden);
+    let sec = DropDown::from_strings(&["Open", "WPA2 Personal", "WPA3 Personal", "WPA2/3 Enterprise"]); sec.set_selected(1); b.append(&sec);
+    let ent = GtkBox::new(Orientation::Vertical, 8); ent.set_visible(false);
+    let eap = DropDown::from_strings(&["PEAP", "TTLS", "TLS"]); ent.append(&eap);
+    let identity = gtk4::Entry::builder().placeholder_text("Identity").build(); ent.append(&identity);
+    let anon = gtk4::Entry::builder().placeholder_text("Anonymous identity").build(); ent.append(&anon);
+    let ca = gtk4::Entry::builder().placeholder_text("CA certificate path").build(); ent.append(&ca);
+    let phase2 = gtk4::Entry::builder().placeholder_text("Inner auth (mschapv2)").build(); ent.append(&phase2);
+    b.append(&ent);
+    let pass = PasswordEntry::builder().placeholder_text("Password").build(); b.append(&pass);
+    let ent_c = ent.clone();
+    sec.connect_selected_notify(move |dd| ent_c.set_visible(dd.selected() == 3));
     let row = GtkBox::new(Orientation::Horizontal, 10); row.set_halign(Align::End);
     let cn = Button::with_label("Cancel"); let ok = Button::with_label("Connect"); ok.add_css_class("suggested-action"); d.set_default_widget(Some(&ok));
     row.append(&cn); row.append(&ok); b.append(&row); d.set_child(Some(&b)); d.present();
     let (tx, rx) = futures::channel::oneshot::channel(); let tx = Rc::new(RefCell::new(Some(tx)));
-    let tx1 = tx.clone(); let d1 = d.clone(); let e1 = e.clone();
-    ok.connect_clicked(move |_| { if let Some(t) = tx1.borrow_mut().take() { let _=t.send(Some(e1.text().to_string())); } d1.close(); });
-    let tx2 = tx.clone(); let d2 = d.clone();
-    cn.connect_clicked(move |_| { if let Some(t) = tx2.borrow_mut().take() { let _=t.send(None); } d2.close(); });
+    let tx1 = tx.clone(); let d1 = d.clone();
+    ok.connect_clicked(move |_| {
+        let settings = WifiSettings {
+            ssid: ssid.text().to_string(),
+            hidden: hidden.is_active(),
+            key_mgmt: match sec.selected() { 0 => KeyMgmt::Open, 2 => KeyMgmt::Wpa3Sae, 3 => KeyMgmt::Enterprise, _ => KeyMgmt::Wpa2 },
+            password: pass.text().to_string(),
+            eap: match eap.selected() { 1 => EapMethod::Ttls, 2 => EapMethod::Tls, _ => EapMethod::Peap },
+            identity: identity.text().to_string(),
+            anonymous_identity: anon.text().to_string(),
+            ca_cert: ca.text().to_string(),
+            phase2_auth: phase2.text().to_string(),
+            ..Default::default()
+        };
+        if let Some(t) = tx1.borrow_mut().take() { let _=t.send(Some(settings)); } d1.close();
+    });
+    let tx2 = tx.clone();
+    d.connect_close_request(move |_| { if let Some(t) = tx2.borrow_mut().take() { let _=t.send(None); } glib::Propagation::Proceed });
+    let d2 = d.clone();
+    cn.connect_clicked(move |_| d2.close());
     rx.await.unwrap_or(None)
 }
+async fn prompt_hotspot(parent: Option<&gtk4::Window>) -> Option<(String, String, WifiBand)> {
+    let d = gtk4::Window::builder().modal(true).title("Start hotspot").default_width(300).build();
+    d.set_transient_for(parent);
+    let b = GtkBox::new(Orientation::Vertical, 8); b.set_margin_top(20); b.set_margin_start(20); b.set_margin_end(20); b.set_margin_bottom(20);
+    let ssid = gtk4::Entry::builder().placeholder_text("Network name").text(glib::host_name().as_str()).build(); b.append(&ssid);
+    let pass = PasswordEntry::builder().placeholder_text("Password (8+ characters)").show_peek_icon(true).build(); b.append(&pass);
+    let band = DropDown::from_strings(&["Any band", "2.4 GHz", "5 GHz"]); b.append(&band);
+    let row = GtkBox::new(Orientation::Horizontal, 10); row.set_halign(Align::End);
+    let cn = Button::with_label("Cancel"); let ok = Button::with_label("Start"); ok.add_css_class("suggested-action"); d.set_default_widget(Some(&ok));
+    row.append(&cn); row.append(&ok); b.append(&row); d.set_child(Some(&b)); d.present();
+    let (tx, rx) = futures::channel::oneshot::channel(); let tx = Rc::new(RefCell::new(Some(tx)));
+    let tx1 = tx.clone(); let d1 = d.clone();
+    ok.connect_clicked(move |_| {
+        let band = match band.selected() { 1 => WifiBand::Ghz2_4, 2 => WifiBand::Ghz5, _ => WifiBand::Unknown };
+        if let Some(t) = tx1.borrow_mut().take() { let _=t.send(Some((ssid.text().to_string(), pass.text().to_string(), band))); } d1.close();
+    });
+    let tx2 = tx.clone();
+    d.connect_close_request(move |_| { if let Some(t) = tx2.borrow_mut().take() { let _=t.send(None); } glib::Propagation::Proceed });
+    let d2 = d.clone();
+    cn.connect_clicked(move |_| d2.close());
+    rx.await.unwrap_or(None)
+}
+async fn prompt_pin(parent: &ApplicationWindow, retries: u32) -> Option<String> {
+    let d = gtk4::Window::builder().transient_for(parent).modal(true).title("SIM PIN").default_width(280).build();
+    let b = GtkBox::new(Orientation::Vertical, 8); b.set_margin_top(20); b.set_margin_start(20); b.set_margin_end(20); b.set_margin_bottom(20);
+    if retries > 0 { b.append(&Label::new(Some(&format!("{} attempts left", retries)))); }
+    let pin = PasswordEntry::builder().placeholder_text("PIN").activates_default(true).build(); b.append(&pin);
+    let row = GtkBox::new(Orientation::Horizontal, 10); row.set_halign(Align::End);
+    let cn = Button::with_label("Cancel"); let ok = Button::with_label("Unlock"); ok.add_css_class("suggested-action"); d.set_default_widget(Some(&ok));
+    row.append(&cn); row.append(&ok); b.append(&row); d.set_child(Some(&b)); d.present();
+    let (tx, rx) = futures::channel::oneshot::channel(); let tx = Rc::new(RefCell::new(Some(tx)));
+    let tx1 = tx.clone(); let d1 = d.clone();
+    ok.connect_clicked(move |_| { if let Some(t) = tx1.borrow_mut().take() { let _=t.send(Some(pin.text().to_string())); } d1.close(); });
+    let tx2 = tx.clone();
+    d.connect_close_request(move |_| { if let Some(t) = tx2.borrow_mut().take() { let _=t.send(None); } glib::Propagation::Proceed });
+    let d2 = d.clone();
+    cn.connect_clicked(move |_| d2.close());
+    rx.await.unwrap_or(None)
+}
+fn show_portal(parent: &ApplicationWindow, url: &str) {
+    let d = gtk4::Window::builder().transient_for(parent).title("Sign in to network").default_width(300).build();
+    let b = GtkBox::new(Orientation::Vertical, 10); b.set_margin_top(20); b.set_margin_start(20); b.set_margin_end(20); b.set_margin_bottom(20);
+    b.append(&Label::new(Some("This network needs you to sign in before it gives internet access.")));
+    let row = GtkBox::new(Orientation::Horizontal, 10); row.set_halign(Align::End);
+    let cn = Button::with_label("Later"); let ok = Button::with_label("Open sign-in page"); ok.add_css_class("suggested-action");
+    row.append(&cn); row.append(&ok); b.append(&row); d.set_child(Some(&b)); d.present();
+    let url = url.to_string(); let d1 = d.clone();
+    ok.connect_clicked(move |_| { let _ = gtk4::gio::AppInfo::launch_default_for_uri(&url, None::<&gtk4::gio::AppLaunchContext>); d1.close(); });
+    let d2 = d.clone();
+    cn.connect_clicked(move |_| d2.close());
+}
+fn show_qr(parent: Option<&gtk4::Window>, ssid: &str, png: &[u8]) {
+    let d = gtk4::Window::builder().title(ssid).default_width(300).default_height(340).build();
+    d.set_transient_for(parent);
+    let b = GtkBox::new(Orientation::Vertical, 10); b.set_margin_top(20); b.set_margin_start(20); b.set_margin_end(20); b.set_margin_bottom(20);
+    if let Ok(pb) = gtk4::gdk_pixbuf::Pixbuf::from_read(std::io::Cursor::new(png.to_vec())) {
+        let pic = gtk4::Picture::for_paintable(&gtk4::gdk::Texture::for_pixbuf(&pb)); pic.set_vexpand(true); b.append(&pic);
+    }
+    b.append(&Label::new(Some(&format!("Scan to join {}", ssid))));
+    d.set_child(Some(&b)); d.present();
+}
+async fn prompt_pairing(parent: &ApplicationWindow, req: &PairingRequest, prompts: &Rc<RefCell<HashMap<u32, gtk4::Window>>>) -> Option<String> {
+    let d = gtk4::Window::builder().transient_for(parent).modal(true).title("Bluetooth pairing").default_width(300).build();
+    let b = GtkBox::new(Orientation::Vertical, 10); b.set_margin_top(20); b.set_margin_start(20); b.set_margin_end(20); b.set_margin_bottom(20);
+    let msg = match req.kind {
+        PairingKind::PinCode => format!("Enter the PIN for {}", req.alias),
+        PairingKind::Passkey => format!("Enter the passkey shown on {}", req.alias),
+        PairingKind::DisplayPasskey | PairingKind::DisplayPinCode => format!("Type {} on {} and press Enter", req.passkey, req.alias),
+        PairingKind::Confirmation => format!("Does {} show {}?", req.alias, req.passkey),
+        PairingKind::Authorization => format!("Pair with {}?", req.alias),
+        PairingKind::AuthorizeService => format!("Allow {} to connect?", req.alias),
+    };
+    let lbl = Label::new(Some(&msg)); lbl.set_wrap(true); b.append(&lbl);
+    let entry = PasswordEntry::builder().activates_default(true).show_peek_icon(true).build();
+    let asks_code = matches!(req.kind, PairingKind::PinCode | PairingKind::Passkey);
+    if asks_code { b.append(&entry); }
+    let shows_code = matches!(req.kind, PairingKind::DisplayPasskey | PairingKind::DisplayPinCode);
+    let row = GtkBox::new(Orientation::Horizontal, 10); row.set_halign(Align::End);
+    let cn = Button::with_label("Cancel"); row.append(&cn);
+    let ok = Button::with_label(if asks_code { "Pair" } else { "Allow" }); ok.add_css_class("suggested-action");
+    if !shows_code { d.set_default_widget(Some(&ok)); row.append(&ok); }
+    b.append(&row); d.set_child(Some(&b)); d.present();
+    prompts.borrow_mut().insert(req.id, d.clone());
+    let (tx, rx) = futures::channel::oneshot::channel(); let tx = Rc::new(RefCell::new(Some(tx)));
+    let tx1 = tx.clone(); let d1 = d.clone();
+    ok.connect_clicked(move |_| { if let Some(t) = tx1.borrow_mut().take() { let _=t.send(Some(entry.text().to_string())); } d1.close(); });
+    let tx2 = tx.clone();
+    d.connect_close_request(move |_| { if let Some(t) = tx2.borrow_mut().take() { let _=t.send(None); } glib::Propagation::Proceed });
+    let d2 = d.clone();
+    cn.connect_clicked(move |_| d2.close());
+    let res = rx.await.unwrap_or(None);
+    prompts.borrow_mut().remove(&req.id);
+    res
+}
+async fn prompt_transfer(parent: &ApplicationWindow, req: &TransferRequest, prompts: &Rc<RefCell<HashMap<u32, gtk4::Window>>>) -> bool {
+    let d = gtk4::Window::builder().transient_for(parent).modal(true).title("Incoming file").default_width(300).build();
+    let b = GtkBox::new(Orientation::Vertical, 10); b.set_margin_top(20); b.set_margin_start(20); b.set_margin_end(20); b.set_margin_bottom(20);
+    let size = if req.size > 0 { format!(" ({})", fmt_bytes(req.size)) } else { String::new() };
+    let lbl = Label::new(Some(&format!("{} wants to send you {}{}", req.alias, req.name, size))); lbl.set_wrap(true); b.append(&lbl);
+    let row = GtkBox::new(Orientation::Horizontal, 10); row.set_halign(Align::End);
+    let cn = Button::with_label("Decline"); row.append(&cn);
+    let ok = Button::with_label("Accept"); ok.add_css_class("suggested-action"); row.append(&ok);
+    d.set_default_widget(Some(&ok));
+    b.append(&row); d.set_child(Some(&b)); d.present();
+    prompts.borrow_mut().insert(req.id, d.clone());
+    let (tx, rx) = futures::channel::oneshot::channel(); let tx = Rc::new(RefCell::new(Some(tx)));
+    let tx1 = tx.clone(); let d1 = d.clone();
+    ok.connect_clicked(move |_| { if let Some(t) = tx1.borrow_mut().take() { let _=t.send(true); } d1.close(); });
+    let tx2 = tx.clone();
+    d.connect_close_request(move |_| { if let Some(t) = tx2.borrow_mut().take() { let _=t.send(false); } glib::Propagation::Proceed });
+    let d2 = d.clone();
+    cn.connect_clicked(move |_| d2.close());
+    let res = rx.await.unwrap_or(false);
+    prompts.borrow_mut().remove(&req.id);
+    res
+}
+async fn prompt_secrets(parent: &ApplicationWindow, req: &SecretRequest, prompts: &Rc<RefCell<HashMap<u32, gtk4::Window>>>) -> Option<HashMap<String, String>> {
+    let d = gtk4::Window::builder().transient_for(parent).modal(true).title("Authentication required").default_width(300).build();
+    let b = GtkBox::new(Orientation::Vertical, 10); b.set_margin_top(20); b.set_margin_start(20); b.set_margin_end(20); b.set_margin_bottom(20);
+    let name = if req.ssid.is_empty() { &req.connection } else { &req.ssid };
+    let msg = if req.retry { format!("Wrong credentials for {}, try again", name) } else { format!("Enter credentials for {}", name) };
+    b.append(&Label::new(Some(&msg)));
+    let mut entries: Vec<(String, gtk4::Editable)> = Vec::new();
+    for f in &req.fields {
+        b.append(&Label::builder().label(f.as_str()).halign(Align::Start).build());
+        let e: gtk4::Editable = if f == "identity" { gtk4::Entry::new().upcast() } else { let p = PasswordEntry::new(); p.set_activates_default(true); p.upcast() };
+        b.append(&e);
+        entries.push((f.clone(), e));
+    }
+    let row = GtkBox::new(Orientation::Horizontal, 10); row.set_halign(Align::End);
+    let cn = Button::with_label("Cancel"); let ok = Button::with_label("Connect"); ok.add_css_class("suggested-action"); d.set_default_widget(Some(&ok));
+    row.append(&cn); row.append(&ok); b.append(&row); d.set_child(Some(&b)); d.present();
+    prompts.borrow_mut().insert(req.id, d.clone());
+    let (tx, rx) = futures::channel::oneshot::channel(); let tx = Rc::new(RefCell::new(Some(tx)));
+    let tx1 = tx.clone(); let d1 = d.clone();
+    ok.connect_clicked(move |_| {
+        let vals = entries.iter().map(|(k, e)| (k.clone(), e.text().to_string())).collect();
+        if let Some(t) = tx1.borrow_mut().take() { let _=t.send(Some(vals)); } d1.close();
+    });
+    let tx2 = tx.clone();
+    d.connect_close_request(move |_| { if let Some(t) = tx2.borrow_mut().take() { let _=t.send(None); } glib::Propagation::Proceed });
+    let d2 = d.clone();
+    cn.connect_clicked(move |_| d2.close());
+    let res = rx.await.unwrap_or(None);
+    prompts.borrow_mut().remove(&req.id);
+    res
+}